@@ -4,8 +4,9 @@ use gtk4::{
     gdk::RGBA,
     glib::{self, FromVariant, StaticVariantType, ToVariant},
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Colour {
     pub red: u8,
     pub green: u8,
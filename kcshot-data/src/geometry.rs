@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 mod point;
 mod rectangle;
 
@@ -10,7 +12,7 @@ pub use rectangle::*;
 /// * has a radius of w/2 (= a) in the x axis
 /// * has a radius of h/2 (= b) in the y axis
 /// * center is at (x + w/2, y + h/2) (= (x0, y0))
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Ellipse {
     pub x: f64,
     pub y: f64,
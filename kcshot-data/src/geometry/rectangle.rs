@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use super::Point;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Rectangle {
     pub x: f64,
     pub y: f64,
@@ -1,13 +1,43 @@
 use colour::Colour;
 use gtk4::pango::FontDescription;
+use serde::{Deserialize, Serialize};
 
 pub mod colour;
 pub mod geometry;
+pub mod serialisation;
 pub mod settings;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Text {
     pub string: String,
+    /// What the user typed in, `string` is generated from it
+    pub markdown: String,
+    #[serde(with = "serialisation::font_description")]
     pub font_description: FontDescription,
     pub colour: Colour,
+    pub style: TextStyle,
+}
+
+/// Describes the box drawn behind text, along with the effects applied to the text itself
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextStyle {
+    /// The colour of the box behind the text, there is no box when this is `None`
+    pub background: Option<Colour>,
+    /// The space between the text and the edges of its box
+    pub padding: f64,
+    pub corner_radius: f64,
+    /// The width of the outline drawn around the glyphs, there is no outline when this is 0
+    pub outline_width: f64,
+    pub shadow: bool,
+}
+
+impl TextStyle {
+    /// Returns the padding around the text, which only exists when there is a box
+    pub fn effective_padding(&self) -> f64 {
+        if self.background.is_some() {
+            self.padding
+        } else {
+            0.0
+        }
+    }
 }
@@ -0,0 +1,30 @@
+//! Helpers for (de)serialising types which don't implement serde's traits themselves, use them
+//! through `#[serde(with = "...")]`
+
+/// (De)serialises a [`FontDescription`] as the string returned by [`FontDescription::to_str`]
+///
+/// [`FontDescription`]: gtk4::pango::FontDescription
+/// [`FontDescription::to_str`]: gtk4::pango::FontDescription::to_str
+pub mod font_description {
+    use gtk4::pango::FontDescription;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(
+        font_description: &FontDescription,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&font_description.to_str())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<FontDescription, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let description = String::deserialize(deserializer)?;
+
+        Ok(FontDescription::from_string(&description))
+    }
+}
@@ -13,6 +13,11 @@ use crate::colour::Colour;
     arg_type = "Colour",
     ret_type = "Colour"
 )]
+#[gen_settings_define(
+    key_name = "highlight-colour",
+    arg_type = "Colour",
+    ret_type = "Colour"
+)]
 pub struct Settings;
 
 impl Settings {
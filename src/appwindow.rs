@@ -18,7 +18,7 @@ impl AppWindow {
 }
 
 mod underlying {
-    use std::{cell::RefCell, process::Command};
+    use std::{cell::RefCell, path::Path, process::Command};
 
     use gtk4::{
         glib::{self, clone, ParamSpec, Properties},
@@ -28,7 +28,12 @@ mod underlying {
     use kcshot_data::settings::Settings;
     use once_cell::unsync::OnceCell;
 
-    use crate::{editor::EditorWindow, historymodel::RowData, kcshot::KCShot};
+    use crate::{
+        editor::{project::Project, EditorWindow},
+        historymodel::RowData,
+        kcshot::KCShot,
+        postcapture,
+    };
 
     #[derive(Debug, Properties)]
     #[properties(wrapper_type = super::AppWindow)]
@@ -160,6 +165,32 @@ mod underlying {
                 .width_request(300)
                 .build();
 
+            // Screenshots saved along with a project can be reopened in the editor
+            let click_controller = gtk4::GestureClick::new();
+            click_controller.connect_released(|this, n_press, _x, _y| {
+                if n_press != 2 {
+                    return;
+                }
+
+                let Some(path) = this
+                    .widget()
+                    .downcast::<gtk4::Picture>()
+                    .ok()
+                    .and_then(|picture| picture.file())
+                    .and_then(|file| file.path())
+                else {
+                    return;
+                };
+
+                let project_path = Project::path_for(&path);
+                if project_path.is_dir() {
+                    EditorWindow::show_project(KCShot::the().upcast_ref(), &project_path);
+                } else {
+                    tracing::info!("There is no project to reopen for {path:?}");
+                }
+            });
+            picture.add_controller(click_controller);
+
             list_item.set_child(Some(&picture));
         });
 
@@ -175,6 +206,8 @@ mod underlying {
                 .expect("The child has to exist and it should be a gtk4::Picture");
 
             if let Some(path) = object.path() {
+                let has_project = Project::path_for(Path::new(&path)).is_dir();
+                picture.set_tooltip_text(has_project.then_some("Double-click to edit"));
                 picture.set_filename(Some(&path));
             }
         });
@@ -282,6 +315,44 @@ mod underlying {
 
         content_area.append(&hbox);
 
+        let stamps_chooser = gtk4::FileChooserDialog::new(
+            Some("Choose a folder with images to use as stamps"),
+            Some(&window),
+            gtk4::FileChooserAction::SelectFolder,
+            &[
+                ("Cancel", gtk4::ResponseType::Cancel),
+                ("Apply", gtk4::ResponseType::Apply),
+            ],
+        );
+        let settings_ = settings.clone();
+        stamps_chooser.connect_response(move |this, response| {
+            if response == gtk4::ResponseType::Apply {
+                let folder = this.file().unwrap();
+                settings_.set_stamps_path(
+                    &folder
+                        .path()
+                        .and_then(|path| path.to_str().map(str::to_owned))
+                        .unwrap(),
+                );
+            }
+            this.destroy();
+        });
+
+        let stamps_chooser_about = gtk4::Label::new(Some("Stamps directory"));
+        let stamps_chooser_button = gtk4::Button::new();
+        settings
+            .bind_stamps_path(&stamps_chooser_button, "label")
+            .build();
+        stamps_chooser_button.connect_clicked(move |_| {
+            stamps_chooser.show();
+        });
+
+        let stamps_hbox = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        stamps_hbox.append(&stamps_chooser_about);
+        stamps_hbox.append(&stamps_chooser_button);
+
+        content_area.append(&stamps_hbox);
+
         let history_enabled_label = gtk4::Label::new(Some("Enable history"));
         history_enabled_label.set_halign(gtk4::Align::Start);
         let history_enabled_button = gtk4::Switch::new();
@@ -343,14 +414,111 @@ mod underlying {
         content_area.set_margin_start(10);
         content_area.set_margin_end(10);
 
+        let include_decorations_label = gtk4::Label::builder()
+            .label("Include window decorations when picking windows")
+            .tooltip_text(
+                "Hold Shift while clicking a window to get the other option, holding it while \
+                 dragging out a region makes the region square instead",
+            )
+            .halign(gtk4::Align::Start)
+            .build();
+        let include_decorations_button = gtk4::Switch::builder().halign(gtk4::Align::End).build();
+        settings
+            .bind_window_selection_includes_decorations(&include_decorations_button, "active")
+            .build();
+        let include_decorations_container = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(6)
+            .homogeneous(true)
+            .build();
+        include_decorations_container.append(&include_decorations_label);
+        include_decorations_container.append(&include_decorations_button);
+
+        content_area.append(&include_decorations_container);
+
+        let save_editable_projects_label = gtk4::Label::builder()
+            .label("Keep annotations editable")
+            .tooltip_text("Saved screenshots can be reopened from the history with all of their annotations still editable")
+            .halign(gtk4::Align::Start)
+            .build();
+        let save_editable_projects_button =
+            gtk4::Switch::builder().halign(gtk4::Align::End).build();
+        settings
+            .bind_save_editable_projects(&save_editable_projects_button, "active")
+            .build();
+        let save_editable_projects_container = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(6)
+            .homogeneous(true)
+            .build();
+        save_editable_projects_container.append(&save_editable_projects_label);
+        save_editable_projects_container.append(&save_editable_projects_button);
+
+        content_area.append(&save_editable_projects_container);
+
         let notebook = gtk4::Notebook::new();
         notebook.append_page(&content_area, Some(&gtk4::Label::new(Some("General"))));
+        notebook.append_page(
+            &build_post_capture_actions_page(&settings),
+            Some(&gtk4::Label::new(Some("After capturing"))),
+        );
 
         window.set_child(Some(&notebook));
 
         window
     }
 
+    fn build_post_capture_actions_page(settings: &Settings) -> gtk4::Box {
+        let page = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(4)
+            .margin_top(5)
+            .margin_bottom(10)
+            .margin_start(10)
+            .margin_end(10)
+            .build();
+
+        for action in postcapture::get_postcapture_actions() {
+            let id = action.id();
+
+            let label = gtk4::Label::builder()
+                .label(action.name())
+                .tooltip_text(action.description())
+                .halign(gtk4::Align::Start)
+                .build();
+            let button = gtk4::Switch::builder()
+                .halign(gtk4::Align::End)
+                .active(settings.post_capture_actions().contains(&id))
+                .build();
+
+            // Newly enabled actions are run after the ones already enabled
+            button.connect_active_notify(clone!(@strong settings => move |this| {
+                let mut actions = settings.post_capture_actions();
+                actions.retain(|action| *action != id);
+                if this.is_active() {
+                    actions.push(id.clone());
+                }
+
+                let actions = actions.iter().map(String::as_str).collect::<Vec<_>>();
+                if let Err(why) = settings.try_set_post_capture_actions(&actions) {
+                    tracing::warn!("Failed to update `post-capture-actions` setting value: {why}");
+                }
+            }));
+
+            let container = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .homogeneous(true)
+                .build();
+            container.append(&label);
+            container.append(&button);
+
+            page.append(&container);
+        }
+
+        page
+    }
+
     fn make_label(text: &str) -> gtk4::Label {
         let label = gtk4::Label::new(Some(text));
         label.set_halign(gtk4::Align::Start);
@@ -1,20 +1,34 @@
+use std::path::Path;
+
 use gtk4::{
     gio,
     glib::{self, CastNone},
     subclass::prelude::ObjectSubclassIsExt,
     traits::{GtkWindowExt, NativeExt, WidgetExt},
 };
-use kcshot_data::{colour::Colour, settings::Settings};
+use kcshot_data::{colour::Colour, geometry::Rectangle, settings::Settings};
 
-use self::operations::Tool;
+use self::{
+    operations::{
+        ArrowheadStyle, CropConstraint, FilterKind, InsetShape, MeasureKind, NumberStyle,
+        OperationStack, SelectionMode, SpotlightEffect, SpotlightShape, StampSource, StrokeStyle,
+        Tool, ToolOptions, DEFAULT_LINE_WIDTH,
+    },
+    project::Project,
+};
+use crate::kcshot::KCShot;
 
 mod colourchooser;
 mod colourwheel;
+mod cropdialog;
+mod loupe;
 mod operations;
+pub mod project;
 mod textdialog;
 mod toolbar;
 mod underlying;
 mod utils;
+mod viewport;
 
 glib::wrapper! {
     pub struct EditorWindow(ObjectSubclass<underlying::EditorWindow>)
@@ -24,29 +38,134 @@ glib::wrapper! {
 
 impl EditorWindow {
     pub fn new(app: &gtk4::Application, editing_starts_with_cropping: bool) -> Self {
-        let editor = glib::Object::builder::<Self>()
+        let editor = Self::build(app, editing_starts_with_cropping);
+
+        let image = kcshot_screenshot::take_screenshot(KCShot::the().tokio_rt())
+            .expect("Couldn't take a screenshot");
+        let windows = kcshot_screenshot::get_windows().unwrap_or_else(|why| {
+            tracing::info!("Got while trying to retrieve windows: {why}");
+            vec![]
+        });
+        let screen_dimensions = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            w: image.width() as f64,
+            h: image.height() as f64,
+        };
+
+        editor.imp().set_image(
+            image,
+            OperationStack::new(windows, screen_dimensions, editing_starts_with_cropping),
+        );
+        editor.restore_settings();
+
+        editor
+    }
+
+    /// Makes an editor for a project saved by a previous editing session
+    ///
+    /// Projects are always opened in "crop-first" mode, with the saved crop region already applied
+    /// and the toolbar shown.
+    pub fn from_project(app: &gtk4::Application, project: Project) -> Self {
+        let editor = Self::build(app, true);
+
+        let screen_dimensions = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            w: project.surface.width() as f64,
+            h: project.surface.height() as f64,
+        };
+
+        let mut operation_stack = OperationStack::new(vec![], screen_dimensions, true);
+        operation_stack.restore_project(
+            project.crop.unwrap_or(screen_dimensions),
+            project.operations,
+        );
+
+        editor.imp().set_image(project.surface, operation_stack);
+        editor.restore_settings();
+        // The toolbar starts out with the pencil picked, as the crop region was already chosen
+        editor.set_current_tool(Tool::Pencil);
+
+        if let Some(toolbar) = editor.imp().toolbar.get() {
+            toolbar.set_visible(true);
+        }
+
+        editor
+    }
+
+    fn build(app: &gtk4::Application, editing_starts_with_cropping: bool) -> Self {
+        glib::Object::builder::<Self>()
             .property("application", app)
             .property("editing-starts-with-cropping", editing_starts_with_cropping)
-            .build();
+            .build()
+    }
 
+    fn restore_settings(&self) {
         let settings = Settings::open();
 
+        let crop_constraint =
+            CropConstraint::from_preset(&settings.crop_preset()).unwrap_or_default();
+        let selection_mode = if settings.window_selection_includes_decorations() {
+            SelectionMode::WindowsWithDecorations
+        } else {
+            SelectionMode::WindowsWithoutDecorations
+        };
+        self.imp().with_image_mut("restore settings", |image| {
+            image.operation_stack.set_crop_constraint(crop_constraint);
+            image
+                .operation_stack
+                .set_preferred_selection_mode(selection_mode);
+            image.operation_stack.keep_measurements = settings.keep_measurements();
+            image.operation_stack.tool_options.text_size = settings.text_size();
+
+            let effect_strength = &mut image.operation_stack.tool_options.effect_strength;
+            effect_strength.blur_radius = settings.blur_radius() as f32;
+            effect_strength.pixelate_size = settings.pixelate_size() as u64;
+
+            let bubbles = &mut image.operation_stack.bubbles;
+            bubbles.radius = settings.bubble_radius();
+            bubbles.number_style =
+                NumberStyle::from_name(&settings.bubble_number_style()).unwrap_or_default();
+            bubbles.auto_size = settings.bubble_auto_size();
+            image
+                .operation_stack
+                .set_bubble_start_number(settings.bubble_start_number());
+        });
+
         let restored_primary_colour = settings.last_used_primary_colour();
         let restored_secondary_colour = settings.last_used_secondary_colour();
 
-        editor.set_primary_colour(restored_primary_colour);
-        editor.set_secondary_colour(restored_secondary_colour);
-
-        editor
+        self.set_primary_colour(restored_primary_colour);
+        self.set_secondary_colour(restored_secondary_colour);
+        self.set_highlight_colour(settings.highlight_colour());
     }
 
     pub fn show(app: &gtk4::Application, editing_starts_with_cropping: bool) {
-        let window = Self::new(app, editing_starts_with_cropping);
-        window.set_decorated(false);
-        window.show();
-        window.fullscreen();
+        Self::new(app, editing_starts_with_cropping).present_fullscreen();
+    }
 
-        let surface = window
+    /// Reopens the project stored at `path` in a new editor window
+    pub fn show_project(app: &gtk4::Application, path: &Path) {
+        match Project::load(path) {
+            Ok(project) => {
+                for file in project.missing_stamps() {
+                    tracing::warn!(
+                        "The project at {path:?} has a stamp whose image, {file:?}, is missing"
+                    );
+                }
+                Self::from_project(app, project).present_fullscreen();
+            }
+            Err(why) => tracing::error!("Failed to open project at {path:?}: {why}"),
+        }
+    }
+
+    fn present_fullscreen(&self) {
+        self.set_decorated(false);
+        self.show();
+        self.fullscreen();
+
+        let surface = self
             .native()
             .map(|native| native.surface())
             .and_downcast::<gdk4_x11::X11Surface>();
@@ -63,9 +182,43 @@ impl EditorWindow {
     }
 
     fn set_current_tool(&self, tool: Tool) {
+        let line_width = tool.has_line_width().then(|| saved_line_width(tool));
         self.imp().with_image_mut("set_current_tool", |image| {
             image.operation_stack.set_current_tool(tool);
+            if let Some(line_width) = line_width {
+                image.operation_stack.line_width = line_width;
+            }
         });
+
+        // The toolbar has to be told outside of `with_image_mut`, as it updates the stack when the
+        // width it shows changes
+        if let (Some(toolbar), Some(line_width)) = (self.imp().toolbar.get(), line_width) {
+            toolbar.show_line_width(line_width);
+        }
+    }
+
+    /// Makes the size the current tool draws with (its line width, text size or bubble size)
+    /// `steps` steps bigger, or smaller when `steps` is negative, and previews it around the cursor
+    fn adjust_tool_size(&self, steps: f64) {
+        let Some(tool) = self.imp().with_image("adjust_tool_size", |image| {
+            image.operation_stack.current_tool()
+        }) else {
+            return;
+        };
+
+        if let Some(toolbar) = self.imp().toolbar.get() {
+            toolbar.adjust_tool_size(tool, steps);
+        }
+
+        let size = self
+            .imp()
+            .with_image("adjust_tool_size preview", |image| {
+                image.operation_stack.current_tool_size()
+            })
+            .flatten();
+        if let Some(size) = size {
+            self.imp().preview_tool_size(size);
+        }
     }
 
     /// Returns the primary colour of the editor
@@ -83,6 +236,11 @@ impl EditorWindow {
     fn set_primary_colour(&self, colour: Colour) {
         self.imp().with_image_mut("set_primary_colour", |image| {
             image.operation_stack.primary_colour = colour;
+            image
+                .operation_stack
+                .edit_selection("primary-colour", |operation| {
+                    operation.set_primary_colour(colour)
+                });
         });
 
         let settings = Settings::open();
@@ -107,6 +265,11 @@ impl EditorWindow {
     fn set_secondary_colour(&self, colour: Colour) {
         self.imp().with_image_mut("set_secondary_colour", |image| {
             image.operation_stack.secondary_colour = colour;
+            image
+                .operation_stack
+                .edit_selection("secondary-colour", |operation| {
+                    operation.set_secondary_colour(colour)
+                });
         });
 
         let settings = Settings::open();
@@ -115,9 +278,331 @@ impl EditorWindow {
         }
     }
 
+    /// Returns the colour of highlights, which is kept apart from the other colours as it needs to
+    /// be translucent or light for the highlighted content to stay legible
+    #[track_caller]
+    fn highlight_colour(&self) -> Colour {
+        self.imp()
+            .with_image("get highlight_colour", |image| {
+                image.operation_stack.tool_options.highlight_colour
+            })
+            .unwrap()
+    }
+
+    fn set_highlight_colour(&self, colour: Colour) {
+        self.imp().with_image_mut("set_highlight_colour", |image| {
+            image.operation_stack.tool_options.highlight_colour = colour;
+            image
+                .operation_stack
+                .edit_selection("highlight-colour", |operation| {
+                    operation.set_highlight_colour(colour)
+                });
+        });
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_highlight_colour(colour) {
+            tracing::warn!("Failed to update `highlight-colour` setting value: {why}");
+        }
+    }
+
+    fn set_crop_constraint(&self, crop_constraint: CropConstraint) {
+        self.imp().with_image_mut("set_crop_constraint", |image| {
+            image.operation_stack.set_crop_constraint(crop_constraint);
+            image.operation_stack.constrain_crop_region();
+        });
+        self.queue_draw();
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_crop_preset(&crop_constraint.to_preset()) {
+            tracing::warn!("Failed to update `crop-preset` setting value: {why}");
+        }
+    }
+
+    fn set_measure_kind(&self, measure_kind: MeasureKind) {
+        self.imp().with_image_mut("set_measure_kind", |image| {
+            image.operation_stack.tool_options.measure_kind = measure_kind;
+        });
+    }
+
+    fn set_spotlight_shape(&self, shape: SpotlightShape) {
+        self.imp().with_image_mut("set_spotlight_shape", |image| {
+            image.operation_stack.tool_options.spotlight_style.shape = shape;
+        });
+    }
+
+    fn set_spotlight_effect(&self, effect: SpotlightEffect) {
+        self.imp().with_image_mut("set_spotlight_effect", |image| {
+            image.operation_stack.tool_options.spotlight_style.effect = effect;
+        });
+    }
+
+    fn set_arrowhead_style(&self, style: ArrowheadStyle) {
+        self.imp().with_image_mut("set_arrowhead_style", |image| {
+            image.operation_stack.tool_options.arrowhead = style;
+        });
+    }
+
+    /// The options new operations are made with, which the toolbar starts out showing
+    #[track_caller]
+    fn tool_options(&self) -> ToolOptions {
+        self.imp()
+            .with_image("get tool_options", |image| {
+                image.operation_stack.tool_options.clone()
+            })
+            .unwrap()
+    }
+
+    #[track_caller]
+    fn corner_radius(&self) -> f64 {
+        self.imp()
+            .with_image("get corner_radius", |image| {
+                image.operation_stack.tool_options.corner_radius
+            })
+            .unwrap()
+    }
+
+    fn set_corner_radius(&self, radius: f64) {
+        self.imp().with_image_mut("set_corner_radius", |image| {
+            image.operation_stack.tool_options.corner_radius = radius;
+        });
+    }
+
+    /// Shows `stroke` in the toolbar, which makes it the style of new operations too
+    fn show_stroke_style(&self, stroke: StrokeStyle) {
+        if let Some(toolbar) = self.imp().toolbar.get() {
+            toolbar.show_stroke_style(stroke);
+        }
+    }
+
+    /// Applies `edit` to the stroke style of new operations, as well as to that of the selected one,
+    /// `control` names what was used to make the edit, see [`OperationStack::edit_selection`]
+    fn edit_stroke_style(&self, control: &'static str, edit: impl Fn(&mut StrokeStyle)) {
+        self.imp().with_image_mut("edit_stroke_style", |image| {
+            edit(&mut image.operation_stack.tool_options.stroke);
+            image
+                .operation_stack
+                .edit_selection(control, |operation| operation.edit_stroke_style(&edit));
+        });
+    }
+
+    /// Picks what new stamps show, which also swaps the image of the selected stamp
+    fn set_stamp(&self, source: StampSource) {
+        self.imp().with_image_mut("set_stamp", |image| {
+            image
+                .operation_stack
+                .edit_selection("stamp", |operation| operation.set_stamp_source(&source));
+            image.operation_stack.tool_options.stamp = source;
+        });
+    }
+
+    fn set_filter_kind(&self, kind: FilterKind) {
+        self.imp().with_image_mut("set_filter_kind", |image| {
+            image.operation_stack.tool_options.filter.kind = kind;
+        });
+    }
+
+    fn set_filter_brightness(&self, brightness: f64) {
+        self.imp().with_image_mut("set_filter_brightness", |image| {
+            image.operation_stack.tool_options.filter.brightness = brightness;
+        });
+    }
+
+    fn set_filter_contrast(&self, contrast: f64) {
+        self.imp().with_image_mut("set_filter_contrast", |image| {
+            image.operation_stack.tool_options.filter.contrast = contrast;
+        });
+    }
+
+    fn set_sharpen_amount(&self, amount: f64) {
+        self.imp().with_image_mut("set_sharpen_amount", |image| {
+            image.operation_stack.tool_options.filter.sharpen_amount = amount;
+        });
+    }
+
+    fn set_magnify_zoom(&self, zoom: f64) {
+        self.imp().with_image_mut("set_magnify_zoom", |image| {
+            image.operation_stack.tool_options.magnify.zoom = zoom;
+        });
+    }
+
+    fn set_inset_shape(&self, shape: InsetShape) {
+        self.imp().with_image_mut("set_inset_shape", |image| {
+            image.operation_stack.tool_options.magnify.shape = shape;
+        });
+    }
+
+    fn set_keep_measurements(&self, keep_measurements: bool) {
+        self.imp().with_image_mut("set_keep_measurements", |image| {
+            image.operation_stack.keep_measurements = keep_measurements;
+        });
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_keep_measurements(keep_measurements) {
+            tracing::warn!("Failed to update `keep-measurements` setting value: {why}");
+        }
+    }
+
+    fn set_blur_radius(&self, radius: f32) {
+        self.imp().with_image_mut("set_blur_radius", |image| {
+            image
+                .operation_stack
+                .tool_options
+                .effect_strength
+                .blur_radius = radius;
+            image
+                .operation_stack
+                .edit_selection("blur-radius", |operation| operation.set_blur_radius(radius));
+        });
+        self.queue_draw();
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_blur_radius(radius as f64) {
+            tracing::warn!("Failed to update `blur-radius` setting value: {why}");
+        }
+    }
+
+    fn set_pixelate_size(&self, size: u32) {
+        self.imp().with_image_mut("set_pixelate_size", |image| {
+            image
+                .operation_stack
+                .tool_options
+                .effect_strength
+                .pixelate_size = size as u64;
+            image
+                .operation_stack
+                .edit_selection("pixelate-size", |operation| {
+                    operation.set_pixelate_size(size as u64)
+                });
+        });
+        self.queue_draw();
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_pixelate_size(size) {
+            tracing::warn!("Failed to update `pixelate-size` setting value: {why}");
+        }
+    }
+
+    fn set_bubble_radius(&self, radius: f64) {
+        self.imp().with_image_mut("set_bubble_radius", |image| {
+            image.operation_stack.bubbles.radius = radius;
+        });
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_bubble_radius(radius) {
+            tracing::warn!("Failed to update `bubble-radius` setting value: {why}");
+        }
+    }
+
+    fn set_bubble_number_style(&self, number_style: NumberStyle) {
+        self.imp()
+            .with_image_mut("set_bubble_number_style", |image| {
+                image.operation_stack.bubbles.number_style = number_style;
+            });
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_bubble_number_style(number_style.name()) {
+            tracing::warn!("Failed to update `bubble-number-style` setting value: {why}");
+        }
+    }
+
+    fn set_bubble_auto_size(&self, auto_size: bool) {
+        self.imp().with_image_mut("set_bubble_auto_size", |image| {
+            image.operation_stack.bubbles.auto_size = auto_size;
+        });
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_bubble_auto_size(auto_size) {
+            tracing::warn!("Failed to update `bubble-auto-size` setting value: {why}");
+        }
+    }
+
+    /// Returns the number the next bubble will get, if there's an image to place bubbles on yet
+    fn next_bubble_number(&self) -> Option<i32> {
+        self.imp().with_image("get next_bubble_number", |image| {
+            image.operation_stack.bubbles.next_number
+        })
+    }
+
+    fn set_next_bubble_number(&self, number: i32) {
+        self.imp()
+            .with_image_mut("set_next_bubble_number", |image| {
+                image.operation_stack.bubbles.next_number = number;
+            });
+    }
+
+    /// Changes the number bubbles start from, which the next bubble gets if none were placed yet
+    fn set_bubble_start_number(&self, start_number: i32) {
+        self.imp()
+            .with_image_mut("set_bubble_start_number", |image| {
+                image.operation_stack.set_bubble_start_number(start_number);
+            });
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_bubble_start_number(start_number) {
+            tracing::warn!("Failed to update `bubble-start-number` setting value: {why}");
+        }
+    }
+
+    /// Makes the next bubble get the start number again, and returns it
+    fn reset_bubble_numbering(&self) -> Option<i32> {
+        self.imp()
+            .with_image_mut("reset_bubble_numbering", |image| {
+                image.operation_stack.bubbles.reset_numbering();
+                image.operation_stack.bubbles.next_number
+            })
+    }
+
     fn set_line_width(&self, line_width: f64) {
-        self.imp().with_image_mut("set_line_width", |image| {
+        let tool = self.imp().with_image_mut("set_line_width", |image| {
             image.operation_stack.line_width = line_width;
+            image
+                .operation_stack
+                .edit_selection("line-width", |operation| {
+                    operation.set_line_width(line_width)
+                });
+            image.operation_stack.current_tool()
+        });
+
+        if let Some(tool) = tool.filter(|tool| tool.has_line_width()) {
+            save_line_width(tool, line_width);
+        }
+    }
+
+    fn set_text_size(&self, text_size: f64) {
+        self.imp().with_image_mut("set_text_size", |image| {
+            image.operation_stack.tool_options.text_size = text_size;
         });
+
+        let settings = Settings::open();
+        if let Err(why) = settings.try_set_text_size(text_size) {
+            tracing::warn!("Failed to update `text-size` setting value: {why}");
+        }
+    }
+}
+
+/// Returns the line width `tool` was last used with
+fn saved_line_width(tool: Tool) -> f64 {
+    Settings::open()
+        .tool_line_widths()
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .find(|(name, _)| *name == tool.name())
+        .and_then(|(_, line_width)| line_width.parse().ok())
+        .unwrap_or(DEFAULT_LINE_WIDTH)
+}
+
+/// Remembers `line_width` as the one `tool` was last used with, see [`saved_line_width`]
+fn save_line_width(tool: Tool, line_width: f64) {
+    let settings = Settings::open();
+    let mut entries = settings
+        .tool_line_widths()
+        .into_iter()
+        .filter(|entry| entry.split_once('=').map(|(name, _)| name) != Some(tool.name()))
+        .collect::<Vec<_>>();
+    entries.push(format!("{}={line_width}", tool.name()));
+
+    let entries = entries.iter().map(String::as_str).collect::<Vec<_>>();
+    if let Err(why) = settings.try_set_tool_line_widths(&entries) {
+        tracing::warn!("Failed to update `tool-line-widths` setting value: {why}");
     }
 }
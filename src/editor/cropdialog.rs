@@ -0,0 +1,164 @@
+use gtk4::{
+    glib::clone, prelude::*, subclass::prelude::ObjectSubclassIsExt, DialogFlags, ResponseType,
+};
+use kcshot_data::geometry::Rectangle;
+
+/// Shows a dialog in which the position and size of the crop region can be typed in
+pub fn pop_crop_dialog(editor: &super::EditorWindow) {
+    let crop_and_screen_dimensions = editor
+        .imp()
+        .with_image("crop dialog", |image| {
+            image
+                .operation_stack
+                .crop_region_to_adjust()
+                .map(|crop| (crop, image.operation_stack.screen_dimensions))
+        })
+        .flatten();
+    let Some((crop, screen_dimensions)) = crop_and_screen_dimensions else {
+        return;
+    };
+
+    let dialog = gtk4::Dialog::with_buttons(
+        Some("Adjust crop region"),
+        Some(editor),
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        &[],
+    );
+
+    let cancel_button = dialog.add_button("Cancel", ResponseType::Cancel);
+    cancel_button.add_css_class("destructive-action");
+    cancel_button.set_margin_bottom(10);
+
+    let ok_button = dialog.add_button("OK", ResponseType::Ok);
+    ok_button.add_css_class("suggested-action");
+    ok_button.set_margin_bottom(10);
+    ok_button.set_margin_start(5);
+    ok_button.set_margin_end(10);
+    dialog.set_default_response(ResponseType::Ok);
+
+    let grid = gtk4::Grid::builder()
+        .row_spacing(4)
+        .column_spacing(6)
+        .margin_top(10)
+        .margin_bottom(10)
+        .margin_start(10)
+        .margin_end(10)
+        .build();
+
+    let add_row = |row: i32, label: &str, min: f64, max: f64| {
+        let label = gtk4::Label::builder()
+            .label(label)
+            .halign(gtk4::Align::Start)
+            .build();
+        let spinner = gtk4::SpinButton::with_range(min, max, 1.0);
+        spinner.set_activates_default(true);
+
+        grid.attach(&label, 0, row, 1, 1);
+        grid.attach(&spinner, 1, row, 1, 1);
+
+        spinner
+    };
+    let Rectangle {
+        x: left,
+        y: top,
+        w: screen_width,
+        h: screen_height,
+    } = screen_dimensions;
+    let (right, bottom) = (left + screen_width, top + screen_height);
+    // The region has to be at least a pixel big and has to stay on the screen, so the size can only
+    // go up to where the screen ends
+    let x = add_row(0, "X", left, right - 1.0);
+    let y = add_row(1, "Y", top, bottom - 1.0);
+    let width = add_row(2, "Width", 1.0, screen_width);
+    let height = add_row(3, "Height", 1.0, screen_height);
+    x.connect_value_changed(clone!(@weak width => move |this| {
+        width.set_range(1.0, right - this.value());
+    }));
+    y.connect_value_changed(clone!(@weak height => move |this| {
+        height.set_range(1.0, bottom - this.value());
+    }));
+    x.set_value(crop.x.round());
+    y.set_value(crop.y.round());
+    width.set_range(1.0, right - x.value());
+    height.set_range(1.0, bottom - y.value());
+    width.set_value(crop.w.round());
+    height.set_value(crop.h.round());
+    dialog.content_area().append(&grid);
+
+    dialog.connect_response(clone!(@weak editor => move |this, response| {
+        this.close();
+
+        if response != ResponseType::Ok {
+            return;
+        }
+
+        let crop = Rectangle {
+            x: x.value(),
+            y: y.value(),
+            w: width.value(),
+            h: height.value(),
+        };
+        editor.imp().with_image_mut("crop dialog response", |image| {
+            image.operation_stack.adjust_crop_region(crop);
+        });
+        editor.queue_draw();
+    }));
+
+    dialog.show();
+}
+
+/// Asks the user for the width and height of a new crop size, `callback` receives them if the user
+/// didn't cancel
+pub fn pop_crop_size_dialog(
+    editor: &super::EditorWindow,
+    callback: impl Fn(Option<(f64, f64)>) + 'static,
+) {
+    let dialog = gtk4::Dialog::with_buttons(
+        Some("Add crop size"),
+        Some(editor),
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        &[],
+    );
+
+    let cancel_button = dialog.add_button("Cancel", ResponseType::Cancel);
+    cancel_button.add_css_class("destructive-action");
+    cancel_button.set_margin_bottom(10);
+
+    let ok_button = dialog.add_button("OK", ResponseType::Ok);
+    ok_button.add_css_class("suggested-action");
+    ok_button.set_margin_bottom(10);
+    ok_button.set_margin_start(5);
+    ok_button.set_margin_end(10);
+    dialog.set_default_response(ResponseType::Ok);
+
+    let hbox = gtk4::Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .spacing(6)
+        .margin_top(10)
+        .margin_bottom(10)
+        .margin_start(10)
+        .margin_end(10)
+        .build();
+    let width = gtk4::SpinButton::with_range(1.0, 16384.0, 1.0);
+    width.set_value(1920.0);
+    width.set_activates_default(true);
+    let height = gtk4::SpinButton::with_range(1.0, 16384.0, 1.0);
+    height.set_value(1080.0);
+    height.set_activates_default(true);
+    hbox.append(&width);
+    hbox.append(&gtk4::Label::new(Some("×")));
+    hbox.append(&height);
+    dialog.content_area().append(&hbox);
+
+    dialog.connect_response(move |this, response| {
+        this.close();
+
+        if response == ResponseType::Ok {
+            callback(Some((width.value(), height.value())));
+        } else {
+            callback(None);
+        }
+    });
+
+    dialog.show();
+}
@@ -0,0 +1,215 @@
+use std::{f64::consts::PI, fmt::Write as _};
+
+use cairo::{Context, ImageSurface};
+use gtk4::pango::FontDescription;
+use kcshot_data::{
+    colour::Colour,
+    geometry::{Point, Rectangle},
+};
+
+use super::utils::CairoExt;
+
+/// How many pixels of the screenshot are shown on each side of the pixel under the cursor
+const PIXELS_AROUND_CURSOR: f64 = 7.0;
+/// How big every pixel of the screenshot is inside the loupe
+const LOUPE_ZOOM: f64 = 9.0;
+const LOUPE_SIZE: f64 = (2.0 * PIXELS_AROUND_CURSOR + 1.0) * LOUPE_ZOOM;
+/// The distance between the cursor and the loupe
+const LOUPE_DISTANCE: f64 = 24.0;
+const LABEL_PADDING: f64 = 4.0;
+const BORDER_COLOUR: Colour = Colour {
+    red: 255,
+    green: 255,
+    blue: 255,
+    alpha: 255,
+};
+const CROSSHAIR_COLOUR: Colour = Colour {
+    red: 0,
+    green: 127,
+    blue: 190,
+    alpha: 255,
+};
+const LABEL_BACKGROUND_COLOUR: Colour = Colour {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 191,
+};
+
+/// What the loupe shows information about
+pub struct Target {
+    /// The pixel under the cursor, in screenshot coordinates
+    pub pixel: Point,
+    pub colour: Colour,
+    /// The region being selected right now, if any
+    pub selection: Option<Rectangle>,
+}
+
+/// Draws a magnified view of the pixels of `surface` around the `target` pixel, along with its
+/// coordinates and colour
+///
+/// `cursor` is the position of the cursor in window coordinates, while `window_size` is the size
+/// of the window, which is used to keep the loupe on-screen.
+pub fn draw(
+    cairo: &Context,
+    surface: &ImageSurface,
+    cursor: Point,
+    window_size: (f64, f64),
+    target: &Target,
+) -> Result<(), cairo::Error> {
+    let label = label_for(target);
+    let layout = pangocairo::create_layout(cairo);
+    layout.set_font_description(Some(&FontDescription::from_string("Monospace 9")));
+    layout.set_text(&label);
+    let (label_width, label_height) = layout.pixel_size();
+    let (label_width, label_height) = (label_width as f64, label_height as f64);
+
+    let total_height = LOUPE_SIZE + 2.0 * LABEL_PADDING + label_height;
+    let Point { x, y } = placement(cursor, window_size, (LOUPE_SIZE, total_height));
+
+    cairo.save()?;
+
+    // The magnified pixels
+    cairo.save()?;
+    cairo.arc(
+        x + LOUPE_SIZE / 2.0,
+        y + LOUPE_SIZE / 2.0,
+        LOUPE_SIZE / 2.0,
+        0.0,
+        2.0 * PI,
+    );
+    cairo.clip();
+    cairo.set_source_rgb(0.0, 0.0, 0.0);
+    cairo.paint()?;
+    cairo.translate(x, y);
+    cairo.scale(LOUPE_ZOOM, LOUPE_ZOOM);
+    cairo.translate(
+        PIXELS_AROUND_CURSOR - target.pixel.x.floor(),
+        PIXELS_AROUND_CURSOR - target.pixel.y.floor(),
+    );
+    cairo.set_source_surface(surface, 0.0, 0.0)?;
+    cairo.source().set_filter(cairo::Filter::Nearest);
+    cairo.paint()?;
+    cairo.restore()?;
+
+    // The crosshair, which outlines the pixel under the cursor
+    let centre = PIXELS_AROUND_CURSOR * LOUPE_ZOOM;
+    cairo.set_source_colour(CROSSHAIR_COLOUR);
+    cairo.set_line_width(1.0);
+    cairo.rectangle(
+        x + centre + 0.5,
+        y + centre + 0.5,
+        LOUPE_ZOOM - 1.0,
+        LOUPE_ZOOM - 1.0,
+    );
+    cairo.move_to(x + centre + LOUPE_ZOOM / 2.0, y);
+    cairo.line_to(x + centre + LOUPE_ZOOM / 2.0, y + centre);
+    cairo.move_to(x + centre + LOUPE_ZOOM / 2.0, y + centre + LOUPE_ZOOM);
+    cairo.line_to(x + centre + LOUPE_ZOOM / 2.0, y + LOUPE_SIZE);
+    cairo.move_to(x, y + centre + LOUPE_ZOOM / 2.0);
+    cairo.line_to(x + centre, y + centre + LOUPE_ZOOM / 2.0);
+    cairo.move_to(x + centre + LOUPE_ZOOM, y + centre + LOUPE_ZOOM / 2.0);
+    cairo.line_to(x + LOUPE_SIZE, y + centre + LOUPE_ZOOM / 2.0);
+    cairo.stroke()?;
+
+    cairo.set_source_colour(BORDER_COLOUR);
+    cairo.set_line_width(2.0);
+    cairo.arc(
+        x + LOUPE_SIZE / 2.0,
+        y + LOUPE_SIZE / 2.0,
+        LOUPE_SIZE / 2.0,
+        0.0,
+        2.0 * PI,
+    );
+    cairo.stroke()?;
+
+    // The label, with a swatch of the colour under the cursor next to it
+    let label_top = y + LOUPE_SIZE + LABEL_PADDING;
+    let swatch_size = label_height.min(LOUPE_SIZE / 4.0);
+    let label_left = x + (LOUPE_SIZE - label_width - swatch_size - LABEL_PADDING) / 2.0;
+    cairo.set_source_colour(LABEL_BACKGROUND_COLOUR);
+    cairo.rectangle(
+        label_left - LABEL_PADDING,
+        label_top - LABEL_PADDING,
+        label_width + swatch_size + 3.0 * LABEL_PADDING,
+        label_height + 2.0 * LABEL_PADDING,
+    );
+    cairo.fill()?;
+
+    cairo.set_source_colour(Colour {
+        alpha: 255,
+        ..target.colour
+    });
+    cairo.rectangle(label_left, label_top, swatch_size, swatch_size);
+    cairo.fill_preserve()?;
+    cairo.set_source_colour(BORDER_COLOUR);
+    cairo.set_line_width(1.0);
+    cairo.stroke()?;
+
+    cairo.move_to(label_left + swatch_size + LABEL_PADDING, label_top);
+    pangocairo::update_layout(cairo, &layout);
+    pangocairo::show_layout(cairo, &layout);
+
+    cairo.restore()?;
+
+    Ok(())
+}
+
+fn label_for(target: &Target) -> String {
+    let Point { x, y } = target.pixel;
+    let Colour {
+        red, green, blue, ..
+    } = target.colour;
+
+    let mut label = format!(
+        "{}, {}\n#{red:0>2x}{green:0>2x}{blue:0>2x}",
+        x.floor(),
+        y.floor()
+    );
+    if let Some(selection) = target.selection {
+        let Rectangle { w, h, .. } = selection.normalised();
+        write!(label, "\n{} × {}", w.round(), h.round())
+            .expect("Writing to a string shouldn't fail");
+    }
+
+    label
+}
+
+/// Puts the loupe below and to the right of the cursor, unless that would make it go off-screen
+fn placement(cursor: Point, (width, height): (f64, f64), (w, h): (f64, f64)) -> Point {
+    let x = if cursor.x + LOUPE_DISTANCE + w > width {
+        cursor.x - LOUPE_DISTANCE - w
+    } else {
+        cursor.x + LOUPE_DISTANCE
+    };
+    let y = if cursor.y + LOUPE_DISTANCE + h > height {
+        cursor.y - LOUPE_DISTANCE - h
+    } else {
+        cursor.y + LOUPE_DISTANCE
+    };
+
+    Point { x, y }
+}
+
+/// Draws a circle `diameter` wide (in window coordinates) around the cursor, which shows how big
+/// the marks made by the current tool will be
+pub fn draw_size_preview(
+    cairo: &Context,
+    cursor: Point,
+    diameter: f64,
+) -> Result<(), cairo::Error> {
+    // The circle is outlined twice so that it stands out on both light and dark screenshots
+    let radius = (diameter / 2.0).max(1.0);
+    cairo.save()?;
+    cairo.new_sub_path();
+    cairo.arc(cursor.x, cursor.y, radius, 0.0, 2.0 * PI);
+    cairo.set_source_rgb(0.0, 0.0, 0.0);
+    cairo.set_line_width(3.0);
+    cairo.stroke_preserve()?;
+    cairo.set_source_colour(BORDER_COLOUR);
+    cairo.set_line_width(1.0);
+    cairo.stroke()?;
+    cairo.restore()?;
+
+    Ok(())
+}
@@ -1,14 +1,39 @@
 use cairo::{Context, Error as CairoError, ImageSurface};
 use gtk4::pango::FontDescription;
-use kcshot_data::{colour::Colour, geometry::*, Text};
+use kcshot_data::{colour::Colour, geometry::*, Text, TextStyle};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-pub use self::stack::*;
+pub use self::{
+    bubble::{BubbleSettings, NumberStyle},
+    constraints::*,
+    magnify::{InsetShape, MagnifySettings},
+    measure::MeasureKind,
+    pixelops::{EffectStrength, FilterKind, FilterSettings},
+    shapes::ArrowheadStyle,
+    snapping::EdgeMap,
+    spotlight::{SpotlightEffect, SpotlightShape, SpotlightStyle},
+    stack::*,
+    stamp::{user_stamps, BundledStamp, StampSource},
+    stroke::{CapStyle, DashStyle, JoinStyle, StrokeStyle},
+};
 use super::utils::CairoExt;
 
+mod bubble;
+mod callout;
+mod constraints;
+mod highlighter;
+mod magnify;
+mod measure;
+mod pencil;
 mod pixelops;
+mod selection;
 mod shapes;
+mod snapping;
+mod spotlight;
 mod stack;
+mod stamp;
+mod stroke;
 
 const HIGHLIGHT_COLOUR: Colour = Colour {
     red: 255,
@@ -17,6 +42,13 @@ const HIGHLIGHT_COLOUR: Colour = Colour {
     alpha: 63,
 };
 
+/// How rounded the corners made by [`Tool::RoundedRectangle`] are by default
+const CORNER_RADIUS: f64 = 12.0;
+/// The line width of tools which weren't used yet
+pub const DEFAULT_LINE_WIDTH: f64 = 4.0;
+/// The size of new text, in points, until another one is picked
+const TEXT_SIZE: f64 = 40.0;
+
 const INVISIBLE: Colour = Colour {
     red: 0,
     green: 0,
@@ -24,10 +56,16 @@ const INVISIBLE: Colour = Colour {
     alpha: 0,
 };
 
-/// How big the bubbles will be
-const BUBBLE_RADIUS: f64 = 10.0;
+/// How far shadows are cast, down and to the right
+const SHADOW_OFFSET: f64 = 3.0;
+const SHADOW_COLOUR: Colour = Colour {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 96,
+};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Operation {
     Crop(Rectangle),
     Blur {
@@ -37,55 +75,230 @@ pub enum Operation {
     Pixelate {
         rect: Rectangle,
         seed: u64,
+        #[serde(default = "pixelops::default_pixelate_size")]
+        size: u64,
     },
     DrawLine {
         start: Point,
         end: Point,
         colour: Colour,
         line_width: f64,
+        #[serde(default)]
+        stroke: StrokeStyle,
     },
     DrawRectangle {
         rect: Rectangle,
         border: Colour,
         fill: Colour,
         line_width: f64,
+        /// The corners are sharp when this is 0
+        #[serde(default)]
+        corner_radius: f64,
+        #[serde(default)]
+        stroke: StrokeStyle,
     },
     Text {
         top_left: Point,
         text: String,
+        /// The source `text` was generated from, which is shown when the text is edited
+        #[serde(default)]
+        markdown: String,
         colour: Colour,
+        #[serde(with = "kcshot_data::serialisation::font_description")]
         font_description: FontDescription,
+        #[serde(default)]
+        style: TextStyle,
     },
     DrawArrow {
         start: Point,
         end: Point,
         colour: Colour,
         line_width: f64,
+        #[serde(default)]
+        head: ArrowheadStyle,
+        /// Whether there's also a head at `start`
+        #[serde(default)]
+        double_headed: bool,
+        #[serde(default)]
+        stroke: StrokeStyle,
     },
     Highlight {
         rect: Rectangle,
+        #[serde(default = "default_highlight_colour")]
+        colour: Colour,
     },
     DrawEllipse {
         ellipse: Ellipse,
         border: Colour,
         fill: Colour,
         line_width: f64,
+        #[serde(default)]
+        stroke: StrokeStyle,
     },
     Bubble {
         centre: Point,
         bubble_colour: Colour,
         text_colour: Colour,
         number: i32,
+        #[serde(with = "kcshot_data::serialisation::font_description")]
         font_description: FontDescription,
+        /// Only the outline and the shadow are used, as the bubble itself is the box of its text
+        #[serde(default)]
+        style: TextStyle,
+        #[serde(default = "bubble::default_radius")]
+        radius: f64,
+        #[serde(default)]
+        number_style: NumberStyle,
+        /// Whether the bubble grows so that its number fits inside it
+        #[serde(default)]
+        auto_size: bool,
     },
     Pencil {
         start: Point,
         points: Vec<Point>,
         colour: Colour,
         line_width: f64,
+        /// How hard the stylus was pressed at `start` and at each of the `points`, from 0 to 1,
+        /// this is empty for strokes which weren't made with a stylus
+        #[serde(default)]
+        pressures: Vec<f64>,
+        #[serde(default)]
+        stroke: StrokeStyle,
+    },
+    Measure {
+        start: Point,
+        end: Point,
+        kind: MeasureKind,
+        colour: Colour,
+    },
+    /// A freehand stroke like [`Operation::Pencil`], but blended in like a highlighter marker, see
+    /// [`execute_all`] for how multiple strokes are drawn
+    Highlighter {
+        start: Point,
+        points: Vec<Point>,
+        colour: Colour,
+        line_width: f64,
+    },
+    Callout {
+        /// The box holding the text, it grows downwards when the text doesn't fit
+        rect: Rectangle,
+        /// Where the tail points
+        target: Point,
+        text: String,
+        /// The source `text` was generated from, which is shown when the text is edited
+        markdown: String,
+        text_colour: Colour,
+        fill: Colour,
+        border: Colour,
+        line_width: f64,
+        #[serde(with = "kcshot_data::serialisation::font_description")]
+        font_description: FontDescription,
+    },
+    /// Darkens or blurs everything outside of `rect`, see [`execute_all`] for how multiple
+    /// spotlights are drawn
+    Spotlight {
+        rect: Rectangle,
+        style: SpotlightStyle,
+    },
+    /// Covers `rect` with an opaque fill, unlike blurring or pixelating this can't be undone by
+    /// looking at the result, see [`burn_in_redactions`] for how the pixels below are destroyed
+    Redact {
+        rect: Rectangle,
+        colour: Colour,
+    },
+    Grayscale {
+        rect: Rectangle,
+    },
+    Invert {
+        rect: Rectangle,
+    },
+    BrightnessContrast {
+        rect: Rectangle,
+        /// How much lighter the region is made, from -1 to 1
+        brightness: f64,
+        /// How much the channels are stretched away from the middle grey, 1 leaves them alone
+        contrast: f64,
+    },
+    Sharpen {
+        rect: Rectangle,
+        amount: f64,
+    },
+    /// An arrow which bends towards `control`, as a quadratic Bézier curve
+    CurvedArrow {
+        start: Point,
+        control: Point,
+        end: Point,
+        colour: Colour,
+        line_width: f64,
+        head: ArrowheadStyle,
+        double_headed: bool,
+        stroke: StrokeStyle,
+    },
+    /// Lines going through `points` in order, which make up a filled polygon when `closed` is set
+    Polyline {
+        points: Vec<Point>,
+        closed: bool,
+        border: Colour,
+        fill: Colour,
+        line_width: f64,
+        stroke: StrokeStyle,
+    },
+    /// Shows `source` magnified `zoom` times in an inset centred on `centre`
+    Magnify {
+        source: Rectangle,
+        centre: Point,
+        zoom: f64,
+        shape: InsetShape,
+        border: Colour,
+        line_width: f64,
+    },
+    /// An image or emoji drawn as big as it fits inside `rect`
+    Stamp {
+        rect: Rectangle,
+        source: StampSource,
     },
 }
 
+/// The options of the tools which have some of their own, which are picked in the toolbar
+#[derive(Clone, Debug)]
+pub struct ToolOptions {
+    pub measure_kind: MeasureKind,
+    pub spotlight_style: SpotlightStyle,
+    pub effect_strength: EffectStrength,
+    pub filter: FilterSettings,
+    pub magnify: MagnifySettings,
+    /// The colour used by both [`Tool::Highlight`] and [`Tool::Highlighter`]
+    pub highlight_colour: Colour,
+    /// The style of the heads of new arrows, be they straight, double-headed or curved
+    pub arrowhead: ArrowheadStyle,
+    /// How rounded the corners made by [`Tool::RoundedRectangle`] are
+    pub corner_radius: f64,
+    /// How the lines of new shapes, arrows and pencil strokes are drawn
+    pub stroke: StrokeStyle,
+    /// The size of new text and callouts, in points
+    pub text_size: f64,
+    /// What [`Tool::Stamp`] places
+    pub stamp: StampSource,
+}
+
+impl Default for ToolOptions {
+    fn default() -> Self {
+        Self {
+            measure_kind: MeasureKind::default(),
+            spotlight_style: SpotlightStyle::default(),
+            effect_strength: EffectStrength::default(),
+            filter: FilterSettings::default(),
+            magnify: MagnifySettings::default(),
+            highlight_colour: HIGHLIGHT_COLOUR,
+            arrowhead: ArrowheadStyle::default(),
+            corner_radius: CORNER_RADIUS,
+            stroke: StrokeStyle::default(),
+            text_size: TEXT_SIZE,
+            stamp: StampSource::default(),
+        }
+    }
+}
+
 /// This enum is like [Operations] but without any associated data
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Tool {
@@ -106,6 +319,20 @@ pub enum Tool {
     // Unlike CropAndSave, this one is not visible
     Crop = 11,
     Save = 12,
+
+    Select = 13,
+    Measure = 14,
+    Callout = 15,
+    Spotlight = 16,
+    Redact = 17,
+    Filter = 18,
+    Magnify = 19,
+    Highlighter = 20,
+    RoundedRectangle = 21,
+    Polyline = 22,
+    DoubleArrow = 23,
+    CurvedArrow = 24,
+    Stamp = 25,
 }
 
 impl Tool {
@@ -124,6 +351,51 @@ impl Tool {
             Tool::Pencil => "/kc/kcshot/editor/tool-pencil.png",
             Tool::Crop => panic!("Nothing should try to get the associated path of the simple Crop tool, as it intentionally does not have a button"),
             Tool::Save => "/kc/kcshot/editor/tool-checkmark.png",
+            Tool::Select => "/kc/kcshot/editor/tool-select.png",
+            Tool::Measure => "/kc/kcshot/editor/tool-measure.png",
+            Tool::Callout => "/kc/kcshot/editor/tool-callout.png",
+            Tool::Spotlight => "/kc/kcshot/editor/tool-spotlight.png",
+            Tool::Redact => "/kc/kcshot/editor/tool-redact.png",
+            Tool::Filter => "/kc/kcshot/editor/tool-filter.png",
+            Tool::Magnify => "/kc/kcshot/editor/tool-magnify.png",
+            Tool::Highlighter => "/kc/kcshot/editor/tool-highlighter.png",
+            Tool::RoundedRectangle => "/kc/kcshot/editor/tool-roundedrectangle.png",
+            Tool::Polyline => "/kc/kcshot/editor/tool-polyline.png",
+            Tool::DoubleArrow => "/kc/kcshot/editor/tool-doublearrow.png",
+            Tool::CurvedArrow => "/kc/kcshot/editor/tool-curvedarrow.png",
+            Tool::Stamp => "/kc/kcshot/editor/tool-stamp.png",
+        }
+    }
+
+    /// The name of the tool as stored in the settings
+    pub const fn name(self) -> &'static str {
+        match self {
+            Tool::CropAndSave => "crop-and-save",
+            Tool::Line => "line",
+            Tool::Arrow => "arrow",
+            Tool::Rectangle => "rectangle",
+            Tool::Ellipse => "ellipse",
+            Tool::Highlight => "highlight",
+            Tool::Pixelate => "pixelate",
+            Tool::Blur => "blur",
+            Tool::AutoincrementBubble => "autoincrement-bubble",
+            Tool::Text => "text",
+            Tool::Pencil => "pencil",
+            Tool::Crop => "crop",
+            Tool::Save => "save",
+            Tool::Select => "select",
+            Tool::Measure => "measure",
+            Tool::Callout => "callout",
+            Tool::Spotlight => "spotlight",
+            Tool::Redact => "redact",
+            Tool::Filter => "filter",
+            Tool::Magnify => "magnify",
+            Tool::Highlighter => "highlighter",
+            Tool::RoundedRectangle => "rounded-rectangle",
+            Tool::Polyline => "polyline",
+            Tool::DoubleArrow => "double-arrow",
+            Tool::CurvedArrow => "curved-arrow",
+            Tool::Stamp => "stamp",
         }
     }
 
@@ -141,6 +413,19 @@ impl Tool {
             'i' | 'I' => AutoincrementBubble,
             't' | 'T' => Text,
             'p' | 'P' => Pencil,
+            's' | 'S' => Select,
+            'm' | 'M' => Measure,
+            'o' | 'O' => Callout,
+            'f' | 'F' => Spotlight,
+            'd' | 'D' => Redact,
+            'g' | 'G' => Filter,
+            'z' | 'Z' => Magnify,
+            'k' | 'K' => Highlighter,
+            'u' | 'U' => RoundedRectangle,
+            'y' | 'Y' => Polyline,
+            'w' | 'W' => DoubleArrow,
+            'v' | 'V' => CurvedArrow,
+            'j' | 'J' => Stamp,
             _ => None?,
         })
     }
@@ -160,6 +445,19 @@ impl Tool {
             Tool::Pencil => "Pe<u>n</u>cil tool",
             Tool::Crop => panic!("Nothing should try to get the tooltip of the simple Crop tool, as it does not have a button"),
             Tool::Save => "Save current screenshot",
+            Tool::Select => "<u>S</u>election tool",
+            Tool::Measure => "<u>M</u>easure tool",
+            Tool::Callout => "Call<u>o</u>ut tool",
+            Tool::Spotlight => "Spotlight tool, to <u>f</u>ocus on a region",
+            Tool::Redact => "Re<u>d</u>act tool",
+            Tool::Filter => "Re<u>g</u>ion filter tool",
+            Tool::Magnify => "Magnify tool, to <u>z</u>oom into a region",
+            Tool::Highlighter => "Highlighter mar<u>k</u>er tool",
+            Tool::RoundedRectangle => "Ro<u>u</u>nded rectangle tool",
+            Tool::Polyline => "Pol<u>y</u>line tool, double-click to finish or click the first point for a polygon",
+            Tool::DoubleArrow => "Double-headed arro<u>w</u> tool",
+            Tool::CurvedArrow => "Cur<u>v</u>ed arrow tool",
+            Tool::Stamp => "Stamp tool, for stickers, emo<u>j</u>i and images",
         }
     }
 
@@ -170,16 +468,56 @@ impl Tool {
     pub const fn is_cropping_tool(self) -> bool {
         matches!(self, Self::CropAndSave | Self::Crop)
     }
+
+    /// Returns whether this tool draws lines whose width is remembered separately from the other
+    /// tools, the selection tool can change the width of lines too, but doesn't have its own
+    pub const fn has_line_width(self) -> bool {
+        matches!(
+            self,
+            Self::Line
+                | Self::Arrow
+                | Self::DoubleArrow
+                | Self::CurvedArrow
+                | Self::Rectangle
+                | Self::RoundedRectangle
+                | Self::Ellipse
+                | Self::Polyline
+                | Self::Pencil
+                | Self::Highlighter
+                | Self::Callout
+                | Self::Magnify
+        )
+    }
+
+    /// Returns whether the corners of the regions drawn by this tool snap to nearby edges
+    pub const fn snaps_to_edges(self) -> bool {
+        matches!(
+            self,
+            Self::CropAndSave
+                | Self::Crop
+                | Self::Rectangle
+                | Self::RoundedRectangle
+                | Self::Highlight
+                | Self::Blur
+                | Self::Pixelate
+                | Self::Measure
+                | Self::Spotlight
+                | Self::Redact
+                | Self::Filter
+                | Self::Magnify
+        )
+    }
 }
 
 impl Operation {
     fn create_default_for_tool(
         tool: Tool,
         start: Point,
-        bubble_index: &mut i32,
+        bubbles: &mut BubbleSettings,
         primary_colour: Colour,
         secondary_colour: Colour,
         line_width: f64,
+        options: &ToolOptions,
     ) -> Self {
         let rect = Rectangle {
             x: start.x,
@@ -188,10 +526,12 @@ impl Operation {
             h: 1.0,
         };
 
-        let font_description = FontDescription::from_string("Fira Code, 40pt");
+        let mut font_description = FontDescription::from_string("Fira Code");
+        font_description.set_size((options.text_size * gtk4::pango::SCALE as f64) as i32);
 
         match tool {
             Tool::Save => panic!("`Tool::Save` should never be converted to an `Operation`"),
+            Tool::Select => panic!("`Tool::Select` only edits existing operations, it should never be converted to an `Operation`"),
             Tool::CropAndSave | Tool::Crop => Self::Crop(Rectangle {
                 x: start.x,
                 y: start.y,
@@ -204,18 +544,46 @@ impl Operation {
                 end: start,
                 colour: secondary_colour,
                 line_width,
+                stroke: options.stroke,
+            },
+            Tool::Arrow | Tool::DoubleArrow => Self::DrawArrow {
+                start,
+                end: start,
+                colour: secondary_colour,
+                line_width,
+                head: options.arrowhead,
+                double_headed: tool == Tool::DoubleArrow,
+                stroke: options.stroke,
             },
-            Tool::Arrow => Self::DrawArrow {
+            Tool::CurvedArrow => Self::CurvedArrow {
                 start,
+                control: start,
                 end: start,
                 colour: secondary_colour,
                 line_width,
+                head: options.arrowhead,
+                double_headed: false,
+                stroke: options.stroke,
             },
-            Tool::Rectangle => Self::DrawRectangle {
+            Tool::Rectangle | Tool::RoundedRectangle => Self::DrawRectangle {
                 rect,
                 border: secondary_colour,
                 fill: primary_colour,
                 line_width,
+                corner_radius: if tool == Tool::RoundedRectangle {
+                    options.corner_radius
+                } else {
+                    0.0
+                },
+                stroke: options.stroke,
+            },
+            Tool::Polyline => Self::Polyline {
+                points: vec![start],
+                closed: false,
+                border: secondary_colour,
+                fill: primary_colour,
+                line_width,
+                stroke: options.stroke,
             },
             Tool::Ellipse => Self::DrawEllipse {
                 ellipse: Ellipse {
@@ -227,39 +595,107 @@ impl Operation {
                 border: secondary_colour,
                 fill: primary_colour,
                 line_width,
+                stroke: options.stroke,
+            },
+            Tool::Highlight => Self::Highlight {
+                rect,
+                colour: options.highlight_colour,
             },
-            Tool::Highlight => Self::Highlight { rect },
             Tool::Pixelate => Self::Pixelate {
                 rect,
                 seed: rand::thread_rng().gen(),
+                size: options.effect_strength.pixelate_size,
+            },
+            Tool::Blur => Self::Blur {
+                rect,
+                radius: options.effect_strength.blur_radius,
+            },
+            Tool::AutoincrementBubble => Self::Bubble {
+                centre: start,
+                bubble_colour: primary_colour,
+                text_colour: secondary_colour,
+                number: bubbles.take_number(),
+                font_description: bubbles.font_description(),
+                style: TextStyle::default(),
+                radius: bubbles.radius,
+                number_style: bubbles.number_style,
+                auto_size: bubbles.auto_size,
             },
-            Tool::Blur => Self::Blur { rect, radius: 5.0 },
-            Tool::AutoincrementBubble => {
-                let bubble = Self::Bubble {
-                    centre: start,
-                    bubble_colour: primary_colour,
-                    text_colour: secondary_colour,
-                    number: *bubble_index,
-                    font_description,
-                };
-                *bubble_index += 1;
-                bubble
-            }
             Tool::Text => Self::Text {
                 top_left: start,
                 text: String::new(),
+                markdown: String::new(),
                 // We use secondary colour here as the primary one is more likely to be transparent,
                 // given that's the default, and people are likely to use boxes and ellipses to try
                 // and bring things into attention, and in those situations the primary colour is
                 // used to fill those shapes.
                 colour: secondary_colour,
                 font_description,
+                style: TextStyle::default(),
             },
             Tool::Pencil => Self::Pencil {
                 start,
                 points: vec![],
                 colour: secondary_colour,
                 line_width,
+                pressures: vec![],
+                stroke: options.stroke,
+            },
+            Tool::Highlighter => Self::Highlighter {
+                start,
+                points: vec![],
+                colour: options.highlight_colour,
+                line_width,
+            },
+            Tool::Measure => Self::Measure {
+                start,
+                end: start,
+                kind: options.measure_kind,
+                colour: secondary_colour,
+            },
+            Tool::Callout => Self::Callout {
+                rect: callout::place(start, start),
+                target: start,
+                text: String::new(),
+                markdown: String::new(),
+                text_colour: secondary_colour,
+                fill: primary_colour,
+                border: secondary_colour,
+                line_width,
+                font_description,
+            },
+            Tool::Spotlight => Self::Spotlight {
+                rect,
+                style: options.spotlight_style,
+            },
+            Tool::Redact => Self::Redact {
+                rect,
+                colour: opaque(primary_colour),
+            },
+            Tool::Filter => match options.filter.kind {
+                FilterKind::Grayscale => Self::Grayscale { rect },
+                FilterKind::Invert => Self::Invert { rect },
+                FilterKind::BrightnessContrast => Self::BrightnessContrast {
+                    rect,
+                    brightness: options.filter.brightness,
+                    contrast: options.filter.contrast,
+                },
+                FilterKind::Sharpen => Self::Sharpen {
+                    rect,
+                    amount: options.filter.sharpen_amount,
+                },
+            },
+            Tool::Magnify => Self::Magnify {
+                source: rect,
+                centre: magnify::inset_centre_for(rect, options.magnify.zoom),
+                zoom: options.magnify.zoom,
+                shape: options.magnify.shape,
+                border: secondary_colour,
+                line_width,
+            },
+            Tool::Stamp => Self::Stamp {
+                rect,
+                source: options.stamp.clone(),
             },
         }
     }
@@ -270,6 +706,81 @@ impl Operation {
         cairo: &Context,
         is_in_draw_event: bool,
         should_crop_indicators_be_dashed: bool,
+    ) -> Result<(), Error> {
+        let Some((stroke, line_width)) = self.stroke_style() else {
+            return self.draw(
+                surface,
+                cairo,
+                is_in_draw_event,
+                should_crop_indicators_be_dashed,
+            );
+        };
+
+        cairo.save()?;
+        stroke.apply(cairo, line_width);
+        // The whole operation is made translucent at once, so that e.g. the border of a shape
+        // doesn't show through where it overlaps its fill
+        if stroke.is_translucent() {
+            cairo.push_group();
+        }
+        self.draw(
+            surface,
+            cairo,
+            is_in_draw_event,
+            should_crop_indicators_be_dashed,
+        )?;
+        if stroke.is_translucent() {
+            cairo.pop_group_to_source()?;
+            cairo.paint_with_alpha(stroke.opacity)?;
+        }
+        cairo.restore()?;
+
+        Ok(())
+    }
+
+    /// Returns how the lines of this operation are drawn along with their width, if it has any
+    /// whose style can be picked
+    pub fn stroke_style(&self) -> Option<(StrokeStyle, f64)> {
+        match self {
+            Operation::DrawLine {
+                stroke, line_width, ..
+            }
+            | Operation::DrawRectangle {
+                stroke, line_width, ..
+            }
+            | Operation::DrawArrow {
+                stroke, line_width, ..
+            }
+            | Operation::DrawEllipse {
+                stroke, line_width, ..
+            }
+            | Operation::CurvedArrow {
+                stroke, line_width, ..
+            }
+            | Operation::Polyline {
+                stroke, line_width, ..
+            } => Some((*stroke, *line_width)),
+            // Freehand strokes always get round ends and corners, as they'd look jagged otherwise
+            Operation::Pencil {
+                stroke, line_width, ..
+            } => Some((
+                StrokeStyle {
+                    cap: CapStyle::Round,
+                    join: JoinStyle::Round,
+                    ..*stroke
+                },
+                *line_width,
+            )),
+            _ => None,
+        }
+    }
+
+    fn draw(
+        &self,
+        surface: &ImageSurface,
+        cairo: &Context,
+        is_in_draw_event: bool,
+        should_crop_indicators_be_dashed: bool,
     ) -> Result<(), Error> {
         match self {
             Operation::Crop(rect) => {
@@ -302,16 +813,17 @@ impl Operation {
                 pixelops::blur(cairo, surface, *radius as usize, rect.normalised())?;
                 cairo.restore()?;
             }
-            Operation::Pixelate { rect, seed } => {
+            Operation::Pixelate { rect, seed, size } => {
                 let rect = rect.normalised();
 
-                pixelops::pixelate(cairo, surface, &rect, *seed)?;
+                pixelops::pixelate(cairo, surface, &rect, *seed, *size)?;
             }
             Operation::DrawLine {
                 start,
                 end,
                 colour,
                 line_width,
+                ..
             } => {
                 shapes::draw_line(cairo, *start, *end, *colour, *line_width)?;
             }
@@ -320,17 +832,21 @@ impl Operation {
                 border,
                 fill,
                 line_width,
+                corner_radius,
+                ..
             } => {
-                shapes::draw_rectangle(cairo, rect, *border, *fill, *line_width)?;
+                shapes::draw_rectangle(cairo, rect, *border, *fill, *line_width, *corner_radius)?;
             }
             Operation::Text {
                 top_left,
                 text,
                 colour,
                 font_description,
+                style,
+                ..
             } => {
                 cairo.save()?;
-                draw_text_at(cairo, *top_left, text, *colour, font_description);
+                draw_text_at(cairo, *top_left, text, *colour, font_description, style)?;
                 cairo.restore()?;
             }
             Operation::DrawArrow {
@@ -338,17 +854,29 @@ impl Operation {
                 end,
                 colour,
                 line_width,
+                head,
+                double_headed,
+                ..
             } => {
-                shapes::draw_arrow(cairo, *start, *end, *colour, *line_width)?;
+                shapes::draw_arrow(
+                    cairo,
+                    *start,
+                    *end,
+                    *colour,
+                    *line_width,
+                    *head,
+                    *double_headed,
+                )?;
             }
-            Operation::Highlight { rect } => {
-                shapes::draw_rectangle(cairo, rect, INVISIBLE, HIGHLIGHT_COLOUR, 1.0)?;
+            Operation::Highlight { rect, colour } => {
+                shapes::draw_rectangle(cairo, rect, INVISIBLE, *colour, 1.0, 0.0)?;
             }
             Operation::DrawEllipse {
                 ellipse,
                 border,
                 fill,
                 line_width,
+                ..
             } => {
                 cairo.save()?;
                 shapes::draw_ellipse(cairo, ellipse, *border, *fill, *line_width)?;
@@ -360,62 +888,419 @@ impl Operation {
                 text_colour,
                 number,
                 font_description,
+                style,
+                radius,
+                number_style,
+                auto_size,
             } => {
-                let Point { x, y } = centre;
-                let num_str = number.to_string();
-
-                let ellipse = Ellipse {
-                    x: x - BUBBLE_RADIUS,
-                    y: y - BUBBLE_RADIUS,
-                    w: 2.0 * BUBBLE_RADIUS,
-                    h: 2.0 * BUBBLE_RADIUS,
-                };
+                let label = number_style.format(*number);
+                let radius = bubble::radius(*radius, *auto_size, &label, font_description);
 
-                shapes::draw_ellipse(cairo, &ellipse, INVISIBLE, *bubble_colour, 1.0)?;
+                if style.shadow {
+                    let shadow = Point {
+                        x: centre.x + SHADOW_OFFSET,
+                        y: centre.y + SHADOW_OFFSET,
+                    };
+                    shapes::fill_circle(cairo, shadow, radius, SHADOW_COLOUR)?;
+                }
+                shapes::fill_circle(cairo, *centre, radius, *bubble_colour)?;
+                cairo.save()?;
                 draw_text_centred_at(
                     cairo,
                     *centre,
-                    num_str.as_str(),
+                    &label,
                     *text_colour,
                     font_description,
-                );
+                    style,
+                )?;
+                cairo.restore()?;
             }
             Operation::Pencil {
                 start,
                 points,
                 colour,
                 line_width,
+                pressures,
+                ..
+            } => {
+                pencil::draw(cairo, *start, points, pressures, *colour, *line_width)?;
+            }
+            Operation::Measure {
+                start,
+                end,
+                kind,
+                colour,
+            } => {
+                measure::draw(cairo, *start, *end, *kind, *colour)?;
+            }
+            Operation::Highlighter {
+                start,
+                points,
+                colour,
+                line_width,
             } => {
+                let stroke = highlighter::Stroke {
+                    start: *start,
+                    points,
+                    line_width: *line_width,
+                };
+                highlighter::draw(cairo, *colour, &[stroke])?;
+            }
+            Operation::Callout {
+                rect,
+                target,
+                text,
+                text_colour,
+                fill,
+                border,
+                line_width,
+                font_description,
+                ..
+            } => {
+                callout::draw(
+                    cairo,
+                    *rect,
+                    *target,
+                    text,
+                    *text_colour,
+                    *fill,
+                    *border,
+                    *line_width,
+                    font_description,
+                )?;
+            }
+            Operation::Spotlight { rect, style } => {
+                spotlight::draw(cairo, surface, &[(*rect, *style)])?;
+            }
+            Operation::Redact { rect, colour } => {
+                let Rectangle { x, y, w, h } = rect.normalised();
                 cairo.save()?;
-                cairo.set_line_width(*line_width);
-                cairo.set_source_colour(*colour);
-                cairo.move_to(start.x, start.y);
-                for point in points {
-                    cairo.line_to(point.x, point.y);
-                }
-                cairo.stroke()?;
+                cairo.rectangle(x, y, w, h);
+                cairo.set_source_colour(opaque(*colour));
+                cairo.set_operator(cairo::Operator::Source);
+                cairo.fill()?;
                 cairo.restore()?;
             }
+            Operation::Grayscale { rect } => {
+                pixelops::grayscale(cairo, surface, *rect)?;
+            }
+            Operation::Invert { rect } => {
+                pixelops::invert(cairo, surface, *rect)?;
+            }
+            Operation::BrightnessContrast {
+                rect,
+                brightness,
+                contrast,
+            } => {
+                pixelops::brightness_contrast(cairo, surface, *rect, *brightness, *contrast)?;
+            }
+            Operation::Sharpen { rect, amount } => {
+                pixelops::sharpen(cairo, surface, *rect, *amount)?;
+            }
+            Operation::CurvedArrow {
+                start,
+                control,
+                end,
+                colour,
+                line_width,
+                head,
+                double_headed,
+                ..
+            } => {
+                shapes::draw_curved_arrow(
+                    cairo,
+                    *start,
+                    Some(*control),
+                    *end,
+                    *colour,
+                    *line_width,
+                    *head,
+                    *double_headed,
+                )?;
+            }
+            Operation::Polyline {
+                points,
+                closed,
+                border,
+                fill,
+                line_width,
+                ..
+            } => {
+                shapes::draw_polyline(cairo, points, *closed, *border, *fill, *line_width)?;
+            }
+            Operation::Magnify {
+                source,
+                centre,
+                zoom,
+                shape,
+                border,
+                line_width,
+            } => {
+                magnify::draw(
+                    cairo,
+                    surface,
+                    *source,
+                    *centre,
+                    *zoom,
+                    *shape,
+                    *border,
+                    *line_width,
+                )?;
+            }
+            Operation::Stamp { rect, source } => {
+                stamp::draw(cairo, *rect, source)?;
+            }
         };
 
         Ok(())
     }
 
     pub fn set_text(&mut self, input_text: Text) {
-        if let Operation::Text {
-            text,
-            colour,
-            font_description,
-            ..
-        } = self
-        {
-            *text = input_text.string;
-            *font_description = input_text.font_description;
-            *colour = input_text.colour;
+        match self {
+            Operation::Text {
+                text,
+                markdown,
+                colour,
+                font_description,
+                style,
+                ..
+            } => {
+                *text = input_text.string;
+                *markdown = input_text.markdown;
+                *font_description = input_text.font_description;
+                *colour = input_text.colour;
+                *style = input_text.style;
+            }
+            // The box of a callout is its style, so the one from the text is ignored
+            Operation::Callout {
+                text,
+                markdown,
+                text_colour,
+                font_description,
+                ..
+            } => {
+                *text = input_text.string;
+                *markdown = input_text.markdown;
+                *font_description = input_text.font_description;
+                *text_colour = input_text.colour;
+            }
+            Operation::Bubble {
+                number,
+                text_colour,
+                font_description,
+                style,
+                number_style,
+                ..
+            } => {
+                match number_style.parse(&input_text.markdown) {
+                    Some(new_number) => *number = new_number,
+                    None => tracing::warn!(
+                        "Bubbles can only hold numbers, keeping {number} instead of {:?}",
+                        input_text.markdown
+                    ),
+                }
+                *font_description = input_text.font_description;
+                *text_colour = input_text.colour;
+                *style = input_text.style;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the text shown by this operation in the form in which it can be edited, if it shows
+    /// any
+    pub fn editable_text(&self) -> Option<Text> {
+        match self {
+            Operation::Text {
+                text,
+                markdown,
+                colour,
+                font_description,
+                style,
+                ..
+            } => Some(Text {
+                string: text.clone(),
+                // Projects saved before the source was stored only have the markup, which is
+                // still valid input
+                markdown: if markdown.is_empty() {
+                    text.clone()
+                } else {
+                    markdown.clone()
+                },
+                font_description: font_description.clone(),
+                colour: *colour,
+                style: *style,
+            }),
+            Operation::Bubble {
+                number,
+                text_colour,
+                font_description,
+                style,
+                number_style,
+                ..
+            } => Some(Text {
+                string: number_style.format(*number),
+                markdown: number_style.format(*number),
+                font_description: font_description.clone(),
+                colour: *text_colour,
+                style: *style,
+            }),
+            Operation::Callout {
+                text,
+                markdown,
+                text_colour,
+                font_description,
+                ..
+            } => Some(Text {
+                string: text.clone(),
+                markdown: markdown.clone(),
+                font_description: font_description.clone(),
+                colour: *text_colour,
+                style: TextStyle::default(),
+            }),
+            _ => None,
         }
     }
 }
 
+/// Executes `operations` in order, returning the errors encountered along the way
+///
+/// Spotlights are all drawn at once in place of the first one, so that each of them leaves its
+/// region clear, instead of the darkness of one being added on top of the region of another.
+/// Likewise, highlighter strokes of the same colour are drawn at once in place of the first one,
+/// so that they don't get darker where they overlap.
+pub fn execute_all<'a>(
+    operations: impl IntoIterator<Item = &'a Operation>,
+    surface: &ImageSurface,
+    cairo: &Context,
+    is_in_draw_event: bool,
+    should_crop_indicators_be_dashed: bool,
+) -> Vec<(&'a Operation, Error)> {
+    let operations = operations.into_iter().collect::<Vec<_>>();
+    // All the spotlights are drawn together in place of the topmost one, as they cut holes into the
+    // same mask, so it's only over whatever is below that one
+    let topmost_spotlight = operations
+        .iter()
+        .rposition(|operation| matches!(operation, Operation::Spotlight { .. }));
+    let spotlights = operations
+        .iter()
+        .filter_map(|operation| match operation {
+            Operation::Spotlight { rect, style } => Some((*rect, *style)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Likewise, the highlighter strokes of each colour are blended in together, in place of the
+    // topmost one of that colour
+    let highlighter_strokes = operations
+        .iter()
+        .copied()
+        .enumerate()
+        .filter_map(|(index, operation)| match operation {
+            Operation::Highlighter {
+                start,
+                points,
+                colour,
+                line_width,
+            } => Some((
+                index,
+                colour.serialise_to_u32(),
+                highlighter::Stroke {
+                    start: *start,
+                    points,
+                    line_width: *line_width,
+                },
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut errors = vec![];
+    for (index, operation) in operations.into_iter().enumerate() {
+        let result = match operation {
+            Operation::Spotlight { .. } if Some(index) != topmost_spotlight => continue,
+            Operation::Spotlight { .. } => spotlight::draw(cairo, surface, &spotlights),
+            Operation::Highlighter { colour, .. } => {
+                let key = colour.serialise_to_u32();
+                if highlighter_strokes
+                    .iter()
+                    .any(|(stroke_index, stroke_key, _)| {
+                        *stroke_index > index && *stroke_key == key
+                    })
+                {
+                    continue;
+                }
+
+                let strokes = highlighter_strokes
+                    .iter()
+                    .filter(|(_, stroke_key, _)| *stroke_key == key)
+                    .map(|(_, _, stroke)| *stroke)
+                    .collect::<Vec<_>>();
+                highlighter::draw(cairo, *colour, &strokes)
+            }
+            _ => operation.execute(
+                surface,
+                cairo,
+                is_in_draw_event,
+                should_crop_indicators_be_dashed,
+            ),
+        };
+
+        if let Err(why) = result {
+            errors.push((operation, why));
+        }
+    }
+
+    errors
+}
+
+/// Draws the redactions among `operations` straight onto `surface`, so that the pixels they cover
+/// are gone from it rather than only being hidden by whatever is drawn on top of it
+///
+/// This is done to screenshots before they're stored in projects, as redactions can be moved or
+/// removed there, and projects can be exported to formats which keep the screenshot in a layer of
+/// its own.
+pub fn burn_in_redactions(surface: &ImageSurface, operations: &[Operation]) -> Result<(), Error> {
+    let cairo = Context::new(surface)?;
+    for operation in operations {
+        if let Operation::Redact { .. } = operation {
+            operation.execute(surface, &cairo, false, false)?;
+        }
+    }
+    surface.flush();
+
+    Ok(())
+}
+
+/// Returns a copy of `surface` with the redactions among `operations` burnt in, see
+/// [`burn_in_redactions`]
+pub fn redacted_copy(
+    surface: &ImageSurface,
+    operations: &[Operation],
+) -> Result<ImageSurface, Error> {
+    let copy = ImageSurface::create(surface.format(), surface.width(), surface.height())?;
+    let cairo = Context::new(&copy)?;
+    cairo.set_source_surface(surface, 0.0, 0.0)?;
+    cairo.paint()?;
+    drop(cairo);
+
+    burn_in_redactions(&copy, operations)?;
+
+    Ok(copy)
+}
+
+const fn default_highlight_colour() -> Colour {
+    HIGHLIGHT_COLOUR
+}
+
+/// Returns `colour` without any transparency, so that nothing shows through redactions
+const fn opaque(colour: Colour) -> Colour {
+    Colour {
+        alpha: 255,
+        ..colour
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Encountered a cairo error: {0}")]
@@ -428,30 +1313,165 @@ pub enum Error {
     PixelBytes,
 }
 
+/// Draws `text` with the top-left corner of its box at `top_left`, see [`TextStyle`] for what the
+/// box looks like
 fn draw_text_at(
     cairo: &Context,
-    Point { x, y }: Point,
+    top_left: Point,
     text: &str,
     colour: Colour,
     font_description: &FontDescription,
-) {
+    style: &TextStyle,
+) -> Result<(), CairoError> {
     let layout = pangocairo::create_layout(cairo);
-
     layout.set_markup(text);
     layout.set_font_description(Some(font_description));
+
+    let padding = style.effective_padding();
+    if let Some(background) = style.background {
+        let pixel_extents = layout.pixel_extents().1;
+        let text_box = Rectangle {
+            x: top_left.x,
+            y: top_left.y,
+            w: pixel_extents.width() as f64 + 2.0 * padding,
+            h: pixel_extents.height() as f64 + 2.0 * padding,
+        };
+
+        if style.shadow {
+            shapes::rounded_rectangle(
+                cairo,
+                Rectangle {
+                    x: text_box.x + SHADOW_OFFSET,
+                    y: text_box.y + SHADOW_OFFSET,
+                    ..text_box
+                },
+                style.corner_radius,
+            );
+            cairo.set_source_colour(SHADOW_COLOUR);
+            cairo.fill()?;
+        }
+
+        shapes::rounded_rectangle(cairo, text_box, style.corner_radius);
+        cairo.set_source_colour(background);
+        cairo.fill()?;
+    }
+
+    let origin = Point {
+        x: top_left.x + padding,
+        y: top_left.y + padding,
+    };
+    // The box casts the shadow when there is one
+    show_styled_layout(
+        cairo,
+        &layout,
+        origin,
+        colour,
+        style.outline_width,
+        style.shadow && style.background.is_none(),
+    )
+}
+
+/// Draws `layout` with its top-left corner at `origin`, optionally outlined in a colour which
+/// contrasts with `colour` and casting a shadow
+fn show_styled_layout(
+    cairo: &Context,
+    layout: &gtk4::pango::Layout,
+    Point { x, y }: Point,
+    colour: Colour,
+    outline_width: f64,
+    shadow: bool,
+) -> Result<(), CairoError> {
+    pangocairo::update_layout(cairo, layout);
+
+    if shadow {
+        cairo.move_to(x + SHADOW_OFFSET, y + SHADOW_OFFSET);
+        pangocairo::layout_path(cairo, layout);
+        cairo.set_source_colour(SHADOW_COLOUR);
+        cairo.fill()?;
+    }
+
+    if outline_width > 0.0 {
+        cairo.move_to(x, y);
+        pangocairo::layout_path(cairo, layout);
+        cairo.set_source_colour(contrasting_colour(colour));
+        // Half of the stroke is covered by the glyphs drawn on top of it
+        cairo.set_line_width(2.0 * outline_width);
+        cairo.set_line_join(cairo::LineJoin::Round);
+        cairo.stroke()?;
+    }
+
     cairo.move_to(x, y);
     cairo.set_source_colour(colour);
-    pangocairo::update_layout(cairo, &layout);
-    pangocairo::show_layout(cairo, &layout);
+    pangocairo::show_layout(cairo, layout);
+
+    Ok(())
+}
+
+/// Returns black for light colours and white for dark ones
+fn contrasting_colour(colour: Colour) -> Colour {
+    let luma = 0.299 * colour.red as f64 + 0.587 * colour.green as f64 + 0.114 * colour.blue as f64;
+
+    if luma > 127.0 {
+        Colour::BLACK
+    } else {
+        Colour {
+            red: 255,
+            green: 255,
+            blue: 255,
+            alpha: 255,
+        }
+    }
+}
+
+/// Returns the width and height of `text` when drawn with [`draw_text_at`], or when wrapped so that
+/// it's no wider than `wrap_width`
+fn text_size(
+    text: &str,
+    font_description: &FontDescription,
+    wrap_width: Option<f64>,
+) -> (f64, f64) {
+    // NOTE: Layouts need a cairo context to measure text in the same way it'll be drawn, so we
+    //       make a throwaway one.
+    let cairo = ImageSurface::create(cairo::Format::ARgb32, 1, 1)
+        .and_then(|surface| Context::new(&surface));
+    let Ok(cairo) = cairo else {
+        return (0.0, 0.0);
+    };
+
+    let layout = make_layout(&cairo, text, font_description, wrap_width);
+
+    let pixel_extents = layout.pixel_extents().1;
+    (pixel_extents.width() as f64, pixel_extents.height() as f64)
+}
+
+fn make_layout(
+    cairo: &Context,
+    text: &str,
+    font_description: &FontDescription,
+    wrap_width: Option<f64>,
+) -> gtk4::pango::Layout {
+    let layout = pangocairo::create_layout(cairo);
+    layout.set_markup(text);
+    layout.set_font_description(Some(font_description));
+
+    if let Some(wrap_width) = wrap_width {
+        layout.set_width((wrap_width.max(1.0) * gtk4::pango::SCALE as f64) as i32);
+        layout.set_wrap(gtk4::pango::WrapMode::WordChar);
+    }
+
+    layout
 }
 
+/// Draws `text` centred on `centre`, the box described by `style` isn't drawn, as this is used for
+/// the text of bubbles
 fn draw_text_centred_at(
     cairo: &Context,
-    Point { x, y }: Point,
+    centre: Point,
     text: &str,
     colour: Colour,
     font_description: &FontDescription,
-) {
+    style: &TextStyle,
+) -> Result<(), CairoError> {
     let layout = pangocairo::create_layout(cairo);
 
     layout.set_markup(text);
@@ -461,8 +1481,85 @@ fn draw_text_centred_at(
     let w = pixel_extents.width() as f64;
     let h = pixel_extents.height() as f64;
 
-    cairo.move_to(x - w / 2.0, y - h / 2.0);
-    cairo.set_source_colour(colour);
-    pangocairo::update_layout(cairo, &layout);
-    pangocairo::show_layout(cairo, &layout);
+    let origin = Point {
+        x: centre.x - w / 2.0,
+        y: centre.y - h / 2.0,
+    };
+    // The bubble casts the shadow
+    show_styled_layout(cairo, &layout, origin, colour, style.outline_width, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use cairo::{Context, Format, ImageSurface};
+
+    use super::{execute_all, Colour, Operation, Point, Rectangle, StrokeStyle};
+
+    const YELLOW: Colour = Colour {
+        red: 255,
+        green: 255,
+        blue: 0,
+        alpha: 255,
+    };
+    const BLUE: Colour = Colour {
+        red: 0,
+        green: 0,
+        blue: 255,
+        alpha: 255,
+    };
+
+    /// A horizontal highlighter stroke across the whole image at `y`
+    fn highlight(y: f64) -> Operation {
+        Operation::Highlighter {
+            start: Point { x: 0.0, y },
+            points: vec![Point { x: 40.0, y }],
+            colour: YELLOW,
+            line_width: 4.0,
+        }
+    }
+
+    /// Draws `operations` onto a white image and returns the red, green and blue components of the
+    /// pixel at (`x`, `y`)
+    fn pixel_after(operations: &[Operation], x: usize, y: usize) -> [u8; 3] {
+        let mut surface = ImageSurface::create(Format::Rgb24, 40, 20).unwrap();
+        {
+            let cairo = Context::new(&surface).unwrap();
+            cairo.set_source_rgb(1.0, 1.0, 1.0);
+            cairo.paint().unwrap();
+            assert!(execute_all(operations, &surface, &cairo, false, false).is_empty());
+        }
+
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        let offset = y * stride + x * 4;
+        // RGB24 pixels are stored as native-endian u32s, which are BGRx on little-endian machines
+        [data[offset + 2], data[offset + 1], data[offset]]
+    }
+
+    #[test]
+    fn restacked_highlighter_strokes() {
+        let square = Operation::DrawRectangle {
+            rect: Rectangle {
+                x: 10.0,
+                y: 0.0,
+                w: 20.0,
+                h: 20.0,
+            },
+            border: BLUE,
+            fill: BLUE,
+            line_width: 2.0,
+            corner_radius: 0.0,
+            stroke: StrokeStyle::default(),
+        };
+
+        // Both strokes are below the square, so it hides them
+        let below = [highlight(5.0), highlight(15.0), square.clone()];
+        assert_eq!(pixel_after(&below, 20, 5), [0, 0, 255]);
+        assert_eq!(pixel_after(&below, 5, 5), [255, 255, 0]);
+
+        // Once one of them is moved above the square, it's multiplied into it
+        let above = [highlight(15.0), square, highlight(5.0)];
+        assert_eq!(pixel_after(&above, 20, 5), [0, 0, 0]);
+        assert_eq!(pixel_after(&above, 5, 15), [255, 255, 0]);
+    }
 }
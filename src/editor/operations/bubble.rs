@@ -0,0 +1,267 @@
+use gtk4::pango::{self, FontDescription};
+use serde::{Deserialize, Serialize};
+
+use super::text_size;
+
+/// How big bubbles are by default, in pixels
+const BUBBLE_RADIUS: f64 = 20.0;
+/// The size of the numbers in bubbles of the default size, in points
+const BUBBLE_FONT_SIZE: f64 = 40.0;
+/// The space left between the number and the edge of an auto-sized bubble
+const BUBBLE_MARGIN: f64 = 3.0;
+const ROMAN_NUMERALS: &[(i32, &str)] = &[
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+/// How the number inside a bubble is written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumberStyle {
+    /// 1, 2, 3
+    #[default]
+    Decimal,
+    /// a, b, c, ..., z, aa, ab
+    LowerLetter,
+    /// A, B, C, ..., Z, AA, AB
+    UpperLetter,
+    /// I, II, III
+    Roman,
+}
+
+impl NumberStyle {
+    /// The styles in the order in which they're shown in the toolbar
+    pub const ALL: [Self; 4] = [
+        Self::Decimal,
+        Self::LowerLetter,
+        Self::UpperLetter,
+        Self::Roman,
+    ];
+
+    /// Parses a style as stored in the settings
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "decimal" => Self::Decimal,
+            "lower-letter" => Self::LowerLetter,
+            "upper-letter" => Self::UpperLetter,
+            "roman" => Self::Roman,
+            _ => None?,
+        })
+    }
+
+    /// The inverse of [`Self::from_name`]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Decimal => "decimal",
+            Self::LowerLetter => "lower-letter",
+            Self::UpperLetter => "upper-letter",
+            Self::Roman => "roman",
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Decimal => "1, 2, 3",
+            Self::LowerLetter => "a, b, c",
+            Self::UpperLetter => "A, B, C",
+            Self::Roman => "I, II, III",
+        }
+    }
+
+    /// Writes `number` in this style, numbers which can't be written in it (like 0 as a letter) are
+    /// written as decimals
+    pub fn format(self, number: i32) -> String {
+        match self {
+            Self::LowerLetter if number > 0 => letters(number).to_lowercase(),
+            Self::UpperLetter if number > 0 => letters(number),
+            Self::Roman if (1..4000).contains(&number) => {
+                let mut remainder = number;
+                let mut numeral = String::new();
+                for (value, symbol) in ROMAN_NUMERALS {
+                    while remainder >= *value {
+                        numeral.push_str(symbol);
+                        remainder -= value;
+                    }
+                }
+                numeral
+            }
+            _ => number.to_string(),
+        }
+    }
+
+    /// The inverse of [`Self::format`], decimals are accepted in every style
+    pub fn parse(self, text: &str) -> Option<i32> {
+        let text = text.trim();
+        if let Ok(number) = text.parse() {
+            return Some(number);
+        }
+
+        match self {
+            Self::Decimal => None,
+            Self::LowerLetter | Self::UpperLetter => {
+                if text.is_empty() || !text.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return None;
+                }
+
+                text.to_ascii_uppercase()
+                    .bytes()
+                    .try_fold(0i32, |number, c| {
+                        number.checked_mul(26)?.checked_add((c - b'A') as i32 + 1)
+                    })
+            }
+            Self::Roman => {
+                let text = text.to_ascii_uppercase();
+                let mut remainder = text.as_str();
+                let mut number = 0;
+                for (value, symbol) in ROMAN_NUMERALS {
+                    while let Some(rest) = remainder.strip_prefix(symbol) {
+                        number += value;
+                        remainder = rest;
+                    }
+                }
+
+                (remainder.is_empty() && number > 0).then_some(number)
+            }
+        }
+    }
+}
+
+/// How new bubbles look and which number they get
+#[derive(Clone, Copy, Debug)]
+pub struct BubbleSettings {
+    pub next_number: i32,
+    /// The number the bubbles start from, and which [`Self::next_number`] is reset to
+    pub start_number: i32,
+    pub radius: f64,
+    pub number_style: NumberStyle,
+    /// Whether bubbles grow so that their numbers fit inside them
+    pub auto_size: bool,
+}
+
+impl Default for BubbleSettings {
+    fn default() -> Self {
+        Self {
+            next_number: 1,
+            start_number: 1,
+            radius: BUBBLE_RADIUS,
+            number_style: NumberStyle::Decimal,
+            auto_size: true,
+        }
+    }
+}
+
+impl BubbleSettings {
+    /// Returns the number of a new bubble, and moves on to the next one
+    pub fn take_number(&mut self) -> i32 {
+        let number = self.next_number;
+        self.next_number = self.next_number.saturating_add(1);
+        number
+    }
+
+    pub fn reset_numbering(&mut self) {
+        self.next_number = self.start_number;
+    }
+
+    /// The font used for the numbers of new bubbles, which grows and shrinks along with them
+    pub fn font_description(&self) -> FontDescription {
+        let size = BUBBLE_FONT_SIZE * self.radius / BUBBLE_RADIUS;
+        let mut font_description = FontDescription::from_string("Fira Code");
+        font_description.set_size((size * pango::SCALE as f64).round() as i32);
+        font_description
+    }
+}
+
+/// Returns the radius at which a bubble is drawn, auto-sized bubbles grow to fit `label` with
+/// [`BUBBLE_MARGIN`] to spare on either side
+pub fn radius(
+    radius: f64,
+    auto_size: bool,
+    label: &str,
+    font_description: &FontDescription,
+) -> f64 {
+    if !auto_size {
+        return radius;
+    }
+
+    let (width, _) = text_size(label, font_description, None);
+    radius.max(width / 2.0 + BUBBLE_MARGIN)
+}
+
+pub const fn default_radius() -> f64 {
+    BUBBLE_RADIUS
+}
+
+/// Writes `number` in bijective base-26, i.e. 1 is A, 26 is Z and 27 is AA
+fn letters(mut number: i32) -> String {
+    let mut letters = vec![];
+    while number > 0 {
+        number -= 1;
+        letters.push(b'A' + (number % 26) as u8);
+        number /= 26;
+    }
+    letters.reverse();
+
+    String::from_utf8(letters).expect("Letters should be valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NumberStyle;
+
+    #[test]
+    fn format() {
+        assert_eq!(NumberStyle::Decimal.format(42), "42");
+        assert_eq!(NumberStyle::LowerLetter.format(1), "a");
+        assert_eq!(NumberStyle::UpperLetter.format(26), "Z");
+        assert_eq!(NumberStyle::UpperLetter.format(27), "AA");
+        assert_eq!(NumberStyle::UpperLetter.format(702), "ZZ");
+        assert_eq!(NumberStyle::Roman.format(4), "IV");
+        assert_eq!(NumberStyle::Roman.format(1994), "MCMXCIV");
+
+        // Numbers which can't be written in a style fall back to decimals
+        assert_eq!(NumberStyle::LowerLetter.format(0), "0");
+        assert_eq!(NumberStyle::Roman.format(-3), "-3");
+        assert_eq!(NumberStyle::Roman.format(4000), "4000");
+    }
+
+    #[test]
+    fn round_trip() {
+        for style in NumberStyle::ALL {
+            for number in (-5..=3999).chain([4000, 100_000, i32::MAX]) {
+                let text = style.format(number);
+                assert_eq!(style.parse(&text), Some(number), "{style:?} {text}");
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(NumberStyle::LowerLetter.parse("AB"), Some(28));
+        assert_eq!(NumberStyle::Roman.parse(" xiv "), Some(14));
+        assert_eq!(NumberStyle::UpperLetter.parse("12"), Some(12));
+
+        assert_eq!(NumberStyle::Decimal.parse("a"), None);
+        assert_eq!(NumberStyle::LowerLetter.parse(""), None);
+        assert_eq!(NumberStyle::LowerLetter.parse("a1"), None);
+        assert_eq!(NumberStyle::Roman.parse("IIV"), None);
+        assert_eq!(NumberStyle::UpperLetter.parse("ZZZZZZZZ"), None);
+    }
+
+    #[test]
+    fn names() {
+        for style in NumberStyle::ALL {
+            assert_eq!(NumberStyle::from_name(style.name()), Some(style));
+        }
+        assert_eq!(NumberStyle::from_name("greek"), None);
+    }
+}
@@ -0,0 +1,201 @@
+use cairo::Context;
+use gtk4::pango::FontDescription;
+use kcshot_data::{
+    colour::Colour,
+    geometry::{Point, Rectangle},
+};
+
+use super::{make_layout, shapes, text_size, Error};
+use crate::editor::utils::CairoExt;
+
+/// The size of a callout before its text is known
+pub const CALLOUT_WIDTH: f64 = 240.0;
+pub const CALLOUT_HEIGHT: f64 = 60.0;
+/// Where the box goes when the tool is only clicked, relative to the point at which the tail points
+const DEFAULT_OFFSET: Point = Point { x: 40.0, y: 40.0 };
+const PADDING: f64 = 10.0;
+const CORNER_RADIUS: f64 = 10.0;
+/// How wide the tail is where it meets the box
+const TAIL_WIDTH: f64 = 20.0;
+
+/// Returns where the box of a callout pointing at `target` goes when the tool is released at `end`,
+/// the box extends away from `target` so that the tail doesn't cross it
+pub fn place(target: Point, end: Point) -> Rectangle {
+    let end = if (end - target).dist() < 1.0 {
+        target + DEFAULT_OFFSET
+    } else {
+        end
+    };
+
+    Rectangle {
+        x: if end.x >= target.x {
+            end.x
+        } else {
+            end.x - CALLOUT_WIDTH
+        },
+        y: if end.y >= target.y {
+            end.y
+        } else {
+            end.y - CALLOUT_HEIGHT
+        },
+        w: CALLOUT_WIDTH,
+        h: CALLOUT_HEIGHT,
+    }
+}
+
+/// Returns the box of a callout, which grows downwards when `text` doesn't fit in `rect`
+pub fn text_box(rect: Rectangle, text: &str, font_description: &FontDescription) -> Rectangle {
+    let rect = rect.normalised();
+    let (_, text_height) = text_size(text, font_description, Some(wrap_width(rect)));
+
+    Rectangle {
+        h: rect.h.max(text_height + 2.0 * PADDING),
+        ..rect
+    }
+}
+
+/// Draws a rounded box with a tail pointing at `target`, containing `text` wrapped to the width of
+/// the box
+#[allow(clippy::too_many_arguments)]
+pub fn draw(
+    cairo: &Context,
+    rect: Rectangle,
+    target: Point,
+    text: &str,
+    text_colour: Colour,
+    fill: Colour,
+    border: Colour,
+    line_width: f64,
+    font_description: &FontDescription,
+) -> Result<(), Error> {
+    let text_box = text_box(rect, text, font_description);
+
+    cairo.save()?;
+
+    // The box and the tail overlap, so we stroke both of them twice as wide as the border and then
+    // cut out their insides, which leaves only the outline of their union
+    if line_width > 0.0 {
+        cairo.push_group();
+        cairo.set_source_colour(border);
+        cairo.set_line_width(2.0 * line_width);
+        cairo.set_line_join(cairo::LineJoin::Round);
+        shapes::rounded_rectangle(cairo, text_box, CORNER_RADIUS);
+        tail(cairo, text_box, target);
+        cairo.stroke()?;
+        cairo.set_operator(cairo::Operator::Clear);
+        fill_shapes(cairo, text_box, target)?;
+        cairo.pop_group_to_source()?;
+        cairo.paint()?;
+    }
+
+    // Where they overlap, translucent fills would be drawn twice if they weren't put in a group
+    cairo.push_group();
+    cairo.set_source_colour(fill);
+    cairo.set_operator(cairo::Operator::Source);
+    fill_shapes(cairo, text_box, target)?;
+    cairo.pop_group_to_source()?;
+    cairo.paint()?;
+
+    let layout = make_layout(cairo, text, font_description, Some(wrap_width(text_box)));
+    cairo.move_to(text_box.x + PADDING, text_box.y + PADDING);
+    cairo.set_source_colour(text_colour);
+    pangocairo::update_layout(cairo, &layout);
+    pangocairo::show_layout(cairo, &layout);
+
+    cairo.restore()?;
+
+    Ok(())
+}
+
+/// Returns whether `point` is on the tail of a callout, within `tolerance`
+pub fn is_on_tail(rect: Rectangle, target: Point, point: Point, tolerance: f64) -> bool {
+    let Some((a, b)) = tail_base(rect.normalised(), target) else {
+        return false;
+    };
+    let middle = (a + b) * 0.5;
+
+    super::selection::distance_to_segment(point, middle, target) <= TAIL_WIDTH / 2.0 + tolerance
+}
+
+fn wrap_width(rect: Rectangle) -> f64 {
+    rect.w - 2.0 * PADDING
+}
+
+/// Fills the box and its tail one after the other, as their paths wind in opposite directions
+fn fill_shapes(cairo: &Context, text_box: Rectangle, target: Point) -> Result<(), cairo::Error> {
+    shapes::rounded_rectangle(cairo, text_box, CORNER_RADIUS);
+    cairo.fill()?;
+    tail(cairo, text_box, target);
+    cairo.fill()
+}
+
+/// Adds the path of the tail to `cairo`
+fn tail(cairo: &Context, text_box: Rectangle, target: Point) {
+    if let Some((a, b)) = tail_base(text_box, target) {
+        cairo.move_to(a.x, a.y);
+        cairo.line_to(target.x, target.y);
+        cairo.line_to(b.x, b.y);
+        cairo.close_path();
+    }
+}
+
+/// Returns the ends of the base of the tail, which sits on the side of the box facing `target`, or
+/// `None` when `target` is inside the box
+fn tail_base(rect: Rectangle, target: Point) -> Option<(Point, Point)> {
+    if rect.contains(target) {
+        return None;
+    }
+
+    let centre = Point {
+        x: rect.x + rect.w / 2.0,
+        y: rect.y + rect.h / 2.0,
+    };
+    let delta = target - centre;
+    let half_width = TAIL_WIDTH / 2.0;
+    // The base is pushed a bit into the box, so that no gap shows between them
+    let inset = CORNER_RADIUS / 2.0;
+
+    // Keeps the base on the straight part of the side, away from the rounded corners
+    let along = |value: f64, start: f64, length: f64| {
+        let margin = (CORNER_RADIUS + half_width).min(length / 2.0);
+        value.clamp(start + margin, start + length - margin)
+    };
+
+    let base = if delta.x.abs() * rect.h > delta.y.abs() * rect.w {
+        let x = if delta.x > 0.0 {
+            rect.x + rect.w - inset
+        } else {
+            rect.x + inset
+        };
+        let y = along(target.y, rect.y, rect.h);
+        (
+            Point {
+                x,
+                y: y - half_width,
+            },
+            Point {
+                x,
+                y: y + half_width,
+            },
+        )
+    } else {
+        let y = if delta.y > 0.0 {
+            rect.y + rect.h - inset
+        } else {
+            rect.y + inset
+        };
+        let x = along(target.x, rect.x, rect.w);
+        (
+            Point {
+                x: x - half_width,
+                y,
+            },
+            Point {
+                x: x + half_width,
+                y,
+            },
+        )
+    };
+
+    Some(base)
+}
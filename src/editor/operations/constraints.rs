@@ -0,0 +1,387 @@
+use std::{f64::consts::PI, fmt};
+
+use kcshot_data::geometry::{Point, Rectangle};
+
+use super::selection::Side;
+
+/// Constrained lines and arrows are kept at multiples of this angle
+const ANGLE_STEP: f64 = PI / 12.0;
+
+/// The presets which are always available, see [`CropConstraint::from_preset`] for their format
+pub const BUILTIN_CROP_PRESETS: &[&str] = &["free", "16:9", "4:3", "1:1", "1280x720"];
+
+/// Restricts the shape of the crop regions the user can select
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CropConstraint {
+    #[default]
+    Free,
+    /// The ratio between the width and the height of the crop region
+    AspectRatio {
+        width: f64,
+        height: f64,
+    },
+    FixedSize {
+        width: f64,
+        height: f64,
+    },
+}
+
+impl CropConstraint {
+    /// Parses a preset as stored in the settings, which is either "free", an aspect ratio like
+    /// "16:9" or a size like "1280x720"
+    pub fn from_preset(preset: &str) -> Option<Self> {
+        if preset == "free" {
+            return Some(Self::Free);
+        }
+
+        let parse_pair = |separator| {
+            let (width, height) = preset.split_once(separator)?;
+            let width = width.trim().parse::<f64>().ok()?;
+            let height = height.trim().parse::<f64>().ok()?;
+
+            (width > 0.0 && height > 0.0).then_some((width, height))
+        };
+
+        if let Some((width, height)) = parse_pair(':') {
+            Some(Self::AspectRatio { width, height })
+        } else {
+            parse_pair('x').map(|(width, height)| Self::FixedSize { width, height })
+        }
+    }
+
+    /// The inverse of [`Self::from_preset`]
+    pub fn to_preset(self) -> String {
+        match self {
+            CropConstraint::Free => "free".to_owned(),
+            CropConstraint::AspectRatio { width, height } => format!("{width}:{height}"),
+            CropConstraint::FixedSize { width, height } => format!("{width}x{height}"),
+        }
+    }
+
+    /// Reshapes `rect` so that it respects the constraint, the corner at (`rect.x`, `rect.y`) stays
+    /// in place while the opposite corner is moved
+    ///
+    /// When `lock_square` is set, free-form crop regions are made square.
+    pub fn apply(self, rect: Rectangle, lock_square: bool) -> Rectangle {
+        let (width, height) = match self {
+            CropConstraint::Free if !lock_square => return rect,
+            CropConstraint::Free => (1.0, 1.0),
+            CropConstraint::AspectRatio { width, height } => (width, height),
+            CropConstraint::FixedSize { width, height } => {
+                return Rectangle {
+                    w: width.copysign(rect.w),
+                    h: height.copysign(rect.h),
+                    ..rect
+                };
+            }
+        };
+
+        let ratio = width / height;
+        let (w, h) = if rect.w.abs() / ratio > rect.h.abs() {
+            (rect.w, (rect.w.abs() / ratio).copysign(rect.h))
+        } else {
+            ((rect.h.abs() * ratio).copysign(rect.w), rect.h)
+        };
+
+        Rectangle { w, h, ..rect }
+    }
+
+    /// Reshapes `rect`, which was resized through the handle on its (`horizontal`, `vertical`)
+    /// sides, so that it respects the constraint, while the sides opposite of the handle stay in
+    /// place
+    pub fn apply_to_resize(
+        self,
+        rect: Rectangle,
+        (horizontal, vertical): (Side, Side),
+    ) -> Rectangle {
+        let (width, height) = match self {
+            CropConstraint::Free => return rect,
+            CropConstraint::FixedSize { width, height } => (width, height),
+            CropConstraint::AspectRatio { width, height } => {
+                let ratio = width / height;
+                let (w, h) = (rect.w.abs(), rect.h.abs());
+                // Handles on the edges only move along one axis, so that axis decides the size
+                match (horizontal, vertical) {
+                    (_, Side::Middle) => (w, w / ratio),
+                    (Side::Middle, _) => (h * ratio, h),
+                    _ if w / ratio > h => (w, w / ratio),
+                    _ => (h * ratio, h),
+                }
+            }
+        };
+
+        let (x, w) = place_along_axis(rect.x, rect.w, horizontal, width);
+        let (y, h) = place_along_axis(rect.y, rect.h, vertical, height);
+
+        Rectangle { x, y, w, h }
+    }
+
+    /// Returns the crop region selected by clicking at `point` without dragging, if the constraint
+    /// determines one
+    pub fn region_at(self, point: Point) -> Option<Rectangle> {
+        match self {
+            CropConstraint::FixedSize { width, height } => Some(Rectangle {
+                x: point.x,
+                y: point.y,
+                w: width,
+                h: height,
+            }),
+            CropConstraint::Free | CropConstraint::AspectRatio { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for CropConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CropConstraint::Free => write!(f, "Free"),
+            CropConstraint::AspectRatio { width, height } => write!(f, "{width}:{height}"),
+            CropConstraint::FixedSize { width, height } => write!(f, "{width} × {height}"),
+        }
+    }
+}
+
+/// Gives the span from `start` to `start + length` the new `size`, while keeping the end opposite
+/// of `side` in place, or its middle if both ends are free to move
+fn place_along_axis(start: f64, length: f64, side: Side, size: f64) -> (f64, f64) {
+    let new_length = size.copysign(length);
+    match side {
+        Side::Start => (start + length - new_length, new_length),
+        Side::Middle => (start + (length - new_length) / 2.0, new_length),
+        Side::End => (start, new_length),
+    }
+}
+
+/// Makes a drag of (`width`, `height`) as wide as it is tall, so that squares and circles are drawn
+pub fn square(width: f64, height: f64) -> (f64, f64) {
+    let side = width.abs().max(height.abs());
+
+    (side.copysign(width), side.copysign(height))
+}
+
+/// Rotates a drag of (`width`, `height`) to the nearest multiple of 15°, keeping its length
+pub fn snap_angle(width: f64, height: f64) -> (f64, f64) {
+    let length = width.hypot(height);
+    let angle = (height.atan2(width) / ANGLE_STEP).round() * ANGLE_STEP;
+
+    (length * angle.cos(), length * angle.sin())
+}
+
+/// Returns the region spanned by dragging (`width`, `height`) away from `origin`, which is one of its
+/// corners, or its centre when `from_centre` is set
+pub fn region_from(origin: Point, width: f64, height: f64, from_centre: bool) -> Rectangle {
+    if from_centre {
+        Rectangle {
+            x: origin.x - width,
+            y: origin.y - height,
+            w: 2.0 * width,
+            h: 2.0 * height,
+        }
+    } else {
+        Rectangle {
+            x: origin.x,
+            y: origin.y,
+            w: width,
+            h: height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use super::{
+        region_from, snap_angle, square, CropConstraint, Point, Rectangle, Side, ANGLE_STEP,
+        BUILTIN_CROP_PRESETS,
+    };
+
+    fn rect(x: f64, y: f64, w: f64, h: f64) -> Rectangle {
+        Rectangle { x, y, w, h }
+    }
+
+    fn parts(Rectangle { x, y, w, h }: Rectangle) -> (f64, f64, f64, f64) {
+        (x, y, w, h)
+    }
+
+    fn assert_close((a, b): (f64, f64), (c, d): (f64, f64)) {
+        assert!(
+            (a - c).abs() < 1e-9 && (b - d).abs() < 1e-9,
+            "({a}, {b}) != ({c}, {d})"
+        );
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(
+            CropConstraint::from_preset("free"),
+            Some(CropConstraint::Free)
+        );
+        assert_eq!(
+            CropConstraint::from_preset("16:9"),
+            Some(CropConstraint::AspectRatio {
+                width: 16.0,
+                height: 9.0
+            })
+        );
+        assert_eq!(
+            CropConstraint::from_preset(" 1280 x 720 "),
+            Some(CropConstraint::FixedSize {
+                width: 1280.0,
+                height: 720.0
+            })
+        );
+        assert_eq!(
+            CropConstraint::from_preset("2.35:1"),
+            Some(CropConstraint::AspectRatio {
+                width: 2.35,
+                height: 1.0
+            })
+        );
+
+        assert_eq!(CropConstraint::from_preset(""), None);
+        assert_eq!(CropConstraint::from_preset("16:0"), None);
+        assert_eq!(CropConstraint::from_preset("-4:3"), None);
+        assert_eq!(CropConstraint::from_preset("1280x"), None);
+        assert_eq!(CropConstraint::from_preset("wide"), None);
+
+        for preset in BUILTIN_CROP_PRESETS {
+            let constraint = CropConstraint::from_preset(preset).unwrap();
+            assert_eq!(constraint.to_preset(), *preset);
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let ratio = CropConstraint::AspectRatio {
+            width: 2.0,
+            height: 1.0,
+        };
+        assert_eq!(
+            parts(ratio.apply(rect(10.0, 10.0, 100.0, 10.0), false)),
+            (10.0, 10.0, 100.0, 50.0)
+        );
+        assert_eq!(
+            parts(ratio.apply(rect(10.0, 10.0, 10.0, 100.0), false)),
+            (10.0, 10.0, 200.0, 100.0)
+        );
+        assert_eq!(
+            parts(ratio.apply(rect(10.0, 10.0, -100.0, 10.0), false)),
+            (10.0, 10.0, -100.0, 50.0)
+        );
+
+        let fixed = CropConstraint::FixedSize {
+            width: 30.0,
+            height: 20.0,
+        };
+        assert_eq!(
+            parts(fixed.apply(rect(5.0, 5.0, -1.0, 1.0), false)),
+            (5.0, 5.0, -30.0, 20.0)
+        );
+
+        let free = rect(0.0, 0.0, 40.0, -10.0);
+        assert_eq!(parts(CropConstraint::Free.apply(free, false)), parts(free));
+        assert_eq!(
+            parts(CropConstraint::Free.apply(free, true)),
+            (0.0, 0.0, 40.0, -40.0)
+        );
+    }
+
+    #[test]
+    fn region_at() {
+        let point = Point { x: 3.0, y: 4.0 };
+        let fixed = CropConstraint::FixedSize {
+            width: 30.0,
+            height: 20.0,
+        };
+
+        assert_eq!(
+            fixed.region_at(point).map(parts),
+            Some((3.0, 4.0, 30.0, 20.0))
+        );
+        assert!(CropConstraint::Free.region_at(point).is_none());
+    }
+
+    #[test]
+    fn squares() {
+        assert_eq!(square(10.0, 3.0), (10.0, 10.0));
+        assert_eq!(square(-2.0, 7.0), (-7.0, 7.0));
+        assert_eq!(square(5.0, -5.0), (5.0, -5.0));
+    }
+
+    #[test]
+    fn snapped_angles() {
+        let length = 10f64.hypot(1.0);
+        assert_close(snap_angle(10.0, 1.0), (length, 0.0));
+
+        let length = 10f64.hypot(9.0);
+        assert_close(
+            snap_angle(10.0, 9.0),
+            (length * FRAC_1_SQRT_2, length * FRAC_1_SQRT_2),
+        );
+
+        // 163° is closest to 165°
+        let length = 10f64.hypot(3.0);
+        let angle = 11.0 * ANGLE_STEP;
+        assert_close(
+            snap_angle(-10.0, 3.0),
+            (length * angle.cos(), length * angle.sin()),
+        );
+
+        assert_close(snap_angle(0.0, -4.0), (0.0, -4.0));
+        assert_close(snap_angle(0.0, 0.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn regions() {
+        let origin = Point { x: 50.0, y: 50.0 };
+
+        assert_eq!(
+            parts(region_from(origin, 10.0, -20.0, false)),
+            (50.0, 50.0, 10.0, -20.0)
+        );
+        assert_eq!(
+            parts(region_from(origin, 10.0, -20.0, true)),
+            (40.0, 70.0, 20.0, -40.0)
+        );
+    }
+
+    #[test]
+    fn apply_to_resize() {
+        let ratio = CropConstraint::AspectRatio {
+            width: 2.0,
+            height: 1.0,
+        };
+        let region = rect(0.0, 0.0, 100.0, 40.0);
+
+        // The right edge decides the size, and the region grows evenly above and below
+        assert_eq!(
+            parts(ratio.apply_to_resize(region, (Side::End, Side::Middle))),
+            (0.0, -5.0, 100.0, 50.0)
+        );
+        // The top edge decides the size, and the bottom stays in place
+        assert_eq!(
+            parts(ratio.apply_to_resize(region, (Side::Middle, Side::Start))),
+            (10.0, 0.0, 80.0, 40.0)
+        );
+        // Corners keep the larger of the two sizes, and the opposite corner stays in place
+        assert_eq!(
+            parts(ratio.apply_to_resize(region, (Side::Start, Side::Start))),
+            (0.0, -10.0, 100.0, 50.0)
+        );
+
+        let fixed = CropConstraint::FixedSize {
+            width: 30.0,
+            height: 20.0,
+        };
+        assert_eq!(
+            parts(fixed.apply_to_resize(region, (Side::End, Side::End))),
+            (0.0, 0.0, 30.0, 20.0)
+        );
+
+        assert_eq!(
+            parts(CropConstraint::Free.apply_to_resize(region, (Side::Start, Side::End))),
+            parts(region)
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use cairo::Context;
+use kcshot_data::{colour::Colour, geometry::Point};
+
+use super::{opaque, Error};
+use crate::editor::utils::CairoExt;
+
+/// A freehand stroke made with [`super::Tool::Highlighter`]
+#[derive(Clone, Copy, Debug)]
+pub struct Stroke<'a> {
+    pub start: Point,
+    pub points: &'a [Point],
+    pub line_width: f64,
+}
+
+/// Draws `strokes` like a highlighter marker would, i.e. by multiplying `colour` into the image,
+/// so that dark text below them stays legible
+///
+/// The strokes are first drawn together in an opaque layer, which is then blended in at once, so
+/// that they don't get darker where they cross each other.
+pub fn draw(cairo: &Context, colour: Colour, strokes: &[Stroke<'_>]) -> Result<(), Error> {
+    cairo.save()?;
+    cairo.push_group();
+
+    cairo.set_source_colour(opaque(colour));
+    cairo.set_line_cap(cairo::LineCap::Round);
+    cairo.set_line_join(cairo::LineJoin::Round);
+    for stroke in strokes {
+        cairo.set_line_width(stroke.line_width);
+        cairo.move_to(stroke.start.x, stroke.start.y);
+        for point in stroke.points {
+            cairo.line_to(point.x, point.y);
+        }
+        cairo.stroke()?;
+    }
+
+    cairo.pop_group_to_source()?;
+    cairo.set_operator(cairo::Operator::Multiply);
+    cairo.paint_with_alpha(colour.alpha as f64 / 255.0)?;
+    cairo.restore()?;
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use cairo::{Context, ImageSurface};
+use kcshot_data::{
+    colour::Colour,
+    geometry::{Point, Rectangle},
+};
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+use crate::editor::utils::CairoExt;
+
+/// How far apart the magnified region and the inset showing it are placed at first
+const INSET_GAP: f64 = 20.0;
+
+/// The shape of the inset showing a magnified region
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InsetShape {
+    #[default]
+    Circle,
+    Box,
+}
+
+/// How the insets made by [`super::Tool::Magnify`] look
+#[derive(Clone, Copy, Debug)]
+pub struct MagnifySettings {
+    pub zoom: f64,
+    pub shape: InsetShape,
+}
+
+impl Default for MagnifySettings {
+    fn default() -> Self {
+        Self {
+            zoom: 2.0,
+            shape: InsetShape::Circle,
+        }
+    }
+}
+
+/// Returns where the inset showing `source` goes at first, which is right next to it
+pub fn inset_centre_for(source: Rectangle, zoom: f64) -> Point {
+    let Rectangle { x, y, w, h } = source.normalised();
+
+    Point {
+        x: x + w + INSET_GAP + w * zoom / 2.0,
+        y: y + h / 2.0,
+    }
+}
+
+/// Returns the region covered by the inset showing `source`
+///
+/// Circular insets go around the magnified region, so that all of it is shown.
+pub fn inset_rect(source: Rectangle, centre: Point, zoom: f64, shape: InsetShape) -> Rectangle {
+    let Rectangle { w, h, .. } = source.normalised();
+    let (w, h) = match shape {
+        InsetShape::Box => (w * zoom, h * zoom),
+        InsetShape::Circle => {
+            let diameter = (w * w + h * h).sqrt() * zoom;
+            (diameter, diameter)
+        }
+    };
+
+    Rectangle {
+        x: centre.x - w / 2.0,
+        y: centre.y - h / 2.0,
+        w,
+        h,
+    }
+}
+
+/// Draws `source` magnified `zoom` times in an inset centred on `centre`, along with outlines of
+/// both
+#[allow(clippy::too_many_arguments)]
+pub fn draw(
+    cairo: &Context,
+    surface: &ImageSurface,
+    source: Rectangle,
+    centre: Point,
+    zoom: f64,
+    shape: InsetShape,
+    border: Colour,
+    line_width: f64,
+) -> Result<(), Error> {
+    let source = source.normalised();
+    if source.area() < 1.0 {
+        return Ok(());
+    }
+    let inset = inset_rect(source, centre, zoom, shape);
+
+    cairo.save()?;
+
+    cairo.rectangle(source.x, source.y, source.w, source.h);
+    cairo.set_source_colour(border);
+    cairo.set_line_width(1.0);
+    cairo.stroke()?;
+
+    cairo.save()?;
+    add_inset_path(cairo, inset, shape);
+    cairo.clip();
+    cairo.translate(centre.x, centre.y);
+    cairo.scale(zoom, zoom);
+    cairo.set_source_surface(
+        surface,
+        -(source.x + source.w / 2.0),
+        -(source.y + source.h / 2.0),
+    )?;
+    cairo.paint()?;
+    cairo.restore()?;
+
+    add_inset_path(cairo, inset, shape);
+    cairo.set_source_colour(border);
+    cairo.set_line_width(line_width);
+    cairo.stroke()?;
+
+    cairo.restore()?;
+
+    Ok(())
+}
+
+/// Adds the path of an inset covering `inset`, see [`inset_rect`]
+fn add_inset_path(cairo: &Context, inset: Rectangle, shape: InsetShape) {
+    match shape {
+        InsetShape::Box => cairo.rectangle(inset.x, inset.y, inset.w, inset.h),
+        InsetShape::Circle => {
+            cairo.new_sub_path();
+            cairo.arc(
+                inset.x + inset.w / 2.0,
+                inset.y + inset.h / 2.0,
+                inset.w / 2.0,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use cairo::Context;
+use gtk4::pango::FontDescription;
+use kcshot_data::{
+    colour::Colour,
+    geometry::{Point, Rectangle},
+};
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+use crate::editor::utils::CairoExt;
+
+/// The length of the ticks drawn across both ends of a dimension line
+const TICK_LENGTH: f64 = 10.0;
+const LABEL_PADDING: f64 = 3.0;
+const LABEL_BACKGROUND_COLOUR: Colour = Colour {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 191,
+};
+const LABEL_TEXT_COLOUR: Colour = Colour {
+    red: 255,
+    green: 255,
+    blue: 255,
+    alpha: 255,
+};
+
+/// What is measured by [`super::Tool::Measure`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MeasureKind {
+    /// The distance between two points, along with how far apart they are on each axis and the
+    /// angle of the line between them
+    #[default]
+    Distance,
+    /// The width and height of the rectangle between two points
+    Size,
+}
+
+/// Draws a dimension line between `start` and `end`, or the rectangle between them, along with a
+/// label describing it
+pub fn draw(
+    cairo: &Context,
+    start: Point,
+    end: Point,
+    kind: MeasureKind,
+    colour: Colour,
+) -> Result<(), Error> {
+    cairo.save()?;
+    cairo.set_source_colour(colour);
+    cairo.set_line_width(1.0);
+
+    let label = match kind {
+        MeasureKind::Distance => {
+            cairo.move_to(start.x, start.y);
+            cairo.line_to(end.x, end.y);
+
+            let delta = end - start;
+            let length = delta.dist();
+            if length > 0.0 {
+                // The ticks are perpendicular to the line
+                let (nx, ny) = (
+                    -delta.y / length * TICK_LENGTH / 2.0,
+                    delta.x / length * TICK_LENGTH / 2.0,
+                );
+                for Point { x, y } in [start, end] {
+                    cairo.move_to(x + nx, y + ny);
+                    cairo.line_to(x - nx, y - ny);
+                }
+            }
+            cairo.stroke()?;
+
+            distance_label(delta)
+        }
+        MeasureKind::Size => {
+            let Rectangle { x, y, w, h } = Rectangle {
+                x: start.x,
+                y: start.y,
+                w: end.x - start.x,
+                h: end.y - start.y,
+            }
+            .normalised();
+            cairo.rectangle(x, y, w, h);
+            cairo.set_dash(&[4.0, 4.0], 0.0);
+            cairo.stroke()?;
+
+            format!("{} × {}", w.round(), h.round())
+        }
+    };
+
+    let centre = Point {
+        x: (start.x + end.x) / 2.0,
+        y: (start.y + end.y) / 2.0,
+    };
+    draw_label(cairo, centre, &label)?;
+
+    cairo.restore()?;
+
+    Ok(())
+}
+
+fn distance_label(delta: Point) -> String {
+    // NOTE: The y axis points downwards, so we flip it in order to measure angles counter-clockwise,
+    //       like they usually are
+    let angle = (-delta.y).atan2(delta.x).to_degrees();
+
+    format!(
+        "{:.1} px\nΔx {}, Δy {}\n{angle:.1}°",
+        delta.dist(),
+        delta.x.abs().round(),
+        delta.y.abs().round(),
+    )
+}
+
+/// Draws `label` in a dark box centred on `centre`
+pub(super) fn draw_label(cairo: &Context, centre: Point, label: &str) -> Result<(), cairo::Error> {
+    let layout = pangocairo::create_layout(cairo);
+    layout.set_font_description(Some(&FontDescription::from_string("Monospace 9")));
+    layout.set_alignment(gtk4::pango::Alignment::Center);
+    layout.set_text(label);
+    let (width, height) = layout.pixel_size();
+    let (width, height) = (width as f64, height as f64);
+
+    let (x, y) = (centre.x - width / 2.0, centre.y - height / 2.0);
+    cairo.set_source_colour(LABEL_BACKGROUND_COLOUR);
+    cairo.rectangle(
+        x - LABEL_PADDING,
+        y - LABEL_PADDING,
+        width + 2.0 * LABEL_PADDING,
+        height + 2.0 * LABEL_PADDING,
+    );
+    cairo.fill()?;
+
+    cairo.set_source_colour(LABEL_TEXT_COLOUR);
+    cairo.move_to(x, y);
+    pangocairo::update_layout(cairo, &layout);
+    pangocairo::show_layout(cairo, &layout);
+
+    Ok(())
+}
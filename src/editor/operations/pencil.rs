@@ -0,0 +1,220 @@
+use cairo::Context;
+use kcshot_data::{colour::Colour, geometry::Point};
+
+use super::{opaque, selection::distance_to_segment, Error};
+use crate::editor::utils::CairoExt;
+
+/// How far (in pixels) points can be from the simplified stroke before they have to be kept
+const SIMPLIFY_TOLERANCE: f64 = 0.75;
+/// Strokes are never drawn thinner than this fraction of their width, no matter how lightly the
+/// stylus was pressed
+const MIN_PRESSURE: f64 = 0.1;
+
+/// Drops the points of a stroke which barely change its shape, so that the curve drawn through the
+/// remaining ones isn't wobbly, the pressures recorded along with the points are kept in step
+///
+/// `pressures` holds the pressure at `start` followed by the ones at the `points`.
+pub fn simplify(start: Point, points: &mut Vec<Point>, pressures: &mut Vec<f64>) {
+    let all_points = std::iter::once(start)
+        .chain(points.iter().copied())
+        .collect::<Vec<_>>();
+    if all_points.len() <= 2 {
+        return;
+    }
+
+    let last = all_points.len() - 1;
+    let mut keep = vec![false; all_points.len()];
+    keep[0] = true;
+    keep[last] = true;
+    mark_kept_points(&all_points, 0, last, &mut keep);
+
+    *points = all_points
+        .iter()
+        .zip(&keep)
+        .skip(1)
+        .filter(|(_, keep)| **keep)
+        .map(|(point, _)| *point)
+        .collect();
+    *pressures = pressures
+        .iter()
+        .zip(&keep)
+        .filter(|(_, keep)| **keep)
+        .map(|(pressure, _)| *pressure)
+        .collect();
+}
+
+/// The Ramer-Douglas-Peucker algorithm, i.e. keeps the point between `first` and `last` which is
+/// the furthest from the segment between them if it's too far, and repeats on both halves
+fn mark_kept_points(points: &[Point], first: usize, last: usize, keep: &mut [bool]) {
+    if last <= first + 1 {
+        return;
+    }
+
+    let (furthest, distance) = (first + 1..last)
+        .map(|idx| {
+            (
+                idx,
+                distance_to_segment(points[idx], points[first], points[last]),
+            )
+        })
+        .fold((first, 0.0), |furthest, candidate| {
+            if candidate.1 > furthest.1 {
+                candidate
+            } else {
+                furthest
+            }
+        });
+
+    if distance > SIMPLIFY_TOLERANCE {
+        keep[furthest] = true;
+        mark_kept_points(points, first, furthest, keep);
+        mark_kept_points(points, furthest, last, keep);
+    }
+}
+
+/// Draws a smooth curve going through `start` and `points`, as a Catmull-Rom spline
+///
+/// When `pressures` isn't empty, the width of the stroke follows them, with `line_width` being the
+/// width at full pressure.
+pub fn draw(
+    cairo: &Context,
+    start: Point,
+    points: &[Point],
+    pressures: &[f64],
+    colour: Colour,
+    line_width: f64,
+) -> Result<(), Error> {
+    let all_points = std::iter::once(start)
+        .chain(points.iter().copied())
+        .collect::<Vec<_>>();
+
+    cairo.save()?;
+    cairo.set_line_cap(cairo::LineCap::Round);
+    cairo.set_line_join(cairo::LineJoin::Round);
+
+    if pressures.is_empty() {
+        cairo.set_source_colour(colour);
+        cairo.set_line_width(line_width);
+        cairo.move_to(start.x, start.y);
+        for idx in 1..all_points.len() {
+            add_segment(cairo, &all_points, idx);
+        }
+        cairo.stroke()?;
+    } else {
+        // Every segment gets its own width, they're drawn opaque and blended in at once so that
+        // translucent strokes don't get darker where the segments meet
+        cairo.push_group();
+        cairo.set_source_colour(opaque(colour));
+        // The dashes would start over with each segment if they weren't carried over
+        let (dashes, _) = cairo.dash();
+        let mut dash_offset = 0.0;
+        let pressure_at = |idx: usize| {
+            pressures
+                .get(idx)
+                .or(pressures.last())
+                .copied()
+                .unwrap_or(1.0)
+                .clamp(MIN_PRESSURE, 1.0)
+        };
+        for idx in 1..all_points.len() {
+            let pressure = (pressure_at(idx - 1) + pressure_at(idx)) / 2.0;
+            cairo.set_line_width(line_width * pressure);
+            cairo.set_dash(&dashes, dash_offset);
+            let Point { x, y } = all_points[idx - 1];
+            cairo.move_to(x, y);
+            add_segment(cairo, &all_points, idx);
+            cairo.stroke()?;
+            dash_offset += (all_points[idx] - all_points[idx - 1]).dist();
+        }
+        cairo.pop_group_to_source()?;
+        cairo.paint_with_alpha(colour.alpha as f64 / 255.0)?;
+    }
+
+    cairo.restore()?;
+
+    Ok(())
+}
+
+/// Adds the part of the Catmull-Rom spline through `points` which ends at `points[idx]`, as a cubic
+/// Bézier curve starting from the current point
+fn add_segment(cairo: &Context, points: &[Point], idx: usize) {
+    let previous = points[idx.saturating_sub(2)];
+    let from = points[idx - 1];
+    let to = points[idx];
+    let next = points[(idx + 1).min(points.len() - 1)];
+
+    let control1 = from + (to - previous) * (1.0 / 6.0);
+    let control2 = to - (next - from) * (1.0 / 6.0);
+    cairo.curve_to(control1.x, control1.y, control2.x, control2.y, to.x, to.y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{simplify, Point};
+
+    fn coordinates(points: &[Point]) -> Vec<(f64, f64)> {
+        points.iter().map(|point| (point.x, point.y)).collect()
+    }
+
+    #[test]
+    fn straight_lines_collapse() {
+        let start = Point { x: 0.0, y: 0.0 };
+        let mut points = (1..=10)
+            .map(|idx| Point {
+                x: idx as f64,
+                y: 2.0 * idx as f64,
+            })
+            .collect();
+        let mut pressures = (0..=10).map(|idx| idx as f64 / 10.0).collect();
+
+        simplify(start, &mut points, &mut pressures);
+        assert_eq!(coordinates(&points), [(10.0, 20.0)]);
+        assert_eq!(pressures, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn small_wobbles_are_dropped() {
+        let start = Point { x: 0.0, y: 0.0 };
+        let mut points = vec![
+            Point { x: 1.0, y: 0.3 },
+            Point { x: 2.0, y: -0.3 },
+            Point { x: 3.0, y: 0.5 },
+            Point { x: 4.0, y: 0.0 },
+        ];
+        let mut pressures = vec![];
+
+        simplify(start, &mut points, &mut pressures);
+        // The first and last points are always kept, and strokes made with a mouse have no
+        // pressures to keep in step
+        assert_eq!(coordinates(&points), [(4.0, 0.0)]);
+        assert!(pressures.is_empty());
+    }
+
+    #[test]
+    fn corners_are_kept() {
+        let start = Point { x: 0.0, y: 0.0 };
+        let mut points = vec![
+            Point { x: 5.0, y: 0.0 },
+            Point { x: 10.0, y: 0.0 },
+            Point { x: 10.0, y: 5.0 },
+            Point { x: 10.0, y: 10.0 },
+        ];
+        let mut pressures = vec![0.1, 0.2, 0.3, 0.4, 0.5];
+
+        simplify(start, &mut points, &mut pressures);
+        assert_eq!(coordinates(&points), [(10.0, 0.0), (10.0, 10.0)]);
+        // The pressures of the dropped points are dropped along with them
+        assert_eq!(pressures, [0.1, 0.3, 0.5]);
+    }
+
+    #[test]
+    fn short_strokes_are_kept() {
+        let start = Point { x: 0.0, y: 0.0 };
+        let mut points = vec![Point { x: 0.1, y: 0.1 }];
+        let mut pressures = vec![0.5, 0.6];
+
+        simplify(start, &mut points, &mut pressures);
+        assert_eq!(coordinates(&points), [(0.1, 0.1)]);
+        assert_eq!(pressures, [0.5, 0.6]);
+    }
+}
@@ -10,8 +10,83 @@ use stackblur_iter::{blur_argb as stackblur, imgref::ImgRefMut};
 use super::Error;
 use crate::editor::utils;
 
-/// How big will pixelate boxes be, in this case, we will group the rectangle into 4x4 boxes, which we will set all of its pixels to the same value
-const PIXELATE_SIZE: u64 = 4;
+/// How big will pixelate boxes be by default, in this case, we will group the rectangle into 4x4 boxes, which we will set all of its pixels to the same value
+pub(super) const PIXELATE_SIZE: u64 = 4;
+/// How far blurs reach by default
+const BLUR_RADIUS: f32 = 5.0;
+
+/// How strong the blurs and pixelations made by the editor are
+#[derive(Clone, Copy, Debug)]
+pub struct EffectStrength {
+    pub blur_radius: f32,
+    /// The size of the boxes regions are pixelated into
+    pub pixelate_size: u64,
+}
+
+impl Default for EffectStrength {
+    fn default() -> Self {
+        Self {
+            blur_radius: BLUR_RADIUS,
+            pixelate_size: PIXELATE_SIZE,
+        }
+    }
+}
+
+pub(super) const fn default_pixelate_size() -> u64 {
+    PIXELATE_SIZE
+}
+
+/// The filters which can be applied to a region by [`super::Tool::Filter`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilterKind {
+    #[default]
+    Grayscale,
+    Invert,
+    BrightnessContrast,
+    Sharpen,
+}
+
+impl FilterKind {
+    /// The filters in the order in which they're shown in the toolbar
+    pub const ALL: [Self; 4] = [
+        Self::Grayscale,
+        Self::Invert,
+        Self::BrightnessContrast,
+        Self::Sharpen,
+    ];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Grayscale => "Grayscale",
+            Self::Invert => "Invert",
+            Self::BrightnessContrast => "Brightness & contrast",
+            Self::Sharpen => "Sharpen",
+        }
+    }
+}
+
+/// Which filter is applied by [`super::Tool::Filter`], and how strongly
+#[derive(Clone, Copy, Debug)]
+pub struct FilterSettings {
+    pub kind: FilterKind,
+    /// How much lighter the region is made, from -1 (black) to 1 (white)
+    pub brightness: f64,
+    /// How much the channels are stretched away from the middle grey, 1 leaves them alone
+    pub contrast: f64,
+    /// How much of the difference between the region and a blurred copy of it is added back
+    pub sharpen_amount: f64,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            kind: FilterKind::Grayscale,
+            brightness: 0.0,
+            contrast: 1.0,
+            sharpen_amount: 1.0,
+        }
+    }
+}
 
 pub fn blur(
     cairo: &Context,
@@ -134,7 +209,9 @@ pub fn pixelate(
     surface: &cairo::Surface,
     rect: &Rectangle,
     seed: u64,
+    size: u64,
 ) -> Result<(), Error> {
+    let size = size.max(1);
     let mut rng = StdRng::seed_from_u64(seed);
 
     let pixbuf = utils::pixbuf_for(surface, *rect).ok_or(Error::Pixbuf(*rect))?;
@@ -146,10 +223,10 @@ pub fn pixelate(
     let bytes_per_pixel = 3 * (pixbuf.bits_per_sample() / 8) as u64;
     let &Rectangle { x, y, w, h } = rect;
 
-    for i in (0..(w as u64)).step_by(PIXELATE_SIZE as usize) {
-        for j in (0..(h as u64)).step_by(PIXELATE_SIZE as usize) {
-            let pixelate_size_x: u64 = PIXELATE_SIZE.min(w as u64 - i);
-            let pixelate_size_y: u64 = PIXELATE_SIZE.min(h as u64 - j);
+    for i in (0..(w as u64)).step_by(size as usize) {
+        for j in (0..(h as u64)).step_by(size as usize) {
+            let pixelate_size_x: u64 = size.min(w as u64 - i);
+            let pixelate_size_y: u64 = size.min(h as u64 - j);
 
             let sample_x: u64 = i + rng.gen_range(0..pixelate_size_x);
             let sample_y: u64 = j + rng.gen_range(0..pixelate_size_y);
@@ -180,3 +257,121 @@ pub fn pixelate(
 
     Ok(())
 }
+
+pub fn grayscale(cairo: &Context, surface: &cairo::Surface, rect: Rectangle) -> Result<(), Error> {
+    filter_region(cairo, surface, rect, |pixels, width, height, stride| {
+        for_each_pixel(pixels, width, height, stride, |pixel| {
+            let (red, green, blue) = (pixel[0] as u32, pixel[1] as u32, pixel[2] as u32);
+            let luma = ((77 * red + 150 * green + 29 * blue) >> 8) as u8;
+            pixel.fill(luma);
+        });
+    })
+}
+
+pub fn invert(cairo: &Context, surface: &cairo::Surface, rect: Rectangle) -> Result<(), Error> {
+    filter_region(cairo, surface, rect, |pixels, width, height, stride| {
+        for_each_pixel(pixels, width, height, stride, |pixel| {
+            for channel in pixel {
+                *channel = 255 - *channel;
+            }
+        });
+    })
+}
+
+pub fn brightness_contrast(
+    cairo: &Context,
+    surface: &cairo::Surface,
+    rect: Rectangle,
+    brightness: f64,
+    contrast: f64,
+) -> Result<(), Error> {
+    filter_region(cairo, surface, rect, |pixels, width, height, stride| {
+        for_each_pixel(pixels, width, height, stride, |pixel| {
+            for channel in pixel {
+                let value = (*channel as f64 - 128.0) * contrast + 128.0 + brightness * 255.0;
+                *channel = value.round().clamp(0.0, 255.0) as u8;
+            }
+        });
+    })
+}
+
+/// Sharpens a region through an unsharp mask, i.e. by exaggerating how much it differs from a
+/// blurred copy of itself
+pub fn sharpen(
+    cairo: &Context,
+    surface: &cairo::Surface,
+    rect: Rectangle,
+    amount: f64,
+) -> Result<(), Error> {
+    /// How far the blurred copy is blurred, which is about the size of the details which are
+    /// sharpened
+    const SHARPEN_RADIUS: usize = 2;
+
+    filter_region(cairo, surface, rect, |pixels, width, height, stride| {
+        let mut copy = pixels.to_vec();
+        copy.resize(copy.len() + (3 - copy.len() % 3) % 3, 0);
+        let (blurred, blurred_stride) = blur_rgb(copy, width, height, stride, SHARPEN_RADIUS);
+        let blurred_stride = blurred_stride as usize;
+
+        for y in 0..height {
+            for x in 0..width * 3 {
+                let original = pixels[y * stride + x] as f64;
+                let blurred = blurred[y * blurred_stride + x] as f64;
+                let value = original + amount * (original - blurred);
+                pixels[y * stride + x] = value.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    })
+}
+
+/// Reads the pixels of `rect` from `surface`, lets `filter` change them in place and paints them
+/// back
+///
+/// The filter gets the pixels as rows of red-green-blue triplets, along with the width, the
+/// height and the stride of the region.
+fn filter_region(
+    cairo: &Context,
+    surface: &cairo::Surface,
+    rect: Rectangle,
+    filter: impl FnOnce(&mut [u8], usize, usize, usize),
+) -> Result<(), Error> {
+    let rect = rect.normalised();
+    if rect.area() < 1.0 {
+        return Ok(());
+    }
+
+    let pixbuf = utils::pixbuf_for(surface, rect).ok_or(Error::Pixbuf(rect))?;
+    assert!(
+        pixbuf.n_channels() == 3 && pixbuf.bits_per_sample() == 8,
+        "Our filters can only handle Pixbufs with 3 channels of 8 bits"
+    );
+
+    let width = pixbuf.width() as usize;
+    let height = pixbuf.height() as usize;
+    let stride = pixbuf.rowstride() as usize;
+    // SAFETY: The pixbuf is newly created so there should be only one reference to the pixel data
+    let pixels = unsafe { pixbuf.pixels() };
+    filter(pixels, width, height, stride);
+
+    cairo.save()?;
+    cairo.set_operator(cairo::Operator::Over);
+    cairo.set_source_pixbuf(&pixbuf, rect.x.floor(), rect.y.floor());
+    cairo.paint()?;
+    cairo.restore()?;
+
+    Ok(())
+}
+
+fn for_each_pixel(
+    pixels: &mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    mut func: impl FnMut(&mut [u8]),
+) {
+    for row in pixels.chunks_mut(stride).take(height) {
+        for pixel in row.chunks_exact_mut(3).take(width) {
+            func(pixel);
+        }
+    }
+}
@@ -0,0 +1,965 @@
+use cairo::Context;
+use gtk4::pango::FontDescription;
+use kcshot_data::{colour::Colour, geometry::*};
+
+use super::{
+    bubble, callout, magnify, opaque, shapes, text_size, Error, MeasureKind, Operation,
+    StampSource, StrokeStyle,
+};
+use crate::editor::utils::CairoExt;
+
+/// How far away from a shape (in pixels) a click can land while still selecting it
+const HIT_TOLERANCE: f64 = 6.0;
+/// The length of the side of the squares used to draw resize handles
+const HANDLE_SIZE: f64 = 8.0;
+
+/// The space between the size label of the crop region and its background
+const LABEL_PADDING: f64 = 3.0;
+
+const SELECTION_COLOUR: Colour = Colour {
+    red: 0,
+    green: 127,
+    blue: 190,
+    alpha: 255,
+};
+const HANDLE_FILL_COLOUR: Colour = Colour {
+    red: 255,
+    green: 255,
+    blue: 255,
+    alpha: 255,
+};
+
+/// Which part of the bounding box a resize handle moves along one of the axes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Start,
+    Middle,
+    End,
+}
+
+/// Something the user can grab in order to change the shape of the selected operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handle {
+    /// One of the eight handles placed on the corners and the edges of the bounding box
+    Resize { horizontal: Side, vertical: Side },
+    /// A handle bound to one of the points returned by [`Operation::control_points`]
+    ControlPoint(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum DragKind {
+    Move,
+    Handle(Handle),
+}
+
+#[derive(Debug)]
+struct Drag {
+    kind: DragKind,
+    /// The selected operation as it was when the drag started, we compute its new state from this
+    /// one and we also put it in the undo stack once the drag is over
+    original: Operation,
+    original_bounds: Rectangle,
+    has_moved: bool,
+}
+
+/// Keeps track of the operation picked with [`super::Tool::Select`]
+#[derive(Debug)]
+pub struct Selection {
+    pub index: usize,
+    drag: Option<Drag>,
+}
+
+impl Selection {
+    pub fn new(index: usize) -> Self {
+        Self { index, drag: None }
+    }
+
+    pub fn start_drag(&mut self, operation: &Operation, handle: Option<Handle>) {
+        let Some(original_bounds) = operation.bounds() else {
+            return;
+        };
+
+        self.drag = Some(Drag {
+            kind: handle.map_or(DragKind::Move, DragKind::Handle),
+            original: operation.clone(),
+            original_bounds,
+            has_moved: false,
+        });
+    }
+
+    /// Updates `operation` according to the current drag, `dx` and `dy` are relative to where
+    /// the drag started
+    pub fn update_drag(&mut self, operation: &mut Operation, dx: f64, dy: f64) {
+        let Some(drag) = self.drag.as_mut() else {
+            return;
+        };
+
+        drag.has_moved |= dx != 0.0 || dy != 0.0;
+        *operation = drag.original.clone();
+        let delta = Point { x: dx, y: dy };
+
+        match drag.kind {
+            DragKind::Move => operation.translate(delta),
+            DragKind::Handle(Handle::ControlPoint(idx)) => {
+                if let Some(&point) = drag.original.control_points().get(idx) {
+                    operation.set_control_point(idx, point + delta);
+                }
+            }
+            DragKind::Handle(Handle::Resize {
+                horizontal,
+                vertical,
+            }) => {
+                let old = drag.original_bounds;
+                let (x, w) = resize_along_axis(old.x, old.w, horizontal, dx);
+                let (y, h) = resize_along_axis(old.y, old.h, vertical, dy);
+
+                operation.map_bounds(old, Rectangle { x, y, w, h });
+            }
+        }
+    }
+
+    /// Ends the current drag, returning the operation as it was before the drag, if the drag
+    /// changed anything
+    pub fn finish_drag(&mut self) -> Option<Operation> {
+        self.drag
+            .take()
+            .filter(|drag| drag.has_moved)
+            .map(|drag| drag.original)
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// The sides of the bounding box moved by the current drag, if it's resizing the operation
+    pub fn resized_sides(&self) -> Option<(Side, Side)> {
+        match self.drag.as_ref()?.kind {
+            DragKind::Handle(Handle::Resize {
+                horizontal,
+                vertical,
+            }) => Some((horizontal, vertical)),
+            DragKind::Move | DragKind::Handle(Handle::ControlPoint(_)) => None,
+        }
+    }
+
+    pub fn draw(&self, cairo: &Context, operation: &Operation) -> Result<(), Error> {
+        let Some(bounds) = operation.bounds() else {
+            return Ok(());
+        };
+
+        cairo.save()?;
+        cairo.set_source_colour(SELECTION_COLOUR);
+        cairo.set_line_width(1.0);
+        cairo.set_dash(&[4.0, 4.0], 0.0);
+        cairo.rectangle(
+            bounds.x - HIT_TOLERANCE / 2.0,
+            bounds.y - HIT_TOLERANCE / 2.0,
+            bounds.w + HIT_TOLERANCE,
+            bounds.h + HIT_TOLERANCE,
+        );
+        cairo.stroke()?;
+        cairo.set_dash(&[], 0.0);
+        draw_handles(cairo, operation)?;
+        cairo.restore()?;
+
+        Ok(())
+    }
+}
+
+/// Draws the handles of the crop region, along with a label showing its size and position
+pub fn draw_crop_adjustment(cairo: &Context, crop: &Operation) -> Result<(), Error> {
+    let Some(Rectangle { x, y, w, h }) = crop.bounds() else {
+        return Ok(());
+    };
+
+    cairo.save()?;
+    cairo.set_line_width(1.0);
+    draw_handles(cairo, crop)?;
+
+    let layout = pangocairo::create_layout(cairo);
+    layout.set_font_description(Some(&FontDescription::from_string("Sans 9")));
+    layout.set_text(&format!(
+        "{} × {} at ({}, {})",
+        w.round(),
+        h.round(),
+        x.round(),
+        y.round()
+    ));
+    let (label_width, label_height) = layout.pixel_size();
+    let (label_width, label_height) = (label_width as f64, label_height as f64);
+
+    // The label goes above the crop region, unless there's no room for it there
+    let label_y = if y - label_height - 2.0 * LABEL_PADDING - HANDLE_SIZE >= 0.0 {
+        y - label_height - 2.0 * LABEL_PADDING - HANDLE_SIZE
+    } else {
+        y + HANDLE_SIZE
+    };
+    let label_x = x + HANDLE_SIZE;
+
+    cairo.set_source_colour(SELECTION_COLOUR);
+    cairo.rectangle(
+        label_x,
+        label_y,
+        label_width + 2.0 * LABEL_PADDING,
+        label_height + 2.0 * LABEL_PADDING,
+    );
+    cairo.fill()?;
+
+    cairo.set_source_colour(HANDLE_FILL_COLOUR);
+    cairo.move_to(label_x + LABEL_PADDING, label_y + LABEL_PADDING);
+    pangocairo::update_layout(cairo, &layout);
+    pangocairo::show_layout(cairo, &layout);
+    cairo.restore()?;
+
+    Ok(())
+}
+
+fn draw_handles(cairo: &Context, operation: &Operation) -> Result<(), Error> {
+    for (_, Point { x, y }) in handles(operation) {
+        cairo.rectangle(
+            x - HANDLE_SIZE / 2.0,
+            y - HANDLE_SIZE / 2.0,
+            HANDLE_SIZE,
+            HANDLE_SIZE,
+        );
+        cairo.set_source_colour(HANDLE_FILL_COLOUR);
+        cairo.fill_preserve()?;
+        cairo.set_source_colour(SELECTION_COLOUR);
+        cairo.stroke()?;
+    }
+
+    Ok(())
+}
+
+/// Returns the handle of `operation` found at `point`, if any
+pub fn handle_at(operation: &Operation, point: Point) -> Option<Handle> {
+    handles(operation)
+        .into_iter()
+        .find(|(_, handle_point)| (*handle_point - point).dist() <= HANDLE_SIZE)
+        .map(|(handle, _)| handle)
+}
+
+fn handles(operation: &Operation) -> Vec<(Handle, Point)> {
+    let mut handles = operation
+        .control_points()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, point)| (Handle::ControlPoint(idx), point))
+        .collect::<Vec<_>>();
+
+    if !operation.is_resizable() {
+        return handles;
+    }
+
+    if let Some(Rectangle { x, y, w, h }) = operation.bounds() {
+        let sides = [Side::Start, Side::Middle, Side::End];
+        let position = |start: f64, length: f64, side: Side| match side {
+            Side::Start => start,
+            Side::Middle => start + length / 2.0,
+            Side::End => start + length,
+        };
+
+        for horizontal in sides {
+            for vertical in sides {
+                if horizontal == Side::Middle && vertical == Side::Middle {
+                    continue;
+                }
+
+                handles.push((
+                    Handle::Resize {
+                        horizontal,
+                        vertical,
+                    },
+                    Point {
+                        x: position(x, w, horizontal),
+                        y: position(y, h, vertical),
+                    },
+                ));
+            }
+        }
+    }
+
+    handles
+}
+
+fn resize_along_axis(start: f64, length: f64, side: Side, delta: f64) -> (f64, f64) {
+    match side {
+        Side::Start => (start + delta, length - delta),
+        Side::Middle => (start, length),
+        Side::End => (start, length + delta),
+    }
+}
+
+impl Operation {
+    /// Returns the smallest rectangle containing everything drawn by this operation
+    pub fn bounds(&self) -> Option<Rectangle> {
+        let rect = match self {
+            Operation::Crop(rect)
+            | Operation::Blur { rect, .. }
+            | Operation::Pixelate { rect, .. }
+            | Operation::DrawRectangle { rect, .. }
+            | Operation::Highlight { rect, .. }
+            | Operation::Spotlight { rect, .. }
+            | Operation::Redact { rect, .. }
+            | Operation::Grayscale { rect }
+            | Operation::Invert { rect }
+            | Operation::BrightnessContrast { rect, .. }
+            | Operation::Sharpen { rect, .. }
+            | Operation::Stamp { rect, .. } => rect.normalised(),
+            // Like with callouts, the handles resize only the magnified region, while the inset is
+            // moved through its control point
+            Operation::Magnify { source, .. } => source.normalised(),
+            Operation::DrawEllipse { ellipse, .. } => {
+                // NOTE: The ellipse is drawn centred on (x + w/2, y + h/2), with a radius of w on the
+                //       x axis and a radius of h on the y axis, see `shapes::draw_ellipse`
+                let Ellipse { x, y, w, h } = *ellipse;
+                Rectangle {
+                    x: x + w / 2.0 - w.abs(),
+                    y: y + h / 2.0 - h.abs(),
+                    w: 2.0 * w.abs(),
+                    h: 2.0 * h.abs(),
+                }
+            }
+            Operation::DrawLine { start, end, .. }
+            | Operation::DrawArrow { start, end, .. }
+            | Operation::Measure { start, end, .. } => bounding_rectangle(&[*start, *end])?,
+            // The curve stays within the triangle made by its ends and its control point
+            Operation::CurvedArrow {
+                start,
+                control,
+                end,
+                ..
+            } => bounding_rectangle(&[*start, *control, *end])?,
+            Operation::Polyline { points, .. } => bounding_rectangle(points)?,
+            Operation::Pencil { start, points, .. }
+            | Operation::Highlighter { start, points, .. } => {
+                let mut all_points = points.clone();
+                all_points.push(*start);
+                bounding_rectangle(&all_points)?
+            }
+            Operation::Text {
+                top_left,
+                text,
+                font_description,
+                style,
+                ..
+            } => {
+                let (w, h) = text_size(text, font_description, None);
+                let padding = style.effective_padding();
+                Rectangle {
+                    x: top_left.x,
+                    y: top_left.y,
+                    w: w + 2.0 * padding,
+                    h: h + 2.0 * padding,
+                }
+            }
+            Operation::Bubble {
+                centre,
+                number,
+                font_description,
+                radius,
+                number_style,
+                auto_size,
+                ..
+            } => {
+                let label = number_style.format(*number);
+                let radius = bubble::radius(*radius, *auto_size, &label, font_description);
+                Rectangle {
+                    x: centre.x - radius,
+                    y: centre.y - radius,
+                    w: 2.0 * radius,
+                    h: 2.0 * radius,
+                }
+            }
+            // The tail is left out, so that the handles resize only the box, while the tail is
+            // moved through its control point
+            Operation::Callout {
+                rect,
+                text,
+                font_description,
+                ..
+            } => callout::text_box(*rect, text, font_description),
+        };
+
+        Some(rect)
+    }
+
+    /// Whether a click at `point` should select this operation
+    pub fn contains(&self, point: Point) -> bool {
+        match self {
+            // Crop regions are not annotations, so they can't be selected
+            Operation::Crop(_) => false,
+            Operation::DrawLine {
+                start,
+                end,
+                line_width,
+                ..
+            }
+            | Operation::DrawArrow {
+                start,
+                end,
+                line_width,
+                ..
+            } => distance_to_segment(point, *start, *end) <= HIT_TOLERANCE + line_width / 2.0,
+            Operation::Measure {
+                start,
+                end,
+                kind: MeasureKind::Distance,
+                ..
+            } => distance_to_segment(point, *start, *end) <= HIT_TOLERANCE,
+            Operation::CurvedArrow {
+                start,
+                control,
+                end,
+                line_width,
+                ..
+            } => {
+                shapes::distance_to_curve(point, *start, *control, *end)
+                    <= HIT_TOLERANCE + line_width / 2.0
+            }
+            Operation::Polyline {
+                points,
+                closed,
+                line_width,
+                ..
+            } => {
+                let on_border = points.iter().zip(points.iter().skip(1)).any(|(a, b)| {
+                    distance_to_segment(point, *a, *b) <= HIT_TOLERANCE + line_width / 2.0
+                });
+                let on_closing_border = *closed
+                    && matches!((points.first(), points.last()), (Some(first), Some(last))
+                        if distance_to_segment(point, *last, *first) <= HIT_TOLERANCE + line_width / 2.0);
+
+                on_border || on_closing_border || (*closed && is_inside_polygon(point, points))
+            }
+            Operation::Pencil {
+                start,
+                points,
+                line_width,
+                ..
+            }
+            | Operation::Highlighter {
+                start,
+                points,
+                line_width,
+                ..
+            } => {
+                // A single click leaves a dot behind, which has no segments
+                if points.is_empty() {
+                    return (point - *start).dist() <= HIT_TOLERANCE + line_width / 2.0;
+                }
+
+                std::iter::once(start)
+                    .chain(points.iter())
+                    .zip(points.iter())
+                    .any(|(a, b)| {
+                        distance_to_segment(point, *a, *b) <= HIT_TOLERANCE + line_width / 2.0
+                    })
+            }
+            Operation::DrawEllipse { .. } => {
+                let Some(Rectangle { x, y, w, h }) = self.bounds() else {
+                    return false;
+                };
+                let a = w / 2.0 + HIT_TOLERANCE;
+                let b = h / 2.0 + HIT_TOLERANCE;
+                let dx = point.x - (x + w / 2.0);
+                let dy = point.y - (y + h / 2.0);
+
+                (dx * dx) / (a * a) + (dy * dy) / (b * b) <= 1.0
+            }
+            Operation::Bubble { centre, .. } => {
+                let Some(bounds) = self.bounds() else {
+                    return false;
+                };
+
+                (*centre - point).dist() <= bounds.w / 2.0 + HIT_TOLERANCE
+            }
+            Operation::Callout {
+                rect,
+                target,
+                text,
+                font_description,
+                ..
+            } => {
+                let Rectangle { x, y, w, h } = callout::text_box(*rect, text, font_description);
+                let on_box = Rectangle {
+                    x: x - HIT_TOLERANCE,
+                    y: y - HIT_TOLERANCE,
+                    w: w + 2.0 * HIT_TOLERANCE,
+                    h: h + 2.0 * HIT_TOLERANCE,
+                }
+                .contains(point);
+
+                on_box || callout::is_on_tail(*rect, *target, point, HIT_TOLERANCE)
+            }
+            Operation::Magnify {
+                source,
+                centre,
+                zoom,
+                shape,
+                ..
+            } => [
+                source.normalised(),
+                magnify::inset_rect(*source, *centre, *zoom, *shape),
+            ]
+            .into_iter()
+            .any(|Rectangle { x, y, w, h }| {
+                Rectangle {
+                    x: x - HIT_TOLERANCE,
+                    y: y - HIT_TOLERANCE,
+                    w: w + 2.0 * HIT_TOLERANCE,
+                    h: h + 2.0 * HIT_TOLERANCE,
+                }
+                .contains(point)
+            }),
+            Operation::Blur { .. }
+            | Operation::Pixelate { .. }
+            | Operation::DrawRectangle { .. }
+            | Operation::Highlight { .. }
+            | Operation::Spotlight { .. }
+            | Operation::Redact { .. }
+            | Operation::Grayscale { .. }
+            | Operation::Invert { .. }
+            | Operation::BrightnessContrast { .. }
+            | Operation::Sharpen { .. }
+            | Operation::Stamp { .. }
+            | Operation::Text { .. }
+            | Operation::Measure {
+                kind: MeasureKind::Size,
+                ..
+            } => {
+                let Some(Rectangle { x, y, w, h }) = self.bounds() else {
+                    return false;
+                };
+
+                Rectangle {
+                    x: x - HIT_TOLERANCE,
+                    y: y - HIT_TOLERANCE,
+                    w: w + 2.0 * HIT_TOLERANCE,
+                    h: h + 2.0 * HIT_TOLERANCE,
+                }
+                .contains(point)
+            }
+        }
+    }
+
+    /// Whether the operation can be resized through the handles placed on its bounding box
+    pub fn is_resizable(&self) -> bool {
+        matches!(
+            self,
+            Operation::Crop(_)
+                | Operation::Blur { .. }
+                | Operation::Pixelate { .. }
+                | Operation::DrawRectangle { .. }
+                | Operation::Highlight { .. }
+                | Operation::Spotlight { .. }
+                | Operation::Redact { .. }
+                | Operation::Grayscale { .. }
+                | Operation::Invert { .. }
+                | Operation::BrightnessContrast { .. }
+                | Operation::Sharpen { .. }
+                | Operation::Stamp { .. }
+                | Operation::DrawEllipse { .. }
+                | Operation::Pencil { .. }
+                | Operation::Highlighter { .. }
+                | Operation::Callout { .. }
+                | Operation::Magnify { .. }
+                | Operation::Polyline { .. }
+        )
+    }
+
+    /// Points which can be dragged individually while the operation is selected
+    pub fn control_points(&self) -> Vec<Point> {
+        match self {
+            Operation::DrawLine { start, end, .. }
+            | Operation::DrawArrow { start, end, .. }
+            | Operation::Measure { start, end, .. } => vec![*start, *end],
+            Operation::CurvedArrow {
+                start,
+                control,
+                end,
+                ..
+            } => vec![*start, *end, *control],
+            Operation::Polyline { points, .. } => points.clone(),
+            Operation::Callout { target, .. } => vec![*target],
+            Operation::Magnify { centre, .. } => vec![*centre],
+            _ => vec![],
+        }
+    }
+
+    pub fn set_control_point(&mut self, idx: usize, point: Point) {
+        match (self, idx) {
+            (
+                Operation::DrawLine { start, .. }
+                | Operation::DrawArrow { start, .. }
+                | Operation::CurvedArrow { start, .. }
+                | Operation::Measure { start, .. },
+                0,
+            ) => *start = point,
+            (Operation::Callout { target, .. }, 0) => *target = point,
+            (Operation::Magnify { centre, .. }, 0) => *centre = point,
+            (
+                Operation::DrawLine { end, .. }
+                | Operation::DrawArrow { end, .. }
+                | Operation::CurvedArrow { end, .. }
+                | Operation::Measure { end, .. },
+                1,
+            ) => *end = point,
+            (Operation::CurvedArrow { control, .. }, 2) => *control = point,
+            (Operation::Polyline { points, .. }, idx) => {
+                if let Some(vertex) = points.get_mut(idx) {
+                    *vertex = point;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn translate(&mut self, delta: Point) {
+        self.map_points(|point| point + delta);
+    }
+
+    /// Stretches the operation so that what used to fill `from` now fills `to`
+    pub fn map_bounds(&mut self, from: Rectangle, to: Rectangle) {
+        let scale = |length: f64, new_length: f64| {
+            if length.abs() < f64::EPSILON {
+                1.0
+            } else {
+                new_length / length
+            }
+        };
+        let scale_x = scale(from.w, to.w);
+        let scale_y = scale(from.h, to.h);
+
+        self.map_points(|Point { x, y }| Point {
+            x: to.x + (x - from.x) * scale_x,
+            y: to.y + (y - from.y) * scale_y,
+        });
+    }
+
+    /// Applies `func` to every point that determines the geometry of the operation
+    fn map_points(&mut self, func: impl Fn(Point) -> Point) {
+        let map_rect = |rect: &mut Rectangle| {
+            let top_left = func(Point {
+                x: rect.x,
+                y: rect.y,
+            });
+            let bottom_right = func(Point {
+                x: rect.x + rect.w,
+                y: rect.y + rect.h,
+            });
+            *rect = Rectangle {
+                x: top_left.x,
+                y: top_left.y,
+                w: bottom_right.x - top_left.x,
+                h: bottom_right.y - top_left.y,
+            };
+        };
+
+        match self {
+            Operation::Crop(rect)
+            | Operation::Blur { rect, .. }
+            | Operation::Pixelate { rect, .. }
+            | Operation::DrawRectangle { rect, .. }
+            | Operation::Highlight { rect, .. }
+            | Operation::Spotlight { rect, .. }
+            | Operation::Redact { rect, .. }
+            | Operation::Grayscale { rect }
+            | Operation::Invert { rect }
+            | Operation::BrightnessContrast { rect, .. }
+            | Operation::Sharpen { rect, .. }
+            | Operation::Stamp { rect, .. }
+            // The tail stays where it points, and the inset where it was put, see
+            // `Operation::bounds`
+            | Operation::Callout { rect, .. }
+            | Operation::Magnify { source: rect, .. } => map_rect(rect),
+            Operation::DrawEllipse { ellipse, .. } => {
+                let mut rect = Rectangle {
+                    x: ellipse.x,
+                    y: ellipse.y,
+                    w: ellipse.w,
+                    h: ellipse.h,
+                };
+                map_rect(&mut rect);
+                *ellipse = Ellipse {
+                    x: rect.x,
+                    y: rect.y,
+                    w: rect.w,
+                    h: rect.h,
+                };
+            }
+            Operation::DrawLine { start, end, .. }
+            | Operation::DrawArrow { start, end, .. }
+            | Operation::Measure { start, end, .. } => {
+                *start = func(*start);
+                *end = func(*end);
+            }
+            Operation::CurvedArrow {
+                start, control, end, ..
+            } => {
+                *start = func(*start);
+                *control = func(*control);
+                *end = func(*end);
+            }
+            Operation::Polyline { points, .. } => {
+                for point in points {
+                    *point = func(*point);
+                }
+            }
+            Operation::Pencil { start, points, .. }
+            | Operation::Highlighter { start, points, .. } => {
+                *start = func(*start);
+                for point in points {
+                    *point = func(*point);
+                }
+            }
+            Operation::Text { top_left, .. } => *top_left = func(*top_left),
+            Operation::Bubble { centre, .. } => *centre = func(*centre),
+        }
+    }
+
+    /// Changes the colour used for filling in shapes, see [`crate::editor::EditorWindow::primary_colour`]
+    pub fn set_primary_colour(&mut self, colour: Colour) {
+        match self {
+            Operation::DrawRectangle { fill, .. }
+            | Operation::DrawEllipse { fill, .. }
+            | Operation::Polyline { fill, .. }
+            | Operation::Callout { fill, .. } => {
+                *fill = colour;
+            }
+            Operation::Bubble { bubble_colour, .. } => *bubble_colour = colour,
+            Operation::Redact {
+                colour: redaction, ..
+            } => *redaction = opaque(colour),
+            _ => {}
+        }
+    }
+
+    /// Changes the colour used for lines and text, see [`crate::editor::EditorWindow::secondary_colour`]
+    pub fn set_secondary_colour(&mut self, new_colour: Colour) {
+        match self {
+            Operation::DrawLine { colour, .. }
+            | Operation::DrawArrow { colour, .. }
+            | Operation::CurvedArrow { colour, .. }
+            | Operation::Pencil { colour, .. }
+            | Operation::Measure { colour, .. }
+            | Operation::Text { colour, .. }
+            | Operation::DrawRectangle { border: colour, .. }
+            | Operation::DrawEllipse { border: colour, .. }
+            | Operation::Polyline { border: colour, .. }
+            | Operation::Callout { border: colour, .. }
+            | Operation::Magnify { border: colour, .. }
+            | Operation::Bubble {
+                text_colour: colour,
+                ..
+            } => *colour = new_colour,
+            _ => {}
+        }
+    }
+
+    /// Changes the colour of highlights, see [`super::ToolOptions::highlight_colour`]
+    pub fn set_highlight_colour(&mut self, new_colour: Colour) {
+        match self {
+            Operation::Highlight { colour, .. } | Operation::Highlighter { colour, .. } => {
+                *colour = new_colour;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn set_blur_radius(&mut self, new_radius: f32) {
+        if let Operation::Blur { radius, .. } = self {
+            *radius = new_radius;
+        }
+    }
+
+    pub fn set_pixelate_size(&mut self, new_size: u64) {
+        if let Operation::Pixelate { size, .. } = self {
+            *size = new_size;
+        }
+    }
+
+    pub fn set_line_width(&mut self, new_line_width: f64) {
+        match self {
+            Operation::DrawLine { line_width, .. }
+            | Operation::DrawArrow { line_width, .. }
+            | Operation::CurvedArrow { line_width, .. }
+            | Operation::Polyline { line_width, .. }
+            | Operation::Pencil { line_width, .. }
+            | Operation::Highlighter { line_width, .. }
+            | Operation::DrawRectangle { line_width, .. }
+            | Operation::DrawEllipse { line_width, .. }
+            | Operation::Callout { line_width, .. }
+            | Operation::Magnify { line_width, .. } => *line_width = new_line_width,
+            _ => {}
+        }
+    }
+
+    /// Changes how the lines are drawn, see [`super::ToolOptions::stroke`]
+    pub fn edit_stroke_style(&mut self, edit: impl FnOnce(&mut StrokeStyle)) {
+        match self {
+            Operation::DrawLine { stroke, .. }
+            | Operation::DrawRectangle { stroke, .. }
+            | Operation::DrawArrow { stroke, .. }
+            | Operation::DrawEllipse { stroke, .. }
+            | Operation::Pencil { stroke, .. }
+            | Operation::CurvedArrow { stroke, .. }
+            | Operation::Polyline { stroke, .. } => edit(stroke),
+            _ => {}
+        }
+    }
+
+    pub fn set_stamp_source(&mut self, new_source: &StampSource) {
+        if let Operation::Stamp { source, .. } = self {
+            *source = new_source.clone();
+        }
+    }
+}
+
+fn bounding_rectangle(points: &[Point]) -> Option<Rectangle> {
+    let first = points.first()?;
+    let (mut min, mut max) = (*first, *first);
+
+    for point in points {
+        min.x = min.x.min(point.x);
+        min.y = min.y.min(point.y);
+        max.x = max.x.max(point.x);
+        max.y = max.y.max(point.y);
+    }
+
+    Some(Rectangle {
+        x: min.x,
+        y: min.y,
+        w: max.x - min.x,
+        h: max.y - min.y,
+    })
+}
+
+pub(super) fn distance_to_segment(point: Point, start: Point, end: Point) -> f64 {
+    let segment = end - start;
+    let length_squared = segment.x * segment.x + segment.y * segment.y;
+    if length_squared < f64::EPSILON {
+        return (point - start).dist();
+    }
+
+    let to_point = point - start;
+    let t = ((to_point.x * segment.x + to_point.y * segment.y) / length_squared).clamp(0.0, 1.0);
+
+    (point - (start + segment * t)).dist()
+}
+
+/// Returns whether `point` is inside the polygon with the vertices `points`, by counting how many
+/// of its sides are crossed going right from `point`
+fn is_inside_polygon(point: Point, points: &[Point]) -> bool {
+    let Some(last) = points.last() else {
+        return false;
+    };
+
+    let mut inside = false;
+    let mut previous = *last;
+    for current in points {
+        let crosses_row = (current.y > point.y) != (previous.y > point.y);
+        if crosses_row {
+            let crossing_x = current.x
+                + (point.y - current.y) * (previous.x - current.x) / (previous.y - current.y);
+            if point.x < crossing_x {
+                inside = !inside;
+            }
+        }
+        previous = *current;
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_inside_polygon, Colour, Operation, Point, StrokeStyle};
+
+    fn points(coordinates: &[(f64, f64)]) -> Vec<Point> {
+        coordinates.iter().map(|&point| point.into()).collect()
+    }
+
+    fn polyline(coordinates: &[(f64, f64)], closed: bool) -> Operation {
+        Operation::Polyline {
+            points: points(coordinates),
+            closed,
+            border: Colour::BLACK,
+            fill: Colour::BLACK,
+            line_width: 2.0,
+            stroke: StrokeStyle::default(),
+        }
+    }
+
+    #[test]
+    fn inside_polygon() {
+        let square = points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        assert!(is_inside_polygon((5.0, 5.0).into(), &square));
+        assert!(!is_inside_polygon((15.0, 5.0).into(), &square));
+        assert!(!is_inside_polygon((5.0, -1.0).into(), &square));
+
+        // The direction in which the vertices go around doesn't matter
+        let reversed = square.iter().rev().copied().collect::<Vec<_>>();
+        assert!(is_inside_polygon((5.0, 5.0).into(), &reversed));
+
+        // A U with its opening at the bottom
+        let concave = points(&[
+            (0.0, 0.0),
+            (30.0, 0.0),
+            (30.0, 30.0),
+            (20.0, 30.0),
+            (20.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 30.0),
+            (0.0, 30.0),
+        ]);
+        assert!(is_inside_polygon((5.0, 20.0).into(), &concave));
+        assert!(is_inside_polygon((25.0, 20.0).into(), &concave));
+        assert!(is_inside_polygon((15.0, 5.0).into(), &concave));
+        assert!(!is_inside_polygon((15.0, 20.0).into(), &concave));
+        assert!(!is_inside_polygon((35.0, 20.0).into(), &concave));
+
+        assert!(!is_inside_polygon((0.0, 0.0).into(), &[]));
+        assert!(!is_inside_polygon(
+            (1.0, 1.0).into(),
+            &points(&[(1.0, 1.0)])
+        ));
+    }
+
+    #[test]
+    fn polyline_hit_testing() {
+        let corners = [(0.0, 0.0), (40.0, 0.0), (40.0, 40.0)];
+        let open = polyline(&corners, false);
+        let closed = polyline(&corners, true);
+
+        // On one of the drawn sides
+        assert!(open.contains((20.0, 0.0).into()));
+        assert!(closed.contains((40.0, 20.0).into()));
+
+        // On the side from the last point back to the first one, which is only drawn when closed
+        assert!(!open.contains((20.0, 20.0).into()));
+        assert!(closed.contains((20.0, 20.0).into()));
+        assert!(closed.contains((20.0, 25.0).into()));
+        assert!(!closed.contains((20.0, 35.0).into()));
+
+        // Inside, which only counts for closed polygons
+        assert!(!open.contains((30.0, 10.0).into()));
+        assert!(closed.contains((30.0, 10.0).into()));
+
+        assert!(!closed.contains((60.0, 60.0).into()));
+    }
+
+    #[test]
+    fn single_click_strokes() {
+        let dot = Operation::Highlighter {
+            start: (10.0, 10.0).into(),
+            points: vec![],
+            colour: Colour::BLACK,
+            line_width: 8.0,
+        };
+
+        assert!(dot.contains((10.0, 10.0).into()));
+        assert!(dot.contains((18.0, 10.0).into()));
+        assert!(!dot.contains((25.0, 10.0).into()));
+    }
+}
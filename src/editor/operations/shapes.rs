@@ -2,25 +2,61 @@ use std::f64::consts::PI;
 
 use cairo::Context;
 use kcshot_data::{colour::Colour, geometry::*};
+use serde::{Deserialize, Serialize};
 
-use super::Error;
+use super::{selection::distance_to_segment, Error};
 use crate::editor::utils::CairoExt;
 
 /// The length of the arrowhead will be 1/10th of the length of the body
 const ARROWHEAD_LENGTH_RATIO: f64 = 0.1;
 /// How open/closed the arrowhead will be
 const ARROWHEAD_APERTURE: f64 = PI / 6.0;
+/// How far the control point of a new curved arrow is from its middle, relative to its length
+const CURVE_BULGE: f64 = 0.25;
+/// How many straight segments curves are split into when checking whether they were clicked
+const CURVE_SEGMENTS: usize = 16;
 
+/// How the ends of arrows look
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArrowheadStyle {
+    /// Two lines meeting at the tip
+    #[default]
+    Open,
+    /// A filled triangle
+    Filled,
+    /// No arrowhead, which makes arrows plain lines
+    None,
+}
+
+impl ArrowheadStyle {
+    /// The styles in the order in which they're shown in the toolbar
+    pub const ALL: [Self; 3] = [Self::Open, Self::Filled, Self::None];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Open => "Open",
+            Self::Filled => "Filled",
+            Self::None => "None",
+        }
+    }
+}
+
+/// Draws `rect` with its corners rounded to `corner_radius`, which can be 0 for sharp corners
 pub fn draw_rectangle(
     cairo: &Context,
     rect: &Rectangle,
     border: Colour,
     fill: Colour,
     line_width: f64,
+    corner_radius: f64,
 ) -> Result<(), Error> {
     cairo.save()?;
-    let Rectangle { x, y, w, h } = rect.normalised();
-    cairo.rectangle(x, y, w, h);
+    if corner_radius > 0.0 {
+        rounded_rectangle(cairo, *rect, corner_radius);
+    } else {
+        let Rectangle { x, y, w, h } = rect.normalised();
+        cairo.rectangle(x, y, w, h);
+    }
 
     cairo.set_source_colour(fill);
     cairo.fill_preserve()?;
@@ -33,6 +69,20 @@ pub fn draw_rectangle(
     Ok(())
 }
 
+/// Adds the path of `rect` with its corners rounded to `cairo`, the radius is clamped so that the
+/// corners fit
+pub fn rounded_rectangle(cairo: &Context, rect: Rectangle, radius: f64) {
+    let Rectangle { x, y, w, h } = rect.normalised();
+    let radius = radius.clamp(0.0, w.min(h) / 2.0);
+
+    cairo.new_sub_path();
+    cairo.arc(x + w - radius, y + radius, radius, -PI / 2.0, 0.0);
+    cairo.arc(x + w - radius, y + h - radius, radius, 0.0, PI / 2.0);
+    cairo.arc(x + radius, y + h - radius, radius, PI / 2.0, PI);
+    cairo.arc(x + radius, y + radius, radius, PI, 3.0 * PI / 2.0);
+    cairo.close_path();
+}
+
 pub fn dimmen_rectangle_around(
     cairo: &Context,
     containing_rectangle: Rectangle,
@@ -99,6 +149,22 @@ pub fn draw_ellipse(
     Ok(())
 }
 
+/// Fills a circle of `radius` around `centre`, unlike [`draw_ellipse`], which draws ellipses
+/// twice as big as the box they're given
+pub fn fill_circle(
+    cairo: &Context,
+    centre: Point,
+    radius: f64,
+    colour: Colour,
+) -> Result<(), Error> {
+    cairo.new_sub_path();
+    cairo.arc(centre.x, centre.y, radius, 0.0, 2.0 * PI);
+    cairo.set_source_colour(colour);
+    cairo.fill()?;
+
+    Ok(())
+}
+
 pub fn draw_line(
     cairo: &Context,
     Point { x: x1, y: y1 }: Point,
@@ -115,35 +181,181 @@ pub fn draw_line(
     Ok(())
 }
 
+/// Draws an arrow going straight from `start` to `end`, which has a head at `start` as well when
+/// `double_headed` is set
 pub fn draw_arrow(
     cairo: &Context,
     start: Point,
     end: Point,
     colour: Colour,
     line_width: f64,
+    head: ArrowheadStyle,
+    double_headed: bool,
 ) -> Result<(), Error> {
-    let angle = get_line_angle(start, end);
-    let length = (end - start).dist();
-    let arrow_length = length * ARROWHEAD_LENGTH_RATIO;
+    draw_curved_arrow(
+        cairo,
+        start,
+        None,
+        end,
+        colour,
+        line_width,
+        head,
+        double_headed,
+    )
+}
 
-    cairo.move_to(start.x, start.y);
-    cairo.line_to(end.x, end.y);
+/// Draws an arrow from `start` to `end` which bends towards `control`, as a quadratic Bézier curve,
+/// or goes straight when there's no `control`
+#[allow(clippy::too_many_arguments)]
+pub fn draw_curved_arrow(
+    cairo: &Context,
+    start: Point,
+    control: Option<Point>,
+    end: Point,
+    colour: Colour,
+    line_width: f64,
+    head: ArrowheadStyle,
+    double_headed: bool,
+) -> Result<(), Error> {
+    let arrow_length = (end - start).dist() * ARROWHEAD_LENGTH_RATIO;
+    // The heads point along the curve where it ends
+    let end_angle = get_line_angle(control.unwrap_or(start), end);
+    let start_angle = get_line_angle(control.unwrap_or(end), start);
 
-    // Since cos(theta) = adjacent / hypotenuse, x1 = arrow_length * cos(theta)
-    let x1 = -arrow_length * (angle - ARROWHEAD_APERTURE).cos();
-    let x2 = -arrow_length * (angle + ARROWHEAD_APERTURE).cos();
+    // Filled heads cover the end of the body, which would otherwise poke out of their sides when
+    // it's thick
+    let inset = match head {
+        ArrowheadStyle::Filled => arrow_length * ARROWHEAD_APERTURE.cos(),
+        ArrowheadStyle::Open | ArrowheadStyle::None => 0.0,
+    };
+    let body_end = end - direction(end_angle) * inset;
+    let body_start = if double_headed {
+        start - direction(start_angle) * inset
+    } else {
+        start
+    };
 
-    // Since sin(theta) = opposite / hypotenuse, y1 = arrow_length * sin(theta)
-    let y1 = -arrow_length * (angle - ARROWHEAD_APERTURE).sin();
-    let y2 = -arrow_length * (angle + ARROWHEAD_APERTURE).sin();
+    cairo.save()?;
+    cairo.set_source_colour(colour);
+    cairo.set_line_width(line_width);
 
-    cairo.rel_move_to(x1, y1);
-    cairo.line_to(end.x, end.y);
-    cairo.rel_line_to(x2, y2);
+    cairo.move_to(body_start.x, body_start.y);
+    match control {
+        Some(control) => {
+            // Cairo only has cubic curves, which can make quadratic ones with these control points
+            let control1 = body_start + (control - body_start) * (2.0 / 3.0);
+            let control2 = body_end + (control - body_end) * (2.0 / 3.0);
+            cairo.curve_to(
+                control1.x, control1.y, control2.x, control2.y, body_end.x, body_end.y,
+            );
+        }
+        None => cairo.line_to(body_end.x, body_end.y),
+    }
+    cairo.stroke()?;
 
-    cairo.set_source_colour(colour);
+    // Only the body is dashed, broken up heads would be hard to make out
+    cairo.set_dash(&[], 0.0);
+    draw_arrowhead(cairo, end, end_angle, arrow_length, head)?;
+    if double_headed {
+        draw_arrowhead(cairo, start, start_angle, arrow_length, head)?;
+    }
+    cairo.restore()?;
+
+    Ok(())
+}
+
+/// Draws an arrowhead with its tip at `tip` pointing towards `angle`
+fn draw_arrowhead(
+    cairo: &Context,
+    tip: Point,
+    angle: f64,
+    arrow_length: f64,
+    head: ArrowheadStyle,
+) -> Result<(), Error> {
+    // Since cos(theta) = adjacent / hypotenuse and sin(theta) = opposite / hypotenuse, the sides
+    // of the arrowhead end at arrow_length * (cos(theta), sin(theta)) away from the tip
+    let side1 = tip - direction(angle - ARROWHEAD_APERTURE) * arrow_length;
+    let side2 = tip - direction(angle + ARROWHEAD_APERTURE) * arrow_length;
+
+    match head {
+        ArrowheadStyle::Open => {
+            cairo.move_to(side1.x, side1.y);
+            cairo.line_to(tip.x, tip.y);
+            cairo.line_to(side2.x, side2.y);
+            cairo.stroke()?;
+        }
+        ArrowheadStyle::Filled => {
+            cairo.move_to(side1.x, side1.y);
+            cairo.line_to(tip.x, tip.y);
+            cairo.line_to(side2.x, side2.y);
+            cairo.close_path();
+            cairo.fill()?;
+        }
+        ArrowheadStyle::None => {}
+    }
+
+    Ok(())
+}
+
+/// Returns where the control point of a curved arrow from `start` to `end` is placed at first,
+/// which is off to the side of its middle, so that the arrow is visibly curved
+pub fn default_curve_control(start: Point, end: Point) -> Point {
+    let middle = (start + end) * 0.5;
+    let Point { x, y } = end - start;
+
+    // This is the arrow turned by 90°, so the curve bulges out to its left
+    middle
+        + Point {
+            x: y * CURVE_BULGE,
+            y: -x * CURVE_BULGE,
+        }
+}
+
+/// Returns the distance between `point` and the quadratic Bézier curve from `start` to `end` which
+/// bends towards `control`, measured against straight segments that approximate the curve
+pub fn distance_to_curve(point: Point, start: Point, control: Point, end: Point) -> f64 {
+    let at =
+        |t: f64| start * ((1.0 - t) * (1.0 - t)) + control * (2.0 * t * (1.0 - t)) + end * (t * t);
+
+    (0..CURVE_SEGMENTS)
+        .map(|idx| {
+            let from = at(idx as f64 / CURVE_SEGMENTS as f64);
+            let to = at((idx + 1) as f64 / CURVE_SEGMENTS as f64);
+            distance_to_segment(point, from, to)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Draws the lines between `points`, going back to the first one and filling the shape in when
+/// `closed` is set
+pub fn draw_polyline(
+    cairo: &Context,
+    points: &[Point],
+    closed: bool,
+    border: Colour,
+    fill: Colour,
+    line_width: f64,
+) -> Result<(), Error> {
+    let Some((first, rest)) = points.split_first() else {
+        return Ok(());
+    };
+
+    cairo.save()?;
+    cairo.move_to(first.x, first.y);
+    for point in rest {
+        cairo.line_to(point.x, point.y);
+    }
+
+    if closed {
+        cairo.close_path();
+        cairo.set_source_colour(fill);
+        cairo.fill_preserve()?;
+    }
+
+    cairo.set_source_colour(border);
     cairo.set_line_width(line_width);
     cairo.stroke()?;
+    cairo.restore()?;
 
     Ok(())
 }
@@ -152,3 +364,11 @@ fn get_line_angle(start: Point, end: Point) -> f64 {
     let Point { x, y } = end - start;
     y.atan2(x)
 }
+
+/// Returns the point at distance 1 from the origin in the direction of `angle`
+fn direction(angle: f64) -> Point {
+    Point {
+        x: angle.cos(),
+        y: angle.sin(),
+    }
+}
@@ -0,0 +1,389 @@
+use std::{fmt, ops::RangeInclusive};
+
+use cairo::{Context, ImageSurface};
+use kcshot_data::{
+    colour::Colour,
+    geometry::{Point, Rectangle},
+};
+use kcshot_screenshot::Window;
+
+use crate::editor::utils::CairoExt;
+
+/// How close (in pixels of the screenshot) an edge has to be for it to be snapped to
+const SNAP_DISTANCE: f64 = 8.0;
+/// How different the brightness of two neighbouring pixels has to be for an edge to pass between
+/// them
+const EDGE_CONTRAST: u8 = 24;
+/// The fraction of the pixels along an edge which have to differ from their neighbours for it to
+/// be snapped to
+const MIN_EDGE_COVERAGE: f64 = 0.6;
+/// How many pixels are looked at on each side of a point when it isn't part of a region yet
+const MIN_EDGE_LENGTH: f64 = 16.0;
+const GUIDE_COLOUR: Colour = Colour {
+    red: 255,
+    green: 0,
+    blue: 255,
+    alpha: 191,
+};
+
+/// The brightness of every pixel of the screenshot, this is what edges are detected in
+pub struct EdgeMap {
+    luma: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl fmt::Debug for EdgeMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EdgeMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EdgeMap {
+    pub fn new(surface: &ImageSurface) -> Self {
+        let width = surface.width() as usize;
+        let height = surface.height() as usize;
+        let stride = surface.stride() as usize;
+
+        let mut luma = Vec::with_capacity(width * height);
+        let result = surface.with_data(|data| {
+            for row in data.chunks(stride).take(height) {
+                // NOTE: The pixels are stored as blue-green-red-unused, see `Image::get_colour_at`
+                luma.extend(row.chunks_exact(4).take(width).map(|pixel| {
+                    let (blue, green, red) = (pixel[0] as u32, pixel[1] as u32, pixel[2] as u32);
+                    ((77 * red + 150 * green + 29 * blue) >> 8) as u8
+                }));
+            }
+        });
+
+        if let Err(why) = result {
+            tracing::warn!("Failed to read the screenshot, edges won't be snapped to: {why}");
+            return Self {
+                luma: vec![],
+                width: 0,
+                height: 0,
+            };
+        }
+
+        Self {
+            luma,
+            width,
+            height,
+        }
+    }
+
+    fn at(&self, x: usize, y: usize) -> u8 {
+        self.luma[y * self.width + x]
+    }
+
+    /// Returns the fraction of `rows` along which there's an edge between the columns `x - 1` and
+    /// `x`
+    fn vertical_edge_coverage(&self, x: usize, rows: RangeInclusive<usize>) -> f64 {
+        if x == 0 || x >= self.width || rows.is_empty() {
+            return 0.0;
+        }
+
+        let length = rows.clone().count();
+        let edges = rows
+            .filter(|&y| self.at(x - 1, y).abs_diff(self.at(x, y)) >= EDGE_CONTRAST)
+            .count();
+
+        edges as f64 / length as f64
+    }
+
+    /// Returns the fraction of `columns` along which there's an edge between the rows `y - 1` and
+    /// `y`
+    fn horizontal_edge_coverage(&self, y: usize, columns: RangeInclusive<usize>) -> f64 {
+        if y == 0 || y >= self.height || columns.is_empty() {
+            return 0.0;
+        }
+
+        let length = columns.clone().count();
+        let edges = columns
+            .filter(|&x| self.at(x, y - 1).abs_diff(self.at(x, y)) >= EDGE_CONTRAST)
+            .count();
+
+        edges as f64 / length as f64
+    }
+
+    /// Returns the fraction of horizontally neighbouring pixels in `rect` which have an edge between
+    /// them, text has a lot more of these than most other things
+    pub fn edge_density(&self, rect: Rectangle) -> f64 {
+        let Rectangle { x, y, w, h } = rect.normalised();
+        let clamp = |value: f64, limit: usize| value.clamp(0.0, limit as f64) as usize;
+        let (left, right) = (clamp(x, self.width), clamp(x + w, self.width));
+        let (top, bottom) = (clamp(y, self.height), clamp(y + h, self.height));
+        if right - left < 2 || bottom <= top {
+            return 0.0;
+        }
+
+        let edges = (top..bottom)
+            .flat_map(|y| (left + 1..right).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.at(x - 1, y).abs_diff(self.at(x, y)) >= EDGE_CONTRAST)
+            .count();
+
+        edges as f64 / ((right - left - 1) * (bottom - top)) as f64
+    }
+
+    /// Returns the columns near `point` which have an edge running along the region between
+    /// `anchor` and `point`
+    fn vertical_edges_near(&self, point: Point, anchor: Point) -> impl Iterator<Item = f64> + '_ {
+        let spans = spans(point.y, anchor.y, self.height);
+        pixels_near(point.x, self.width)
+            .filter(move |&x| {
+                spans
+                    .iter()
+                    .any(|rows| self.vertical_edge_coverage(x, rows.clone()) >= MIN_EDGE_COVERAGE)
+            })
+            .map(|x| x as f64)
+    }
+
+    /// Returns the rows near `point` which have an edge running along the region between `anchor`
+    /// and `point`
+    fn horizontal_edges_near(&self, point: Point, anchor: Point) -> impl Iterator<Item = f64> + '_ {
+        let spans = spans(point.x, anchor.x, self.width);
+        pixels_near(point.y, self.height)
+            .filter(move |&y| {
+                spans.iter().any(|columns| {
+                    self.horizontal_edge_coverage(y, columns.clone()) >= MIN_EDGE_COVERAGE
+                })
+            })
+            .map(|y| y as f64)
+    }
+}
+
+/// The result of snapping a point, along with the edges it was snapped to
+#[derive(Clone, Copy, Debug)]
+pub struct Snap {
+    pub point: Point,
+    pub vertical_edge: Option<f64>,
+    pub horizontal_edge: Option<f64>,
+}
+
+/// Moves `point` onto the nearest edges of windows or edges detected in the screenshot
+///
+/// `anchor` is the opposite corner of the region being drawn, edges in the screenshot have to run
+/// along the side of that region to be snapped to. When it's the same as `point`, edges only have
+/// to go past it.
+pub fn snap(point: Point, anchor: Point, windows: &[Window], edges: &EdgeMap) -> Snap {
+    let rects = windows
+        .iter()
+        .flat_map(|window| [window.outer_rect, window.content_rect])
+        .map(|rect| rect.normalised());
+
+    let vertical_edges = rects
+        .clone()
+        .filter(|rect| is_within(point.y, rect.y, rect.y + rect.h))
+        .flat_map(|rect| [rect.x, rect.x + rect.w])
+        .chain(edges.vertical_edges_near(point, anchor));
+    let horizontal_edges = rects
+        .filter(|rect| is_within(point.x, rect.x, rect.x + rect.w))
+        .flat_map(|rect| [rect.y, rect.y + rect.h])
+        .chain(edges.horizontal_edges_near(point, anchor));
+
+    let vertical_edge = nearest(point.x, vertical_edges);
+    let horizontal_edge = nearest(point.y, horizontal_edges);
+
+    Snap {
+        point: Point {
+            x: vertical_edge.unwrap_or(point.x),
+            y: horizontal_edge.unwrap_or(point.y),
+        },
+        vertical_edge,
+        horizontal_edge,
+    }
+}
+
+/// Draws lines across the screen along the edges which were snapped to
+pub fn draw_guides(cairo: &Context, snap: &Snap, screen: Rectangle) -> Result<(), cairo::Error> {
+    if snap.vertical_edge.is_none() && snap.horizontal_edge.is_none() {
+        return Ok(());
+    }
+
+    cairo.save()?;
+    cairo.set_source_colour(GUIDE_COLOUR);
+    cairo.set_line_width(1.0);
+    cairo.set_dash(&[4.0, 4.0], 0.0);
+
+    if let Some(x) = snap.vertical_edge {
+        cairo.move_to(x, screen.y);
+        cairo.line_to(x, screen.y + screen.h);
+    }
+    if let Some(y) = snap.horizontal_edge {
+        cairo.move_to(screen.x, y);
+        cairo.line_to(screen.x + screen.w, y);
+    }
+
+    cairo.stroke()?;
+    cairo.restore()?;
+
+    Ok(())
+}
+
+fn is_within(value: f64, start: f64, end: f64) -> bool {
+    value >= start - SNAP_DISTANCE && value <= end + SNAP_DISTANCE
+}
+
+fn nearest(value: f64, edges: impl Iterator<Item = f64>) -> Option<f64> {
+    edges
+        .filter(|edge| (edge - value).abs() <= SNAP_DISTANCE)
+        .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+}
+
+/// Returns the pixels which are close enough to `value` to be snapped to
+fn pixels_near(value: f64, limit: usize) -> RangeInclusive<usize> {
+    let clamp = |value: f64| value.clamp(0.0, limit as f64) as usize;
+
+    clamp((value - SNAP_DISTANCE).ceil())..=clamp((value + SNAP_DISTANCE).floor())
+}
+
+/// Returns the spans of pixels along which edges are looked for, which is either the side of the
+/// region between `from` and `to`, or the pixels before and after `from` if there's no region yet
+fn spans(from: f64, to: f64, limit: usize) -> Vec<RangeInclusive<usize>> {
+    let clamp = |value: f64| value.clamp(0.0, limit.saturating_sub(1) as f64) as usize;
+
+    if (to - from).abs() < MIN_EDGE_LENGTH {
+        vec![
+            clamp(from - MIN_EDGE_LENGTH)..=clamp(from - 1.0),
+            clamp(from)..=clamp(from + MIN_EDGE_LENGTH - 1.0),
+        ]
+    } else {
+        vec![clamp(from.min(to))..=clamp(from.max(to) - 1.0)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cairo::{Context, Format, ImageSurface};
+    use kcshot_data::geometry::{Point, Rectangle};
+    use kcshot_screenshot::Window;
+
+    use super::{pixels_near, snap, spans, EdgeMap};
+
+    /// A black 64x64 screenshot with a square of the given brightness from (20, 20) to (44, 44)
+    fn screenshot_with_square(brightness: f64) -> ImageSurface {
+        let surface = ImageSurface::create(Format::Rgb24, 64, 64).unwrap();
+        let cairo = Context::new(&surface).unwrap();
+        cairo.set_source_rgb(0.0, 0.0, 0.0);
+        cairo.paint().unwrap();
+        cairo.set_source_rgb(brightness, brightness, brightness);
+        cairo.rectangle(20.0, 20.0, 24.0, 24.0);
+        cairo.fill().unwrap();
+        drop(cairo);
+
+        surface
+    }
+
+    fn snapped(
+        point: (f64, f64),
+        anchor: (f64, f64),
+        windows: &[Window],
+        edges: &EdgeMap,
+    ) -> (f64, f64) {
+        let snap = snap(point.into(), anchor.into(), windows, edges);
+        (snap.point.x, snap.point.y)
+    }
+
+    #[test]
+    fn edges_in_the_screenshot() {
+        let edges = EdgeMap::new(&screenshot_with_square(1.0));
+
+        // With no region yet, edges only have to go past the point
+        assert_eq!(
+            snapped((22.0, 32.0), (22.0, 32.0), &[], &edges),
+            (20.0, 32.0)
+        );
+        // Otherwise they have to run along the side of the region
+        assert_eq!(
+            snapped((43.0, 42.0), (10.0, 10.0), &[], &edges),
+            (44.0, 44.0)
+        );
+        // Edges further away than the snapping distance are left alone
+        assert_eq!(
+            snapped((32.0, 32.0), (32.0, 32.0), &[], &edges),
+            (32.0, 32.0)
+        );
+
+        // Slight changes in brightness aren't edges
+        let faint = EdgeMap::new(&screenshot_with_square(0.05));
+        assert_eq!(
+            snapped((22.0, 32.0), (22.0, 32.0), &[], &faint),
+            (22.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn window_edges() {
+        let edges = EdgeMap::new(&screenshot_with_square(1.0));
+        let window = Window {
+            outer_rect: Rectangle {
+                x: 5.0,
+                y: 5.0,
+                w: 50.0,
+                h: 50.0,
+            },
+            content_rect: Rectangle {
+                x: 6.0,
+                y: 15.0,
+                w: 48.0,
+                h: 39.0,
+            },
+        };
+
+        // Windows are snapped to even where nothing can be seen in the screenshot, and the content
+        // rectangle counts as well as the outer one
+        assert_eq!(
+            snapped((7.5, 30.0), (7.5, 30.0), &[window], &edges),
+            (6.0, 30.0)
+        );
+
+        // Windows win over edges in the screenshot which are just as close
+        let window = Window {
+            outer_rect: Rectangle {
+                x: 21.0,
+                y: 0.0,
+                w: 30.0,
+                h: 64.0,
+            },
+            content_rect: Rectangle {
+                x: 21.0,
+                y: 0.0,
+                w: 30.0,
+                h: 64.0,
+            },
+        };
+        assert_eq!(
+            snapped((20.5, 32.0), (20.5, 32.0), &[window], &edges),
+            (21.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn image_border() {
+        assert_eq!(pixels_near(3.0, 64), 0..=11);
+        // Columns at the width of the image are looked at, but never have edges
+        assert_eq!(pixels_near(62.0, 64), 54..=64);
+
+        assert_eq!(spans(2.0, 2.0, 64), [0..=1, 2..=17]);
+        assert_eq!(spans(60.0, 10.0, 64), [10..=59]);
+        assert_eq!(spans(5.0, 5.0, 0), [0..=0, 0..=0]);
+
+        let edges = EdgeMap::new(&screenshot_with_square(1.0));
+        assert_eq!(
+            snapped((63.5, 63.5), (63.5, 63.5), &[], &edges),
+            (63.5, 63.5)
+        );
+        assert_eq!(snapped((0.0, 0.0), (50.0, 50.0), &[], &edges), (0.0, 0.0));
+
+        // Screenshots which couldn't be read have no edges at all
+        let empty = EdgeMap {
+            luma: vec![],
+            width: 0,
+            height: 0,
+        };
+        assert_eq!(snapped((5.0, 5.0), (5.0, 5.0), &[], &empty), (5.0, 5.0));
+    }
+}
@@ -0,0 +1,107 @@
+use cairo::{Context, ImageSurface};
+use kcshot_data::{colour::Colour, geometry::Rectangle};
+use serde::{Deserialize, Serialize};
+
+use super::{pixelops, Error};
+use crate::editor::utils::CairoExt;
+
+const DIM_COLOUR: Colour = Colour {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 160,
+};
+const BLUR_RADIUS: usize = 8;
+
+/// The shape of the region which is left alone by a spotlight
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpotlightShape {
+    #[default]
+    Rectangle,
+    /// The ellipse inscribed in the region
+    Ellipse,
+}
+
+/// What is done to everything outside of the spotlights
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpotlightEffect {
+    #[default]
+    Darken,
+    Blur,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpotlightStyle {
+    pub shape: SpotlightShape,
+    pub effect: SpotlightEffect,
+}
+
+/// Darkens or blurs the whole image except for the `spotlights`
+///
+/// All the spotlights of an image are drawn at once, so that each of them stays clear of the
+/// effects of the others, rather than the darkness stacking up.
+pub fn draw(
+    cairo: &Context,
+    surface: &ImageSurface,
+    spotlights: &[(Rectangle, SpotlightStyle)],
+) -> Result<(), Error> {
+    for effect in [SpotlightEffect::Darken, SpotlightEffect::Blur] {
+        if !spotlights.iter().any(|(_, style)| style.effect == effect) {
+            continue;
+        }
+
+        cairo.save()?;
+        cairo.push_group();
+
+        match effect {
+            SpotlightEffect::Darken => {
+                cairo.set_source_colour(DIM_COLOUR);
+                cairo.paint()?;
+            }
+            SpotlightEffect::Blur => {
+                let image = Rectangle {
+                    x: 0.0,
+                    y: 0.0,
+                    w: surface.width() as f64,
+                    h: surface.height() as f64,
+                };
+                pixelops::blur(cairo, surface, BLUR_RADIUS, image)?;
+            }
+        }
+
+        cairo.set_operator(cairo::Operator::Clear);
+        for (rect, style) in spotlights {
+            add_path(cairo, rect.normalised(), style.shape)?;
+            cairo.fill()?;
+        }
+
+        cairo.pop_group_to_source()?;
+        cairo.paint()?;
+        cairo.restore()?;
+    }
+
+    Ok(())
+}
+
+fn add_path(cairo: &Context, rect: Rectangle, shape: SpotlightShape) -> Result<(), cairo::Error> {
+    let Rectangle { x, y, w, h } = rect;
+
+    match shape {
+        SpotlightShape::Rectangle => cairo.rectangle(x, y, w, h),
+        SpotlightShape::Ellipse => {
+            if w < 1.0 || h < 1.0 {
+                return Ok(());
+            }
+
+            // The path keeps its shape once the scaling is undone
+            cairo.save()?;
+            cairo.translate(x + w / 2.0, y + h / 2.0);
+            cairo.scale(w / 2.0, h / 2.0);
+            cairo.new_sub_path();
+            cairo.arc(0.0, 0.0, 1.0, 0.0, 2.0 * std::f64::consts::PI);
+            cairo.restore()?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,40 +1,97 @@
+use std::cell::RefCell;
+
 use cairo::{Context, ImageSurface};
 use kcshot_data::{
     colour::Colour,
-    geometry::{Point, Rectangle},
+    geometry::{Ellipse, Point, Rectangle},
     Text,
 };
 use kcshot_screenshot::Window;
 use tracing::{error, warn};
 
-use super::{Operation, Tool};
+use super::{
+    self as operations, bubble, callout,
+    constraints::{region_from, snap_angle, square},
+    magnify, measure, pencil,
+    pixelops::PIXELATE_SIZE,
+    selection::{self, Selection},
+    snapping::{self, EdgeMap, Snap},
+    stamp, BubbleSettings, CropConstraint, MeasureKind, Operation, StrokeStyle, Tool, ToolOptions,
+};
 use crate::{
     editor::{operations::shapes, utils::CairoExt},
     log_if_err,
 };
 
+/// The fraction of neighbouring pixels which have to differ for a region to be considered text, see
+/// [`EdgeMap::edge_density`]
+const TEXT_EDGE_DENSITY: f64 = 0.08;
+/// How close (in pixels) to the first point of a polyline a click has to land to close it
+const POLYGON_CLOSE_DISTANCE: f64 = 8.0;
+const PIXELATED_TEXT_WARNING: &str =
+    "This looks like text, which can often be\nrecovered from pixelation, use Redact for secrets";
+
 #[derive(Debug)]
 pub struct OperationStack {
     // The stack itself
     operations: Vec<Operation>,
-    undone_operations: Vec<Operation>,
+    /// The changes which need to be applied in order to undo what was done to `operations`
+    history: Vec<Change>,
+    /// The changes which need to be applied in order to redo what was undone
+    undone_changes: Vec<Change>,
 
     // State relating to the operation going on right now
     current_tool: Tool,
     current_operation: Option<Operation>,
-    autoincrement_bubble_number: i32,
+    pub bubbles: BubbleSettings,
     pub primary_colour: Colour,
     pub secondary_colour: Colour,
     pub line_width: f64,
+    /// Where the operation going on right now was started
+    origin: Point,
+    /// Whether the shape being drawn should be constrained, i.e. regions are made square and lines
+    /// are kept at multiples of 15°
+    pub constrain: bool,
+    /// Whether the shape being drawn should grow outwards from its origin, rather than from a corner,
+    /// which is set while Space is held, since Ctrl already makes crops ignore windows
+    pub from_centre: bool,
+    /// Whether the corners of regions being drawn snap to the edges of windows and to edges
+    /// detected in the screenshot
+    pub snap_to_edges: bool,
+    edge_map: Option<EdgeMap>,
+    /// Where the corner being dragged right now was snapped to, if anywhere
+    last_snap: Option<Snap>,
+    /// Whether measurements are added to the screenshot, rather than only being shown until the
+    /// next one is made
+    pub keep_measurements: bool,
+    transient_measurement: Option<Operation>,
+    pub tool_options: ToolOptions,
+    /// How hard the stylus is pressed onto the canvas right now, from 0 to 1, if one is being used
+    stylus_pressure: Option<f64>,
+    /// The last region which was pixelated, when it looks like it holds text
+    pixelated_text: Option<Rectangle>,
+    /// The screenshot with the redactions burnt in, along with the redactions it was made for
+    ///
+    /// Effects sample this one, so that what the editor shows matches what is saved.
+    redacted_surface: RefCell<Option<(Vec<Operation>, ImageSurface)>>,
 
     // State relating to crop selection
-    pub selection_mode: SelectionMode,
+    selection_mode: SelectionMode,
+    /// The selection mode picked in the settings, which is temporarily inverted by holding Shift
+    preferred_selection_mode: SelectionMode,
+    crop_constraint: CropConstraint,
     is_in_crop_drag: bool,
     /// This in in stacking order
     windows: Vec<Window>,
     current_window: Option<usize>,
     ignore_windows: bool,
 
+    // State relating to the selection tool
+    selection: Option<Selection>,
+    /// The selected operation and the control it was last edited with through
+    /// [`Self::edit_selection`], while that edit is the last change in the undo stack
+    last_edit: Option<(usize, &'static str)>,
+
     /// Used for arrows, lines, pencil and the contours of rectangles
     editing_started_with_cropping: bool,
     pub screen_dimensions: Rectangle,
@@ -46,6 +103,60 @@ pub enum SelectionMode {
     WindowsWithoutDecorations,
 }
 
+impl SelectionMode {
+    pub fn inverted(self) -> Self {
+        match self {
+            SelectionMode::WindowsWithDecorations => SelectionMode::WindowsWithoutDecorations,
+            SelectionMode::WindowsWithoutDecorations => SelectionMode::WindowsWithDecorations,
+        }
+    }
+
+    /// Returns the rectangle of `window` which is picked when it is clicked
+    pub fn rect_of(self, window: &Window) -> Rectangle {
+        match self {
+            SelectionMode::WindowsWithDecorations => window.outer_rect,
+            SelectionMode::WindowsWithoutDecorations => window.content_rect,
+        }
+    }
+}
+
+/// A change to the list of operations, the undo and redo stacks are made out of these
+#[derive(Debug)]
+enum Change {
+    Insert { index: usize, operation: Operation },
+    Remove { index: usize },
+    Replace { index: usize, operation: Operation },
+    Move { from: usize, to: usize },
+}
+
+impl Change {
+    /// Applies the change to `operations`, returning the change which reverts it
+    fn apply(self, operations: &mut Vec<Operation>) -> Self {
+        match self {
+            Change::Insert { index, operation } => {
+                operations.insert(index, operation);
+                Change::Remove { index }
+            }
+            Change::Remove { index } => Change::Insert {
+                index,
+                operation: operations.remove(index),
+            },
+            Change::Replace {
+                index,
+                mut operation,
+            } => {
+                std::mem::swap(&mut operations[index], &mut operation);
+                Change::Replace { index, operation }
+            }
+            Change::Move { from, to } => {
+                let operation = operations.remove(from);
+                operations.insert(to, operation);
+                Change::Move { from: to, to: from }
+            }
+        }
+    }
+}
+
 impl OperationStack {
     pub fn new(
         windows: Vec<Window>,
@@ -54,14 +165,15 @@ impl OperationStack {
     ) -> Self {
         Self {
             operations: vec![],
-            undone_operations: vec![],
+            history: vec![],
+            undone_changes: vec![],
             current_tool: if editing_started_with_cropping {
                 Tool::Crop
             } else {
                 Tool::CropAndSave
             },
             current_operation: None,
-            autoincrement_bubble_number: 1,
+            bubbles: BubbleSettings::default(),
             primary_colour: Colour {
                 red: 127,
                 green: 0,
@@ -78,14 +190,72 @@ impl OperationStack {
             current_window: None,
             is_in_crop_drag: false,
             ignore_windows: false,
+            selection: None,
+            last_edit: None,
             selection_mode: SelectionMode::WindowsWithDecorations,
-            line_width: 4.0,
+            preferred_selection_mode: SelectionMode::WindowsWithDecorations,
+            crop_constraint: CropConstraint::Free,
+            line_width: operations::DEFAULT_LINE_WIDTH,
+            origin: Point { x: 0.0, y: 0.0 },
+            constrain: false,
+            from_centre: false,
+            snap_to_edges: true,
+            edge_map: None,
+            last_snap: None,
+            keep_measurements: false,
+            transient_measurement: None,
+            tool_options: ToolOptions::default(),
+            stylus_pressure: None,
+            pixelated_text: None,
+            redacted_surface: RefCell::default(),
             editing_started_with_cropping,
             screen_dimensions,
         }
     }
 
+    /// Puts back the crop region and annotations of a previously saved project, this is meant to be
+    /// used with a "crop-first" stack, so that the crop region can't be changed by undoing
+    pub fn restore_project(&mut self, crop: Rectangle, annotations: Vec<Operation>) {
+        self.bubbles.next_number = annotations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::Bubble { number, .. } => Some(number + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1);
+
+        self.operations = std::iter::once(Operation::Crop(crop))
+            .chain(annotations)
+            .collect();
+        self.history.clear();
+        self.undone_changes.clear();
+        self.selection = None;
+        self.ignore_windows = true;
+    }
+
+    /// Returns every operation other than the crop regions, in stacking order
+    pub fn annotations(&self) -> Vec<Operation> {
+        self.operations
+            .iter()
+            .filter(|operation| !matches!(operation, Operation::Crop(_)))
+            .cloned()
+            .collect()
+    }
+
     pub fn set_current_tool(&mut self, tool: Tool) {
+        if tool != Tool::Select {
+            self.selection = None;
+        }
+        self.transient_measurement = None;
+
+        // A polyline in progress is kept as it is when another tool is picked
+        if tool != Tool::Polyline {
+            if let Some(Operation::Polyline { .. }) = self.current_operation {
+                self.push_unfinished_operation();
+            }
+        }
+
         self.current_tool = tool;
     }
 
@@ -93,6 +263,29 @@ impl OperationStack {
         self.current_tool
     }
 
+    /// Returns how wide the marks made by the current tool are, in pixels, i.e. the width of its
+    /// lines, the height of its text or the diameter of its bubbles, if it has a size at all
+    pub fn current_tool_size(&self) -> Option<f64> {
+        match self.current_tool {
+            // Auto-sized bubbles can come out bigger than the chosen size, so this is the size of
+            // the next one
+            Tool::AutoincrementBubble => {
+                let label = self.bubbles.number_style.format(self.bubbles.next_number);
+                let radius = bubble::radius(
+                    self.bubbles.radius,
+                    self.bubbles.auto_size,
+                    &label,
+                    &self.bubbles.font_description(),
+                );
+                Some(2.0 * radius)
+            }
+            // Text is laid out at 96 DPI, so a point is 4/3 of a pixel
+            Tool::Text => Some(self.tool_options.text_size * 4.0 / 3.0),
+            tool if tool.has_line_width() || tool == Tool::Select => Some(self.line_width),
+            _ => None,
+        }
+    }
+
     pub fn set_current_window(&mut self, x: f64, y: f64) {
         if self.ignore_windows {
             self.current_window = None;
@@ -107,81 +300,568 @@ impl OperationStack {
         }
     }
 
+    pub fn set_preferred_selection_mode(&mut self, selection_mode: SelectionMode) {
+        self.preferred_selection_mode = selection_mode;
+        self.selection_mode = selection_mode;
+    }
+
+    /// Picks the other kind of window rectangle than the preferred one while `invert` is set
+    pub fn set_selection_mode_inverted(&mut self, invert: bool) {
+        self.selection_mode = if invert {
+            self.preferred_selection_mode.inverted()
+        } else {
+            self.preferred_selection_mode
+        };
+    }
+
     pub fn set_ignore_windows(&mut self, b: bool) {
         self.ignore_windows = b;
     }
 
+    /// Sets how hard the stylus is pressed right now, which is recorded along pencil strokes
+    pub fn set_stylus_pressure(&mut self, pressure: Option<f64>) {
+        self.stylus_pressure = pressure;
+    }
+
+    /// Sets the edges detected in the screenshot, which are snapped to along with the windows
+    pub fn set_edge_map(&mut self, edge_map: EdgeMap) {
+        self.edge_map = Some(edge_map);
+    }
+
+    /// Snaps `point` to the nearest edges if snapping is enabled, see [`snapping::snap`] for what
+    /// `anchor` is
+    fn snap_point(&mut self, point: Point, anchor: Point) -> Point {
+        self.last_snap = self
+            .edge_map
+            .as_ref()
+            .filter(|_| self.snap_to_edges)
+            .map(|edge_map| snapping::snap(point, anchor, &self.windows, edge_map));
+
+        self.last_snap.map_or(point, |snap| snap.point)
+    }
+
     pub fn start_operation_at(&mut self, point: Point) {
-        self.undone_operations.clear();
+        if self.current_tool == Tool::Select {
+            self.start_selection_drag_at(point);
+            return;
+        }
+
+        // The handles of the crop region can be grabbed no matter which tool is used
+        if self.start_crop_adjustment_at(point) {
+            return;
+        }
 
-        if let Some(old_operation) = self.current_operation.take() {
-            self.operations.push(old_operation);
+        // Polylines are made out of several clicks, which add points to them until they're finished
+        if let (Tool::Polyline, Some(Operation::Polyline { points, .. })) =
+            (self.current_tool, self.current_operation.as_mut())
+        {
+            points.push(point);
+            self.origin = point;
+            return;
         }
 
+        self.push_unfinished_operation();
+        self.transient_measurement = None;
+        self.pixelated_text = None;
+
+        let point = if self.current_tool.snaps_to_edges() {
+            self.snap_point(point, point)
+        } else {
+            point
+        };
+        self.origin = point;
+
         self.current_operation = Some(Operation::create_default_for_tool(
             self.current_tool,
             point,
-            &mut self.autoincrement_bubble_number,
+            &mut self.bubbles,
             self.primary_colour,
             self.secondary_colour,
             self.line_width,
+            &self.tool_options,
         ));
     }
 
     pub fn undo(&mut self) {
-        if self.operations.len() == 1 && matches!(self.operations[0], Operation::Crop(_)) {
+        // The crop region chosen at the start of the "crop-first" mode can't be undone
+        let would_remove_initial_crop =
+            matches!(self.history.last(), Some(Change::Remove { index: 0 }))
+                && matches!(self.operations.first(), Some(Operation::Crop(_)));
+        if would_remove_initial_crop {
             return;
         }
 
-        if let Some(op) = self.operations.pop() {
-            self.undone_operations.push(op);
+        if let Some(change) = self.history.pop() {
+            self.selection = None;
+            self.last_edit = None;
+            let inverse = change.apply(&mut self.operations);
+            self.undone_changes.push(inverse);
         }
     }
 
     pub fn redo(&mut self) {
-        if let Some(op) = self.undone_operations.pop() {
-            self.operations.push(op);
+        if let Some(change) = self.undone_changes.pop() {
+            self.selection = None;
+            self.last_edit = None;
+            let inverse = change.apply(&mut self.operations);
+            self.history.push(inverse);
+        }
+    }
+
+    /// Applies `change` and records it in the undo stack
+    fn commit(&mut self, change: Change) {
+        self.undone_changes.clear();
+        self.last_edit = None;
+        let inverse = change.apply(&mut self.operations);
+        self.history.push(inverse);
+    }
+
+    fn push_operation(&mut self, operation: Operation) {
+        self.commit(Change::Insert {
+            index: self.operations.len(),
+            operation,
+        });
+    }
+
+    /// Pushes the operation going on right now as it is, like a polyline which wasn't finished with
+    /// a double-click
+    fn push_unfinished_operation(&mut self) {
+        if let Some(operation) = self.current_operation.take() {
+            if !is_empty_polyline(&operation) {
+                self.push_operation(operation);
+            }
+        }
+    }
+
+    fn start_selection_drag_at(&mut self, point: Point) {
+        self.last_edit = None;
+        if let Some(selection) = self.selection.as_mut() {
+            let selected = &self.operations[selection.index];
+            if let Some(handle) = selection::handle_at(selected, point) {
+                selection.start_drag(selected, Some(handle));
+                return;
+            }
+        }
+
+        // We go through the operations in reverse, as the ones at the end of the stack are drawn on
+        // top of the others, the crop region is only picked if nothing else was
+        self.selection = self
+            .operations
+            .iter()
+            .rposition(|operation| operation.contains(point))
+            .or_else(|| {
+                self.initial_crop()
+                    .filter(|crop| crop.normalised().contains(point))
+                    .map(|_| 0)
+            })
+            .map(|index| {
+                let mut selection = Selection::new(index);
+                selection.start_drag(&self.operations[index], None);
+                selection
+            });
+    }
+
+    fn start_crop_adjustment_at(&mut self, point: Point) -> bool {
+        if self.current_tool.is_cropping_tool() || self.initial_crop().is_none() {
+            return false;
+        }
+
+        let crop = &self.operations[0];
+        let Some(handle) = selection::handle_at(crop, point) else {
+            return false;
+        };
+
+        let mut selection = Selection::new(0);
+        selection.start_drag(crop, Some(handle));
+        self.selection = Some(selection);
+
+        true
+    }
+
+    /// Returns whether something is being dragged around through its selection handles
+    pub fn is_dragging_selection(&self) -> bool {
+        matches!(&self.selection, Some(selection) if selection.is_dragging())
+    }
+
+    /// Returns whether something is being drawn, or dragged around through its selection handles
+    pub fn is_drawing(&self) -> bool {
+        self.current_operation.is_some() || self.is_dragging_selection()
+    }
+
+    /// Returns the crop region chosen at the start of the "crop-first" mode, if it was chosen
+    /// already
+    fn initial_crop(&self) -> Option<Rectangle> {
+        match self.operations.first() {
+            Some(Operation::Crop(rect)) if self.editing_started_with_cropping => Some(*rect),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the selected operation is the crop region of the "crop-first" mode
+    pub fn is_initial_crop_selected(&self) -> bool {
+        matches!(&self.selection, Some(selection) if selection.index == 0)
+            && self.initial_crop().is_some()
+    }
+
+    /// Returns the crop region of the "crop-first" mode, so that it can be adjusted
+    pub fn crop_region_to_adjust(&self) -> Option<Rectangle> {
+        self.initial_crop().map(|crop| crop.normalised())
+    }
+
+    /// Changes the crop region chosen at the start of the "crop-first" mode, in a way that can be
+    /// undone
+    pub fn adjust_crop_region(&mut self, rect: Rectangle) {
+        if self.initial_crop().is_some() {
+            self.commit(Change::Replace {
+                index: 0,
+                operation: Operation::Crop(self.crop_constraint.apply(rect, false)),
+            });
+        }
+    }
+
+    /// Changes the shape crop regions are restricted to from now on
+    pub fn set_crop_constraint(&mut self, crop_constraint: CropConstraint) {
+        self.crop_constraint = crop_constraint;
+    }
+
+    /// Reshapes the crop region of the "crop-first" mode so that it respects the current crop
+    /// constraint, if it was already chosen
+    pub fn constrain_crop_region(&mut self) {
+        if let Some(crop) = self.crop_region_to_adjust() {
+            let constrained = self.crop_constraint.apply(crop, false);
+            if constrained.w != crop.w || constrained.h != crop.h {
+                self.adjust_crop_region(crop);
+            }
+        }
+    }
+
+    /// Moves the selected operation by (`dx`, `dy`), or the crop region of the "crop-first" mode if
+    /// nothing is selected
+    pub fn nudge(&mut self, dx: f64, dy: f64) {
+        let delta = Point { x: dx, y: dy };
+        if self.selection.is_some() {
+            self.edit_selection("nudge", |operation| operation.translate(delta));
+        } else if let Some(crop) = self.initial_crop() {
+            let nudged = self.crop_constraint.apply(
+                Rectangle {
+                    x: crop.x + dx,
+                    y: crop.y + dy,
+                    ..crop
+                },
+                false,
+            );
+            self.replace_operation(0, "nudge", Operation::Crop(nudged));
+        }
+    }
+
+    fn finish_selection_drag(&mut self) {
+        let Some(selection) = self.selection.as_mut() else {
+            return;
+        };
+
+        if let Some(original) = selection.finish_drag() {
+            // The selected operation was already changed in place while dragging, so we only need
+            // to remember how to undo the drag
+            self.undone_changes.clear();
+            self.last_edit = None;
+            self.history.push(Change::Replace {
+                index: selection.index,
+                operation: original,
+            });
         }
     }
 
+    /// Applies `edit` to the selected operation, if there is one, in a way that can be undone
+    ///
+    /// Edits made one after the other through the same `control` are undone together, so that
+    /// dragging a slider doesn't leave an undo step behind for every value it went through.
+    pub fn edit_selection(&mut self, control: &'static str, edit: impl FnOnce(&mut Operation)) {
+        let Some(index) = self.selection.as_ref().map(|selection| selection.index) else {
+            return;
+        };
+
+        let mut operation = self.operations[index].clone();
+        edit(&mut operation);
+        self.replace_operation(index, control, operation);
+    }
+
+    /// Replaces the operation at `index` with `operation` in a way that can be undone, together with
+    /// the edits made to it right before through the same `control`
+    fn replace_operation(&mut self, index: usize, control: &'static str, operation: Operation) {
+        if operation == self.operations[index] {
+            return;
+        }
+
+        if self.last_edit == Some((index, control)) {
+            // The undo stack already knows how the operation looked before the first of these edits
+            self.operations[index] = operation;
+        } else {
+            self.commit(Change::Replace { index, operation });
+            self.last_edit = Some((index, control));
+        }
+    }
+
+    pub fn delete_selection(&mut self) {
+        // The crop region can be changed, but not removed
+        if self.is_initial_crop_selected() {
+            return;
+        }
+
+        if let Some(selection) = self.selection.take() {
+            self.commit(Change::Remove {
+                index: selection.index,
+            });
+        }
+    }
+
+    /// Moves the selected operation `by` places up (towards the top of the stack) or down
+    pub fn restack_selection(&mut self, by: isize) {
+        if self.is_initial_crop_selected() {
+            return;
+        }
+
+        let Some(selection) = self.selection.as_mut() else {
+            return;
+        };
+
+        // Nothing can go below the crop region of the "crop-first" mode, as that's how we find it
+        let lowest = usize::from(matches!(self.operations.first(), Some(Operation::Crop(_))));
+        let from = selection.index;
+        let to = from
+            .saturating_add_signed(by)
+            .clamp(lowest, self.operations.len().saturating_sub(1));
+        if from == to {
+            return;
+        }
+
+        selection.index = to;
+        self.commit(Change::Move { from, to });
+    }
+
     pub fn update_current_operation_end_coordinate(&mut self, new_width: f64, new_height: f64) {
+        if self.current_tool == Tool::Select || self.is_dragging_selection() {
+            if let Some(selection) = self.selection.as_mut() {
+                let operation = &mut self.operations[selection.index];
+                selection.update_drag(operation, new_width, new_height);
+                if let (Operation::Crop(rect), Some(sides)) = (operation, selection.resized_sides())
+                {
+                    *rect = self.crop_constraint.apply_to_resize(*rect, sides);
+                }
+            }
+            return;
+        }
+
+        let (width, height) = self.snap_end_coordinate(new_width, new_height);
+        let origin = self.origin;
+
         let Some(current_operation) = self.current_operation.as_mut() else {
             return;
         };
 
+        // Shapes which span a region are squared when constrained, while lines are kept at
+        // multiples of 15°. Both can be drawn outwards from where the drag started.
         match current_operation {
-            Operation::Crop(rect)
-            | Operation::Blur { rect, .. }
+            Operation::Crop(rect) => {
+                let constrained = self.crop_constraint.apply(
+                    Rectangle {
+                        x: origin.x,
+                        y: origin.y,
+                        w: width,
+                        h: height,
+                    },
+                    self.constrain,
+                );
+                *rect = match self.crop_constraint {
+                    // Mirroring the drag would double the size, so the region is centred instead
+                    CropConstraint::FixedSize { .. } if self.from_centre => Rectangle {
+                        x: origin.x - constrained.w / 2.0,
+                        y: origin.y - constrained.h / 2.0,
+                        ..constrained
+                    },
+                    _ => region_from(origin, constrained.w, constrained.h, self.from_centre),
+                };
+            }
+            Operation::Blur { rect, .. }
             | Operation::Pixelate { rect, .. }
             | Operation::DrawRectangle { rect, .. }
-            | Operation::Highlight { rect } => {
-                rect.w = new_width;
-                rect.h = new_height;
+            | Operation::Highlight { rect, .. }
+            | Operation::Spotlight { rect, .. }
+            | Operation::Redact { rect, .. }
+            | Operation::Grayscale { rect }
+            | Operation::Invert { rect }
+            | Operation::BrightnessContrast { rect, .. }
+            | Operation::Sharpen { rect, .. }
+            | Operation::Stamp { rect, .. } => {
+                let (width, height) = if self.constrain {
+                    square(width, height)
+                } else {
+                    (width, height)
+                };
+                *rect = region_from(origin, width, height, self.from_centre);
             }
-            Operation::DrawLine { start, end, .. } | Operation::DrawArrow { start, end, .. } => {
+            Operation::DrawEllipse { ellipse, .. } => {
+                let (width, height) = if self.constrain {
+                    square(width, height)
+                } else {
+                    (width, height)
+                };
+                // NOTE: Ellipses are drawn centred on (x + w/2, y + h/2) with radii of w and h, see
+                //       `shapes::draw_ellipse`
+                *ellipse = if self.from_centre {
+                    Ellipse {
+                        x: origin.x - width / 2.0,
+                        y: origin.y - height / 2.0,
+                        w: width,
+                        h: height,
+                    }
+                } else {
+                    Ellipse {
+                        x: origin.x,
+                        y: origin.y,
+                        w: width,
+                        h: height,
+                    }
+                };
+            }
+            Operation::DrawLine { start, end, .. }
+            | Operation::DrawArrow { start, end, .. }
+            | Operation::CurvedArrow { start, end, .. }
+            | Operation::Measure {
+                start,
+                end,
+                kind: MeasureKind::Distance,
+                ..
+            } => {
+                let (width, height) = if self.constrain {
+                    snap_angle(width, height)
+                } else {
+                    (width, height)
+                };
+                *start = if self.from_centre {
+                    Point {
+                        x: origin.x - width,
+                        y: origin.y - height,
+                    }
+                } else {
+                    origin
+                };
                 *end = Point {
-                    x: start.x + new_width,
-                    y: start.y + new_height,
-                }
+                    x: origin.x + width,
+                    y: origin.y + height,
+                };
             }
-            Operation::DrawEllipse { ellipse, .. } => {
-                ellipse.w = new_width;
-                ellipse.h = new_height;
+            Operation::Measure {
+                start,
+                end,
+                kind: MeasureKind::Size,
+                ..
+            } => {
+                let (width, height) = if self.constrain {
+                    square(width, height)
+                } else {
+                    (width, height)
+                };
+                let Rectangle { x, y, w, h } = region_from(origin, width, height, self.from_centre);
+                *start = Point { x, y };
+                *end = Point { x: x + w, y: y + h };
             }
             Operation::Pencil {
+                start: Point { x, y },
+                points,
+                pressures,
+                ..
+            } => {
+                points.push(Point {
+                    x: width + *x,
+                    y: height + *y,
+                });
+                // The stylus can report its first pressure after the stroke was started, in which
+                // case the points before it get that pressure too
+                if let Some(pressure) = self.stylus_pressure {
+                    pressures.resize(points.len() + 1, pressure);
+                }
+            }
+            Operation::Highlighter {
                 start: Point { x, y },
                 points,
                 ..
             } => points.push(Point {
-                x: new_width + *x,
-                y: new_height + *y,
+                x: width + *x,
+                y: height + *y,
             }),
+            Operation::Callout { rect, .. } => {
+                *rect = callout::place(
+                    origin,
+                    Point {
+                        x: origin.x + width,
+                        y: origin.y + height,
+                    },
+                );
+            }
+            Operation::Magnify {
+                source,
+                centre,
+                zoom,
+                ..
+            } => {
+                let (width, height) = if self.constrain {
+                    square(width, height)
+                } else {
+                    (width, height)
+                };
+                *source = region_from(origin, width, height, self.from_centre);
+                *centre = magnify::inset_centre_for(*source, *zoom);
+            }
+            // Every click adds a point, which is then dragged around until the button is released
+            Operation::Polyline { points, .. } => {
+                if let Some(last) = points.last_mut() {
+                    *last = Point {
+                        x: origin.x + width,
+                        y: origin.y + height,
+                    };
+                }
+            }
             Operation::Bubble { .. } | Operation::Text { .. } => {}
         }
+
+        // The bend is kept in the same place relative to the arrow until the arrow is placed, after
+        // that it can be dragged around through its control point
+        if let Operation::CurvedArrow {
+            start,
+            control,
+            end,
+            ..
+        } = current_operation
+        {
+            *control = shapes::default_curve_control(*start, *end);
+        }
+    }
+
+    /// Snaps the corner of the region being drawn which is dragged around, the width and height are
+    /// relative to the point at which the region was started
+    fn snap_end_coordinate(&mut self, width: f64, height: f64) -> (f64, f64) {
+        if !self.current_tool.snaps_to_edges() {
+            return (width, height);
+        }
+
+        // Clicks are left alone, so that clicking to pick a window keeps working
+        if width.abs() < 1.0 && height.abs() < 1.0 {
+            self.last_snap = None;
+            return (width, height);
+        }
+
+        let start = self.origin;
+        let end = Point {
+            x: start.x + width,
+            y: start.y + height,
+        };
+        let end = self.snap_point(end, start);
+
+        (end.x - start.x, end.y - start.y)
     }
 
     pub fn set_text(&mut self, text: Text) {
-        if self.current_tool != Tool::Text {
+        if !matches!(self.current_tool, Tool::Text | Tool::Callout) {
             warn!(
                 "Trying to set text when self.current_tool={:?}",
                 self.current_tool
@@ -194,28 +874,201 @@ impl OperationStack {
             .set_text(text);
     }
 
+    /// Changes the number bubbles start from, the next bubble gets it too unless some were already
+    /// placed
+    pub fn set_bubble_start_number(&mut self, start_number: i32) {
+        self.bubbles.start_number = start_number;
+
+        let has_bubbles = self
+            .operations
+            .iter()
+            .any(|operation| matches!(operation, Operation::Bubble { .. }));
+        if !has_bubbles {
+            self.bubbles.reset_numbering();
+        }
+    }
+
+    /// Returns how the lines of the selected operation are drawn, if it has any
+    pub fn selected_stroke_style(&self) -> Option<StrokeStyle> {
+        let selection = self.selection.as_ref()?;
+        self.operations[selection.index]
+            .stroke_style()
+            .map(|(stroke, _)| stroke)
+    }
+
+    /// Returns the text of the selected operation, so that it can be edited
+    pub fn selected_text(&self) -> Option<Text> {
+        let selection = self.selection.as_ref()?;
+        self.operations[selection.index].editable_text()
+    }
+
+    /// Replaces the text of the selected operation, in a way that can be undone
+    pub fn set_selected_text(&mut self, text: Text) {
+        self.edit_selection("text", |operation| operation.set_text(text));
+    }
+
+    /// Returns the crop region being selected right now, if there is one
+    pub fn current_crop_region(&self) -> Option<Rectangle> {
+        match self.current_operation {
+            Some(Operation::Crop(rect)) => Some(rect),
+            _ => None,
+        }
+    }
+
     pub fn set_is_in_crop_drag(&mut self, is_in_crop_drag: bool) {
         self.is_in_crop_drag = is_in_crop_drag;
     }
 
     pub fn finish_current_operation(&mut self) {
+        self.last_snap = None;
+
+        if self.current_tool == Tool::Select {
+            self.finish_selection_drag();
+            return;
+        }
+
+        if self.is_dragging_selection() {
+            // This was an adjustment of the crop region made while using some other tool
+            self.finish_selection_drag();
+            self.selection = None;
+            return;
+        }
+
         if let Some(mut operation) = self.current_operation.take() {
+            if let Operation::Crop(rect) = operation {
+                let point = Point {
+                    x: rect.x,
+                    y: rect.y,
+                };
+                if let Some(region) = self
+                    .crop_constraint
+                    .region_at(point)
+                    .filter(|_| should_crop_selected_window_or_screen(rect))
+                {
+                    operation = Operation::Crop(region);
+                }
+            }
+
             if self.current_tool == Tool::Crop {
                 self.ignore_windows = true;
                 if let Operation::Crop(rect) = operation {
                     if should_crop_selected_window_or_screen(rect) {
                         if let Some(current_window) = self.current_window {
-                            // FIXME: We should allow selecting the content rect somehow
-                            operation = Operation::Crop(self.windows[current_window].outer_rect);
+                            operation = Operation::Crop(
+                                self.selection_mode.rect_of(&self.windows[current_window]),
+                            );
+                        } else {
+                            // We make the whole screen explicit, so that it can be adjusted later
+                            operation = Operation::Crop(self.screen_dimensions);
                         }
                     }
                 }
             }
 
-            self.operations.push(operation);
+            if matches!(operation, Operation::Measure { .. }) && !self.keep_measurements {
+                self.transient_measurement = Some(operation);
+                return;
+            }
+
+            if let Operation::Pencil {
+                start,
+                points,
+                pressures,
+                ..
+            } = &mut operation
+            {
+                pencil::simplify(*start, points, pressures);
+            }
+
+            if is_empty_polyline(&operation) {
+                return;
+            }
+
+            // Larger blocks than the default ones can still be too small, but those are at least a
+            // deliberate choice
+            if let Operation::Pixelate { rect, size, .. } = operation {
+                if size <= PIXELATE_SIZE && self.looks_like_text(rect) {
+                    warn!(
+                        "Text pixelated in blocks of {size}px can often be recovered, secrets \
+                         should be redacted instead"
+                    );
+                    self.pixelated_text = Some(rect);
+                }
+            }
+
+            // A click places the stamp at its default size, centred on the cursor
+            if let Operation::Stamp { rect, .. } = &mut operation {
+                if let Some(region) = stamp::clicked_region(*rect, self.origin) {
+                    *rect = region;
+                }
+            }
+
+            self.push_operation(operation);
         }
     }
 
+    /// Called when a click which placed a point of a polyline is released, the polyline is
+    /// finished when the click was a double-click, or when it closed the polyline by landing on its
+    /// first point
+    pub fn release_polyline_point(&mut self, is_double_click: bool) {
+        let Some(Operation::Polyline { points, closed, .. }) = self.current_operation.as_mut()
+        else {
+            return;
+        };
+
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return;
+        };
+        if points.len() > 3 && (*last - *first).dist() <= POLYGON_CLOSE_DISTANCE {
+            points.pop();
+            *closed = true;
+        } else if is_double_click {
+            // The second click of the double-click placed a point on top of the first one
+            points.pop();
+        } else {
+            return;
+        }
+
+        self.finish_current_operation();
+    }
+
+    /// Returns `surface` with the redactions burnt in, if there are any, the redaction being drawn
+    /// right now is left out, as it's on top of everything that could show what's under it
+    fn redacted_surface(&self, surface: &ImageSurface) -> Option<ImageSurface> {
+        let redactions = self
+            .operations
+            .iter()
+            .filter(|operation| matches!(operation, Operation::Redact { .. }))
+            .cloned()
+            .collect::<Vec<_>>();
+        if redactions.is_empty() {
+            return None;
+        }
+
+        let mut cache = self.redacted_surface.borrow_mut();
+        match cache.as_ref() {
+            Some((cached_redactions, redacted)) if *cached_redactions == redactions => {
+                Some(redacted.clone())
+            }
+            _ => {
+                let redacted = operations::redacted_copy(surface, &redactions)
+                    .map_err(|why| error!("Failed to burn in the redactions: {why}"))
+                    .ok()?;
+                *cache = Some((redactions, redacted.clone()));
+                Some(redacted)
+            }
+        }
+    }
+
+    fn looks_like_text(&self, rect: Rectangle) -> bool {
+        let density = self
+            .edge_map
+            .as_ref()
+            .map(|edge_map| edge_map.edge_density(rect));
+
+        matches!(density, Some(density) if density >= TEXT_EDGE_DENSITY)
+    }
+
     pub fn crop_region(&self, point: Option<Point>) -> Option<Rectangle> {
         // We do this in order to support both "crop-first" and "crop-last" modes
         let crop_rect = match self.operations.last() {
@@ -236,10 +1089,7 @@ impl OperationStack {
                         .iter()
                         .rev()
                         .find(|window| window.outer_rect.contains(point))
-                        .map(|window| match self.selection_mode {
-                            SelectionMode::WindowsWithDecorations => window.outer_rect,
-                            SelectionMode::WindowsWithoutDecorations => window.content_rect,
-                        })
+                        .map(|window| self.selection_mode.rect_of(window))
                 } else {
                     None
                 }
@@ -252,25 +1102,65 @@ impl OperationStack {
     }
 
     pub fn execute(&self, surface: &ImageSurface, cairo: &Context, is_in_draw_event: bool) {
-        for operation in self.operations.iter() {
-            if let Err(why) = operation.execute(
-                surface,
-                cairo,
-                is_in_draw_event,
-                !self.editing_started_with_cropping,
-            ) {
-                error!("Got error trying to execute {operation:?}: {why}");
+        let redacted_surface = self.redacted_surface(surface);
+        let errors = operations::execute_all(
+            self.operations.iter().chain(&self.current_operation),
+            redacted_surface.as_ref().unwrap_or(surface),
+            cairo,
+            is_in_draw_event,
+            !self.editing_started_with_cropping,
+        );
+        for (operation, why) in errors {
+            error!("Got error trying to execute {operation:?}: {why}");
+        }
+
+        if let Some(measurement) = self
+            .transient_measurement
+            .as_ref()
+            .filter(|_| is_in_draw_event)
+        {
+            if let Err(why) = measurement.execute(surface, cairo, is_in_draw_event, false) {
+                error!("Got error trying to execute {measurement:?}: {why}");
+            }
+        }
+
+        if let Some(rect) = self.pixelated_text.filter(|_| is_in_draw_event) {
+            let Rectangle { x, y, w, h } = rect.normalised();
+            let centre = Point {
+                x: x + w / 2.0,
+                y: y + h / 2.0,
+            };
+            if let Err(why) = measure::draw_label(cairo, centre, PIXELATED_TEXT_WARNING) {
+                error!("Got error trying to warn about pixelated text: {why}");
             }
         }
 
-        if let Some(operation) = &self.current_operation {
-            if let Err(why) = operation.execute(
-                surface,
-                cairo,
-                is_in_draw_event,
-                !self.editing_started_with_cropping,
-            ) {
-                error!("Got error trying to execute {operation:?}: {why}");
+        if is_in_draw_event && self.current_operation.is_some() {
+            if let Some(snap) = &self.last_snap {
+                if let Err(why) = snapping::draw_guides(cairo, snap, self.screen_dimensions) {
+                    error!("Got error trying to draw the edges which were snapped to: {why}");
+                }
+            }
+        }
+
+        if is_in_draw_event && self.current_tool == Tool::Select {
+            if let Some(selection) = &self.selection {
+                let operation = &self.operations[selection.index];
+                if let Err(why) = selection.draw(cairo, operation) {
+                    error!("Got error trying to draw the selection around {operation:?}: {why}");
+                }
+            }
+        }
+
+        if is_in_draw_event && !self.current_tool.is_cropping_tool() {
+            if let Some(crop) = self
+                .operations
+                .first()
+                .filter(|_| self.initial_crop().is_some())
+            {
+                if let Err(why) = selection::draw_crop_adjustment(cairo, crop) {
+                    error!("Got error trying to draw the handles of the crop region: {why}");
+                }
             }
         }
 
@@ -296,12 +1186,28 @@ impl OperationStack {
 
         if should_draw_windows {
             if let Some(idx) = self.current_window {
-                let Rectangle { x, y, w, h } = match self.selection_mode {
-                    SelectionMode::WindowsWithDecorations => self.windows[idx].outer_rect,
-                    SelectionMode::WindowsWithoutDecorations => self.windows[idx].content_rect,
-                };
+                let window = &self.windows[idx];
+                let Rectangle { x, y, w, h } = self.selection_mode.rect_of(window);
                 log_if_err!(cairo.save());
 
+                // We also show the rectangle which would be picked while holding Shift, so that
+                // users know they can pick it
+                let alternative = self.selection_mode.inverted().rect_of(window);
+                if (alternative.x, alternative.y, alternative.w, alternative.h) != (x, y, w, h) {
+                    log_if_err!(cairo.save());
+                    cairo.rectangle(alternative.x, alternative.y, alternative.w, alternative.h);
+                    cairo.set_source_colour(Colour {
+                        red: 0,
+                        green: 127,
+                        blue: 190,
+                        alpha: 127,
+                    });
+                    cairo.set_line_width(1.0);
+                    cairo.set_dash(&[2.0, 4.0], 0.0);
+                    log_if_err!(cairo.stroke());
+                    log_if_err!(cairo.restore());
+                }
+
                 cairo.rectangle(x, y, w, h);
                 // When we are in draw events (aka this is being shown to the user), we want to make it clear
                 // they are selecting the region which will be cropped
@@ -357,9 +1263,174 @@ impl OperationStack {
     }
 }
 
+/// Polylines which were only clicked once have nothing to show
+fn is_empty_polyline(operation: &Operation) -> bool {
+    matches!(operation, Operation::Polyline { points, .. } if points.len() < 2)
+}
+
 /// If the width or height of the rectangle are 0, or the area of the rectangle covers
 /// less than a pixel, we consider the entire screen or window under the cursor to be
 /// the crop region
 fn should_crop_selected_window_or_screen(rect: Rectangle) -> bool {
     rect.area() < 1.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Operation, OperationStack, Point, Rectangle, Tool};
+
+    const SCREEN: Rectangle = Rectangle {
+        x: 0.0,
+        y: 0.0,
+        w: 200.0,
+        h: 100.0,
+    };
+
+    /// A blurred region 20 pixels wide, starting at `x`
+    fn blur(x: f64) -> Operation {
+        Operation::Blur {
+            rect: Rectangle {
+                x,
+                y: 0.0,
+                w: 20.0,
+                h: 100.0,
+            },
+            radius: 5.0,
+        }
+    }
+
+    /// Returns a stack with the selection tool picked and a blurred region at each of `xs`
+    fn stack_with_regions(xs: &[f64]) -> OperationStack {
+        let mut stack = OperationStack::new(vec![], SCREEN, false);
+        for &x in xs {
+            stack.push_operation(blur(x));
+        }
+        stack.set_current_tool(Tool::Select);
+        stack
+    }
+
+    /// Where each region in the stack starts, from the bottom of the stack to its top
+    fn region_positions(stack: &OperationStack) -> Vec<f64> {
+        stack
+            .operations
+            .iter()
+            .map(|operation| match operation {
+                Operation::Blur { rect, .. } => rect.x,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn set_blur_radius(new_radius: f32) -> impl FnOnce(&mut Operation) {
+        move |operation| {
+            if let Operation::Blur { radius, .. } = operation {
+                *radius = new_radius;
+            }
+        }
+    }
+
+    #[test]
+    fn drag() {
+        let mut stack = stack_with_regions(&[10.0, 50.0]);
+
+        stack.start_operation_at(Point { x: 15.0, y: 50.0 });
+        stack.update_current_operation_end_coordinate(5.0, 0.0);
+        stack.finish_current_operation();
+        assert_eq!(region_positions(&stack), [15.0, 50.0]);
+
+        stack.undo();
+        assert_eq!(region_positions(&stack), [10.0, 50.0]);
+        stack.redo();
+        assert_eq!(region_positions(&stack), [15.0, 50.0]);
+
+        // Clicking an operation without dragging it doesn't leave an undo step behind
+        let undo_steps = stack.history.len();
+        stack.start_operation_at(Point { x: 55.0, y: 50.0 });
+        stack.finish_current_operation();
+        assert_eq!(stack.history.len(), undo_steps);
+    }
+
+    #[test]
+    fn restack() {
+        let mut stack = stack_with_regions(&[10.0, 50.0, 90.0]);
+        stack.start_operation_at(Point { x: 15.0, y: 50.0 });
+        stack.finish_current_operation();
+
+        stack.restack_selection(1);
+        assert_eq!(region_positions(&stack), [50.0, 10.0, 90.0]);
+        // The selection follows the operation, which can't go past the top of the stack
+        stack.restack_selection(5);
+        assert_eq!(region_positions(&stack), [50.0, 90.0, 10.0]);
+
+        stack.undo();
+        assert_eq!(region_positions(&stack), [50.0, 10.0, 90.0]);
+        stack.undo();
+        assert_eq!(region_positions(&stack), [10.0, 50.0, 90.0]);
+        stack.redo();
+        stack.redo();
+        assert_eq!(region_positions(&stack), [50.0, 90.0, 10.0]);
+    }
+
+    #[test]
+    fn initial_crop() {
+        let crop = Rectangle {
+            x: 10.0,
+            y: 10.0,
+            w: 50.0,
+            h: 40.0,
+        };
+        let mut stack = OperationStack::new(vec![], SCREEN, true);
+        stack.push_operation(Operation::Crop(crop));
+
+        // Nudges in a row are undone together, like those of selected operations
+        stack.nudge(3.0, -3.0);
+        stack.nudge(2.0, -2.0);
+        let nudged = stack.crop_region_to_adjust().unwrap();
+        assert_eq!(
+            (nudged.x, nudged.y, nudged.w, nudged.h),
+            (15.0, 5.0, 50.0, 40.0)
+        );
+
+        stack.undo();
+        let restored = stack.crop_region_to_adjust().unwrap();
+        assert_eq!((restored.x, restored.y), (10.0, 10.0));
+
+        // The crop region isn't removed
+        stack.undo();
+        let restored = stack.crop_region_to_adjust().unwrap();
+        assert_eq!((restored.x, restored.y), (10.0, 10.0));
+    }
+
+    #[test]
+    fn nudge() {
+        let mut stack = stack_with_regions(&[10.0, 50.0]);
+        stack.start_operation_at(Point { x: 15.0, y: 50.0 });
+        stack.finish_current_operation();
+
+        // Nudges in a row are undone together
+        for _ in 0..3 {
+            stack.nudge(1.0, 0.0);
+        }
+        stack.edit_selection("blur-radius", set_blur_radius(8.0));
+        assert_eq!(region_positions(&stack), [13.0, 50.0]);
+
+        stack.undo();
+        assert_eq!(stack.operations[0], blur(13.0));
+        stack.undo();
+        assert_eq!(stack.operations[0], blur(10.0));
+        stack.redo();
+        assert_eq!(stack.operations[0], blur(13.0));
+    }
+
+    #[test]
+    fn unchanged_edits_are_skipped() {
+        let mut stack = stack_with_regions(&[10.0]);
+        stack.start_operation_at(Point { x: 15.0, y: 50.0 });
+        stack.finish_current_operation();
+
+        let undo_steps = stack.history.len();
+        stack.edit_selection("blur-radius", set_blur_radius(5.0));
+        stack.nudge(0.0, 0.0);
+        assert_eq!(stack.history.len(), undo_steps);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use cairo::Context;
+use gtk4::{
+    gdk::prelude::GdkCairoContextExt,
+    gdk_pixbuf::Pixbuf,
+    pango::{self, FontDescription},
+};
+use kcshot_data::{
+    geometry::{Point, Rectangle},
+    settings::Settings,
+};
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+
+/// How big stamps placed with a single click are, in pixels
+const STAMP_SIZE: f64 = 64.0;
+/// Stamps dragged out smaller than this, in pixels, were only meant to be clicked
+const MIN_DRAG_SIZE: f64 = 4.0;
+/// The size emoji are laid out at before being scaled to fit their stamp, in pixels
+const EMOJI_LAYOUT_SIZE: f64 = 128.0;
+/// The kinds of files which can be picked up from the stamps folder
+const STAMP_EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+thread_local! {
+    /// Decoding images every time they're drawn would make dragging stamps around sluggish, so the
+    /// last image loaded for each stamp is kept, along with the size it was loaded at
+    static IMAGES: RefCell<HashMap<StampSource, ((i32, i32), Option<Pixbuf>)>> =
+        RefCell::default();
+}
+
+/// The stamps which come with kcshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BundledStamp {
+    Check,
+    Cross,
+    Warning,
+    Info,
+    Star,
+}
+
+impl BundledStamp {
+    /// The stamps in the order in which they're shown in the toolbar
+    pub const ALL: [Self; 5] = [
+        Self::Check,
+        Self::Cross,
+        Self::Warning,
+        Self::Info,
+        Self::Star,
+    ];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Check => "Check",
+            Self::Cross => "Cross",
+            Self::Warning => "Warning",
+            Self::Info => "Info",
+            Self::Star => "Star",
+        }
+    }
+
+    const fn path(self) -> &'static str {
+        match self {
+            Self::Check => "/kc/kcshot/stamps/check.png",
+            Self::Cross => "/kc/kcshot/stamps/cross.png",
+            Self::Warning => "/kc/kcshot/stamps/warning.png",
+            Self::Info => "/kc/kcshot/stamps/info.png",
+            Self::Star => "/kc/kcshot/stamps/star.png",
+        }
+    }
+}
+
+/// What a stamp shows
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StampSource {
+    Bundled(BundledStamp),
+    /// Text drawn through Pango, which is meant for emoji, but works for any short text
+    Emoji(String),
+    /// A PNG or SVG file, usually one from the stamps folder, see [`user_stamps`]
+    File(PathBuf),
+}
+
+impl Default for StampSource {
+    fn default() -> Self {
+        Self::Bundled(BundledStamp::Check)
+    }
+}
+
+impl StampSource {
+    /// The name of the stamp as shown in the toolbar
+    pub fn label(&self) -> String {
+        match self {
+            Self::Bundled(stamp) => stamp.label().to_owned(),
+            Self::Emoji(text) => text.clone(),
+            Self::File(path) => path
+                .file_stem()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+
+    /// Loads the image of the stamp at `width`x`height`, or at the biggest size which fits in there
+    /// without stretching it
+    fn load(&self, width: i32, height: i32) -> Option<Pixbuf> {
+        let result = match self {
+            Self::Bundled(stamp) => {
+                Pixbuf::from_resource_at_scale(stamp.path(), width, height, true)
+            }
+            Self::File(path) => Pixbuf::from_file_at_scale(path, width, height, true),
+            Self::Emoji(_) => return None,
+        };
+
+        result
+            .map_err(|why| tracing::warn!("Failed to load the image of the {self:?} stamp: {why}"))
+            .ok()
+    }
+}
+
+/// Returns the PNG and SVG files in the stamps folder, sorted by name
+pub fn user_stamps() -> Vec<StampSource> {
+    let folder = PathBuf::from(Settings::open().stamps_path());
+    let entries = match std::fs::read_dir(&folder) {
+        Ok(entries) => entries,
+        Err(why) => {
+            tracing::info!("Couldn't read the stamps folder at {folder:?}: {why}");
+            return vec![];
+        }
+    };
+
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| has_stamp_extension(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    paths.into_iter().map(StampSource::File).collect()
+}
+
+fn has_stamp_extension(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+        return false;
+    };
+
+    STAMP_EXTENSIONS
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(extension))
+}
+
+/// Returns where a stamp placed by a click at `point` goes, if the region it was dragged out to is
+/// too small to have been meant
+pub fn clicked_region(rect: Rectangle, point: Point) -> Option<Rectangle> {
+    let Rectangle { w, h, .. } = rect.normalised();
+    (w < MIN_DRAG_SIZE && h < MIN_DRAG_SIZE).then_some(Rectangle {
+        x: point.x - STAMP_SIZE / 2.0,
+        y: point.y - STAMP_SIZE / 2.0,
+        w: STAMP_SIZE,
+        h: STAMP_SIZE,
+    })
+}
+
+/// Draws the stamp as big as it fits inside `rect`, centred in it
+pub fn draw(cairo: &Context, rect: Rectangle, source: &StampSource) -> Result<(), Error> {
+    let rect = rect.normalised();
+    if rect.w < 1.0 || rect.h < 1.0 {
+        return Ok(());
+    }
+
+    if let StampSource::Emoji(text) = source {
+        return draw_emoji(cairo, rect, text);
+    }
+
+    let size = (rect.w.ceil() as i32, rect.h.ceil() as i32);
+    let image = IMAGES.with(|images| {
+        let mut images = images.borrow_mut();
+        match images.get(source) {
+            Some((loaded_size, image)) if *loaded_size == size => image.clone(),
+            _ => {
+                let image = source.load(size.0, size.1);
+                images.insert(source.clone(), (size, image.clone()));
+                image
+            }
+        }
+    });
+    let Some(image) = image else {
+        return Ok(());
+    };
+
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    let x = rect.x + (rect.w - width) / 2.0;
+    let y = rect.y + (rect.h - height) / 2.0;
+
+    cairo.save()?;
+    cairo.set_source_pixbuf(&image, x, y);
+    cairo.rectangle(x, y, width, height);
+    cairo.fill()?;
+    cairo.restore()?;
+
+    Ok(())
+}
+
+fn draw_emoji(cairo: &Context, rect: Rectangle, text: &str) -> Result<(), Error> {
+    let layout = pangocairo::create_layout(cairo);
+    let mut font_description = FontDescription::new();
+    font_description.set_absolute_size(EMOJI_LAYOUT_SIZE * pango::SCALE as f64);
+    layout.set_font_description(Some(&font_description));
+    layout.set_text(text);
+
+    let (ink, _) = layout.pixel_extents();
+    if ink.width() <= 0 || ink.height() <= 0 {
+        return Ok(());
+    }
+    let (width, height) = (ink.width() as f64, ink.height() as f64);
+    let scale = (rect.w / width).min(rect.h / height);
+
+    cairo.save()?;
+    cairo.translate(
+        rect.x + (rect.w - width * scale) / 2.0,
+        rect.y + (rect.h - height * scale) / 2.0,
+    );
+    cairo.scale(scale, scale);
+    cairo.translate(-ink.x() as f64, -ink.y() as f64);
+    // Glyphs without colours of their own are drawn in black
+    cairo.set_source_rgb(0.0, 0.0, 0.0);
+    pangocairo::update_layout(cairo, &layout);
+    pangocairo::show_layout(cairo, &layout);
+    cairo.restore()?;
+
+    Ok(())
+}
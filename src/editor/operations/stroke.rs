@@ -0,0 +1,148 @@
+use cairo::Context;
+use serde::{Deserialize, Serialize};
+
+/// Whether lines are drawn solid or broken up
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DashStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl DashStyle {
+    /// The styles in the order in which they're shown in the toolbar
+    pub const ALL: [Self; 3] = [Self::Solid, Self::Dashed, Self::Dotted];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Solid => "Solid",
+            Self::Dashed => "Dashed",
+            Self::Dotted => "Dotted",
+        }
+    }
+}
+
+/// How the ends of lines look
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CapStyle {
+    /// The line stops right at its end
+    #[default]
+    Butt,
+    Round,
+    /// The line goes on past its end for half of its width
+    Square,
+}
+
+impl CapStyle {
+    /// The styles in the order in which they're shown in the toolbar
+    pub const ALL: [Self; 3] = [Self::Butt, Self::Round, Self::Square];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Butt => "Flat ends",
+            Self::Round => "Round ends",
+            Self::Square => "Square ends",
+        }
+    }
+
+    const fn to_cairo(self) -> cairo::LineCap {
+        match self {
+            Self::Butt => cairo::LineCap::Butt,
+            Self::Round => cairo::LineCap::Round,
+            Self::Square => cairo::LineCap::Square,
+        }
+    }
+}
+
+/// How the corners where lines meet look
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JoinStyle {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl JoinStyle {
+    /// The styles in the order in which they're shown in the toolbar
+    pub const ALL: [Self; 3] = [Self::Miter, Self::Round, Self::Bevel];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Miter => "Sharp corners",
+            Self::Round => "Round corners",
+            Self::Bevel => "Bevelled corners",
+        }
+    }
+
+    const fn to_cairo(self) -> cairo::LineJoin {
+        match self {
+            Self::Miter => cairo::LineJoin::Miter,
+            Self::Round => cairo::LineJoin::Round,
+            Self::Bevel => cairo::LineJoin::Bevel,
+        }
+    }
+}
+
+/// How the lines of an operation are drawn, the defaults match how they were drawn before this
+/// could be picked
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StrokeStyle {
+    #[serde(default)]
+    pub dash: DashStyle,
+    #[serde(default)]
+    pub cap: CapStyle,
+    #[serde(default)]
+    pub join: JoinStyle,
+    /// How opaque the whole operation is, from 0 to 1, on top of the alpha of its colours
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            dash: DashStyle::default(),
+            cap: CapStyle::default(),
+            join: JoinStyle::default(),
+            opacity: default_opacity(),
+        }
+    }
+}
+
+impl StrokeStyle {
+    /// Sets up `cairo` so that the lines stroked afterwards look like this, the dashes are
+    /// scaled by `line_width` so that thick lines aren't broken up into blocks
+    pub fn apply(self, cairo: &Context, line_width: f64) {
+        cairo.set_line_cap(self.cap.to_cairo());
+        cairo.set_line_join(self.join.to_cairo());
+        cairo.set_dash(&self.dashes(line_width), 0.0);
+    }
+
+    /// Returns whether the operation has to be drawn on its own and blended in afterwards
+    pub fn is_translucent(self) -> bool {
+        self.opacity < 1.0
+    }
+
+    fn dashes(self, line_width: f64) -> Vec<f64> {
+        // Very thin lines would get dashes too short to tell apart from solid ones
+        let unit = line_width.max(1.0);
+        match (self.dash, self.cap) {
+            (DashStyle::Solid, _) => vec![],
+            // Caps are drawn around both ends of each dash, so the gaps have to make room for them
+            (DashStyle::Dashed, CapStyle::Butt) => vec![3.0 * unit, 2.0 * unit],
+            (DashStyle::Dashed, CapStyle::Round | CapStyle::Square) => {
+                vec![2.0 * unit, 3.0 * unit]
+            }
+            // Dashes of no length are drawn as just their caps, which makes for round or square
+            // dots, but flat ends would make them vanish, so those get dots as long as they're wide
+            (DashStyle::Dotted, CapStyle::Butt) => vec![unit, unit],
+            (DashStyle::Dotted, CapStyle::Round | CapStyle::Square) => vec![0.0, 2.0 * unit],
+        }
+    }
+}
+
+fn default_opacity() -> f64 {
+    1.0
+}
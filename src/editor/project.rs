@@ -0,0 +1,465 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use cairo::{Context, ImageSurface, PdfSurface, SvgSurface};
+use kcshot_data::geometry::Rectangle;
+use serde::{Deserialize, Serialize};
+
+use super::operations::{self, Operation, StampSource};
+
+const SCREENSHOT_FILE_NAME: &str = "screenshot.png";
+const MANIFEST_FILE_NAME: &str = "operations.json";
+/// The directory inside projects which the images of stamps picked from files are copied to
+const STAMPS_DIR_NAME: &str = "stamps";
+/// This should be bumped every time a change that older versions of kcshot can't read is made to
+/// the project format
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Encountered an error from cairo: {0}")]
+    Cairo(#[from] cairo::Error),
+    #[error("Encountered an I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to draw an operation: {0}")]
+    Operation(#[from] operations::Error),
+    #[error("Failed to (de)serialise the operations: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("The project was made with a newer version of kcshot (format version {0})")]
+    UnsupportedVersion(u32),
+}
+
+impl From<cairo::IoError> for Error {
+    fn from(cerr: cairo::IoError) -> Self {
+        match cerr {
+            cairo::IoError::Cairo(cairo) => Self::Cairo(cairo),
+            cairo::IoError::Io(io) => Self::Io(io),
+        }
+    }
+}
+
+/// The formats projects can be exported to, in which the screenshot is embedded as a raster image,
+/// while the annotations are kept as vector graphics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorFormat {
+    Svg,
+    Pdf,
+}
+
+impl VectorFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            VectorFormat::Svg => "svg",
+            VectorFormat::Pdf => "pdf",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Manifest<'a> {
+    version: u32,
+    crop: Option<Rectangle>,
+    operations: Cow<'a, [Operation]>,
+}
+
+/// An annotated screenshot that can be reopened in the editor with all of its annotations still
+/// editable
+///
+/// On disk, projects are directories containing the screenshot as a PNG and the annotations as a
+/// JSON list of [`Operation`]s. The images of stamps picked from files are copied in as well, so
+/// that the project doesn't depend on them staying where they were.
+#[derive(Debug)]
+pub struct Project {
+    /// The screenshot as it was before anything was drawn on it
+    pub surface: ImageSurface,
+    pub crop: Option<Rectangle>,
+    pub operations: Vec<Operation>,
+}
+
+impl Project {
+    /// Makes a project out of `surface`, note that the contents of `surface` are copied so that
+    /// drawing the operations on it afterwards won't affect the project
+    ///
+    /// The regions covered by redactions are wiped from the copy, so that what they hide is never
+    /// written to disk.
+    pub fn new(
+        surface: &ImageSurface,
+        crop: Option<Rectangle>,
+        operations: Vec<Operation>,
+    ) -> Result<Self, Error> {
+        let surface = copy_to_rgb24(surface)?;
+        operations::burn_in_redactions(&surface, &operations)?;
+
+        Ok(Self {
+            surface,
+            crop,
+            operations,
+        })
+    }
+
+    /// Returns where the project of the screenshot saved at `screenshot_path` is stored
+    pub fn path_for(screenshot_path: &Path) -> PathBuf {
+        screenshot_path.with_extension("kcshot")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(path)?;
+
+        let mut screenshot = BufWriter::new(File::create(path.join(SCREENSHOT_FILE_NAME))?);
+        self.surface.write_to_png(&mut screenshot)?;
+
+        let manifest = Manifest {
+            version: FORMAT_VERSION,
+            crop: self.crop,
+            operations: Cow::Owned(self.embed_stamps(path)?),
+        };
+        let writer = BufWriter::new(File::create(path.join(MANIFEST_FILE_NAME))?);
+        serde_json::to_writer(writer, &manifest)?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(path.join(MANIFEST_FILE_NAME))?);
+        let manifest: Manifest = serde_json::from_reader(reader)?;
+        if manifest.version > FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(manifest.version));
+        }
+
+        let mut screenshot = BufReader::new(File::open(path.join(SCREENSHOT_FILE_NAME))?);
+        let surface = ImageSurface::create_from_png(&mut screenshot)?;
+
+        let mut operations = manifest.operations.into_owned();
+        for operation in &mut operations {
+            if let Operation::Stamp {
+                source: StampSource::File(file),
+                ..
+            } = operation
+            {
+                // Embedded stamps are stored relative to the project, and joining an absolute path
+                // leaves it as it is
+                *file = path.join(&*file);
+            }
+        }
+
+        Ok(Self {
+            // PNGs with an alpha channel are loaded as ARGB32 surfaces, but the rest of the editor
+            // expects the RGB24 surfaces we get when taking screenshots
+            surface: copy_to_rgb24(&surface)?,
+            crop: manifest.crop,
+            operations,
+        })
+    }
+
+    /// Returns the files of the stamps which couldn't be found, as those stamps aren't drawn
+    pub fn missing_stamps(&self) -> Vec<&Path> {
+        self.operations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::Stamp {
+                    source: StampSource::File(file),
+                    ..
+                } if !file.is_file() => Some(file.as_path()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Copies the images of the stamps picked from files into the project at `path`, and returns
+    /// the operations with those stamps pointing at the copies, relative to `path`
+    ///
+    /// Stamps whose images can't be copied keep pointing at the original files.
+    fn embed_stamps(&self, path: &Path) -> Result<Vec<Operation>, Error> {
+        let mut copies = HashMap::<PathBuf, PathBuf>::new();
+        let mut operations = self.operations.clone();
+        for operation in &mut operations {
+            let Operation::Stamp {
+                source: StampSource::File(file),
+                ..
+            } = operation
+            else {
+                continue;
+            };
+
+            // The stamp was already embedded when this project was saved here before
+            if let Ok(relative) = file.strip_prefix(path) {
+                *file = relative.to_owned();
+                continue;
+            }
+            if let Some(copy) = copies.get(&*file) {
+                *file = copy.clone();
+                continue;
+            }
+
+            let copy = Path::new(STAMPS_DIR_NAME)
+                .join(copies.len().to_string())
+                .with_extension(file.extension().unwrap_or_default());
+            fs::create_dir_all(path.join(STAMPS_DIR_NAME))?;
+            match fs::copy(&*file, path.join(&copy)) {
+                Ok(_) => {
+                    copies.insert(file.clone(), copy.clone());
+                    *file = copy;
+                }
+                Err(why) => {
+                    tracing::warn!("Failed to copy the stamp at {file:?} into the project: {why}");
+                }
+            }
+        }
+
+        Ok(operations)
+    }
+
+    /// Exports the cropped screenshot, along with its annotations, to `path` in the given vector
+    /// format
+    ///
+    /// Note that blurred and pixelated regions only take the screenshot into account, not the
+    /// annotations below them.
+    pub fn export(&self, path: &Path, format: VectorFormat) -> Result<(), Error> {
+        let region = self.region();
+
+        match format {
+            VectorFormat::Svg => {
+                let surface = SvgSurface::new(region.w, region.h, Some(path))?;
+                self.render(&surface, region)?;
+                surface.finish();
+                surface.status()?;
+            }
+            VectorFormat::Pdf => {
+                let surface = PdfSurface::new(region.w, region.h, path)?;
+                self.render(&surface, region)?;
+                surface.finish();
+                surface.status()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the region of the screenshot that was kept
+    fn region(&self) -> Rectangle {
+        self.crop
+            .map(|crop| crop.normalised())
+            .unwrap_or(Rectangle {
+                x: 0.0,
+                y: 0.0,
+                w: self.surface.width() as f64,
+                h: self.surface.height() as f64,
+            })
+    }
+
+    fn render(&self, target: &cairo::Surface, region: Rectangle) -> Result<(), Error> {
+        let cairo = Context::new(target)?;
+        cairo.translate(-region.x, -region.y);
+
+        cairo.set_source_surface(&self.surface, 0.0, 0.0)?;
+        cairo.paint()?;
+
+        let errors = operations::execute_all(&self.operations, &self.surface, &cairo, false, false);
+        if let Some((_, why)) = errors.into_iter().next() {
+            return Err(why.into());
+        }
+
+        Ok(())
+    }
+}
+
+fn copy_to_rgb24(surface: &ImageSurface) -> Result<ImageSurface, Error> {
+    let copy = ImageSurface::create(cairo::Format::Rgb24, surface.width(), surface.height())?;
+
+    let cairo = Context::new(&copy)?;
+    cairo.set_source_surface(surface, 0.0, 0.0)?;
+    cairo.paint()?;
+
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        path::PathBuf,
+    };
+
+    use cairo::{Context, Format, ImageSurface};
+    use kcshot_data::{colour::Colour, geometry::Rectangle};
+
+    use super::{Error, Project, FORMAT_VERSION, MANIFEST_FILE_NAME};
+    use crate::editor::operations::{Operation, StampSource};
+
+    /// Returns an empty directory for a test to save its project into
+    fn project_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("kcshot-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    fn red_screenshot() -> ImageSurface {
+        let surface = ImageSurface::create(Format::Rgb24, 16, 8).unwrap();
+        let cairo = Context::new(&surface).unwrap();
+        cairo.set_source_rgb(1.0, 0.0, 0.0);
+        cairo.paint().unwrap();
+        drop(cairo);
+
+        surface
+    }
+
+    /// Returns the red, green and blue components of the pixel at (`x`, `y`)
+    fn pixel(surface: &mut ImageSurface, x: usize, y: usize) -> [u8; 3] {
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        let offset = y * stride + x * 4;
+
+        // RGB24 pixels are stored as native-endian u32s, which are BGRx on little-endian machines
+        [data[offset + 2], data[offset + 1], data[offset]]
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = project_dir("round-trip");
+        let crop = Rectangle {
+            x: 2.0,
+            y: 1.0,
+            w: 10.0,
+            h: 5.0,
+        };
+        let operations = vec![
+            Operation::Blur {
+                rect: crop,
+                radius: 3.0,
+            },
+            Operation::Blur {
+                rect: Rectangle {
+                    x: 0.0,
+                    y: 0.0,
+                    w: 4.0,
+                    h: 4.0,
+                },
+                radius: 7.0,
+            },
+        ];
+
+        let project = Project::new(&red_screenshot(), Some(crop), operations.clone()).unwrap();
+        project.save(&path).unwrap();
+        let mut loaded = Project::load(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(format!("{:?}", loaded.crop), format!("{:?}", Some(crop)));
+        assert_eq!(
+            format!("{:?}", loaded.operations),
+            format!("{operations:?}")
+        );
+        assert_eq!(loaded.surface.format(), Format::Rgb24);
+        assert_eq!((loaded.surface.width(), loaded.surface.height()), (16, 8));
+
+        // The operations aren't drawn on the saved screenshot
+        assert_eq!(pixel(&mut loaded.surface, 3, 2), [255, 0, 0]);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let path = project_dir("newer-version");
+        let project = Project::new(&red_screenshot(), None, vec![]).unwrap();
+        project.save(&path).unwrap();
+
+        let manifest = format!(
+            r#"{{"version":{},"crop":null,"operations":[]}}"#,
+            FORMAT_VERSION + 1
+        );
+        fs::write(path.join(MANIFEST_FILE_NAME), manifest).unwrap();
+        let result = Project::load(&path);
+        fs::remove_dir_all(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn redactions_are_burnt_in() {
+        let path = project_dir("redactions");
+        let mut screenshot = red_screenshot();
+        let redaction = Operation::Redact {
+            rect: Rectangle {
+                x: 4.0,
+                y: 2.0,
+                w: 8.0,
+                h: 4.0,
+            },
+            colour: Colour::BLACK,
+        };
+
+        let project = Project::new(&screenshot, None, vec![redaction]).unwrap();
+        project.save(&path).unwrap();
+        let mut loaded = Project::load(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        // What was under the redaction is gone from the saved screenshot, even though the
+        // redaction itself can still be edited or removed
+        for (x, y) in [(4, 2), (11, 5), (7, 3)] {
+            assert_eq!(pixel(&mut loaded.surface, x, y), [0, 0, 0]);
+        }
+        for (x, y) in [(3, 2), (12, 5), (7, 1), (7, 6)] {
+            assert_eq!(pixel(&mut loaded.surface, x, y), [255, 0, 0]);
+        }
+        assert!(matches!(loaded.operations[..], [Operation::Redact { .. }]));
+
+        // The screenshot shown in the editor is left alone
+        assert_eq!(pixel(&mut screenshot, 7, 3), [255, 0, 0]);
+    }
+
+    #[test]
+    fn stamps_are_embedded() {
+        let path = project_dir("stamps");
+        let image = std::env::temp_dir().join(format!("kcshot-{}-stamp.png", std::process::id()));
+        let missing = image.with_extension("missing.png");
+        red_screenshot()
+            .write_to_png(&mut File::create(&image).unwrap())
+            .unwrap();
+
+        let stamp = |file: &PathBuf| Operation::Stamp {
+            rect: Rectangle {
+                x: 0.0,
+                y: 0.0,
+                w: 8.0,
+                h: 8.0,
+            },
+            source: StampSource::File(file.clone()),
+        };
+        let operations = vec![stamp(&image), stamp(&image), stamp(&missing)];
+
+        let project = Project::new(&red_screenshot(), None, operations).unwrap();
+        project.save(&path).unwrap();
+        // The project keeps its own copy, so the stamp survives its image being moved or deleted
+        fs::remove_file(&image).unwrap();
+        let loaded = Project::load(&path).unwrap();
+
+        let files = loaded
+            .operations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::Stamp {
+                    source: StampSource::File(file),
+                    ..
+                } => Some(file.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            [
+                path.join("stamps/0.png"),
+                path.join("stamps/0.png"),
+                missing.clone()
+            ]
+        );
+        assert_eq!(loaded.missing_stamps(), [missing.as_path()]);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -2,7 +2,7 @@ use gtk4::{
     glib, glib::clone, pango, prelude::*, subclass::prelude::ObjectSubclassIsExt, DialogFlags,
     ResponseType,
 };
-use kcshot_data::{colour::Colour, Text};
+use kcshot_data::{colour::Colour, settings::Settings, Text, TextStyle};
 
 mod parse;
 
@@ -16,7 +16,37 @@ impl TextInput {
         glib::Object::builder().property("editor", editor).build()
     }
 
-    fn text(&self) -> String {
+    /// Fills the input in with `text`, which is being edited
+    fn edit(&self, text: &Text) {
+        let imp = self.imp();
+
+        imp.input.get().unwrap().buffer().set_text(&text.markdown);
+        imp.font_button
+            .get()
+            .unwrap()
+            .set_font_desc(&text.font_description);
+        imp.colour.set(Some(text.colour));
+
+        let controls = imp.style_controls.get().unwrap();
+        controls
+            .background
+            .set_active(text.style.background.is_some());
+        controls.padding.set_value(text.style.padding);
+        controls.corner_radius.set_value(text.style.corner_radius);
+        controls.outline_width.set_value(text.style.outline_width);
+        controls.shadow.set_active(text.style.shadow);
+        imp.background_colour.set(text.style.background);
+    }
+
+    /// Makes the text start at `size` points, keeping the font picked in the font button
+    fn set_font_size(&self, size: f64) {
+        let font_button = self.imp().font_button.get().unwrap();
+        let mut font_description = font_button.font_desc().unwrap_or_default();
+        font_description.set_size((size * pango::SCALE as f64) as i32);
+        font_button.set_font_desc(&font_description);
+    }
+
+    fn text(&self) -> Text {
         let input = self.imp().input.get().unwrap();
         let buffer = input.buffer();
 
@@ -24,12 +54,42 @@ impl TextInput {
             .text(&buffer.start_iter(), &buffer.end_iter(), true)
             .to_string();
 
-        parse::markdown2pango(&markdown)
+        Text {
+            string: parse::markdown2pango(&markdown),
+            markdown,
+            font_description: self.font_description(),
+            colour: self.colour(),
+            style: self.style(),
+        }
+    }
+
+    #[track_caller]
+    fn style(&self) -> TextStyle {
+        let imp = self.imp();
+        let controls = imp.style_controls.get().unwrap();
+
+        let background = controls.background.is_active().then(|| {
+            imp.background_colour
+                .get()
+                .unwrap_or_else(|| self.editor().unwrap().primary_colour())
+        });
+
+        TextStyle {
+            background,
+            padding: controls.padding.value(),
+            corner_radius: controls.corner_radius.value(),
+            outline_width: controls.outline_width.value(),
+            shadow: controls.shadow.is_active(),
+        }
     }
 
+    /// Text which is being edited keeps its own colour, while new text uses the secondary colour
     #[track_caller]
     fn colour(&self) -> Colour {
-        self.editor().unwrap().secondary_colour()
+        self.imp()
+            .colour
+            .get()
+            .unwrap_or_else(|| self.editor().unwrap().secondary_colour())
     }
 
     #[track_caller]
@@ -44,8 +104,53 @@ impl TextInput {
 }
 
 pub fn pop_text_dialog_and_get_text(editor: &super::EditorWindow) {
+    pop_text_dialog(
+        editor,
+        "Add text",
+        None,
+        clone!(@weak editor => move |text| {
+            editor.imp().with_image_mut("text dialog response", |image| {
+                image.operation_stack.set_text(text);
+                image.operation_stack.finish_current_operation();
+            });
+        }),
+    );
+}
+
+/// Shows a dialog in which the text of the selected operation can be changed, this is a no-op if
+/// the selected operation doesn't have any text
+pub fn pop_text_edit_dialog(editor: &super::EditorWindow) {
+    let Some(text) = editor
+        .imp()
+        .with_image("text edit dialog", |image| {
+            image.operation_stack.selected_text()
+        })
+        .flatten()
+    else {
+        return;
+    };
+
+    pop_text_dialog(
+        editor,
+        "Edit text",
+        Some(&text),
+        clone!(@weak editor => move |text| {
+            editor.imp().with_image_mut("text edit dialog response", |image| {
+                image.operation_stack.set_selected_text(text);
+            });
+            editor.queue_draw();
+        }),
+    );
+}
+
+fn pop_text_dialog(
+    editor: &super::EditorWindow,
+    title: &str,
+    initial_text: Option<&Text>,
+    on_ok: impl Fn(Text) + 'static,
+) {
     let dialog = gtk4::Dialog::with_buttons(
-        Some("Add text"),
+        Some(title),
         Some(editor),
         DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
         &[],
@@ -62,12 +167,15 @@ pub fn pop_text_dialog_and_get_text(editor: &super::EditorWindow) {
     ok_button.set_margin_end(10);
 
     let text_input = TextInput::new(editor);
+    match initial_text {
+        Some(text) => text_input.edit(text),
+        // This is read from the settings, as the dialog is usually shown from inside
+        // `with_image_mut`
+        None => text_input.set_font_size(Settings::open().text_size()),
+    }
     dialog.content_area().append(&text_input);
 
-    dialog.connect_response(clone!(
-        @weak text_input,
-        @weak editor
-    => move |this, response| {
+    dialog.connect_response(clone!(@weak text_input => move |this, response| {
         this.close();
 
         if response != ResponseType::Ok {
@@ -75,23 +183,17 @@ pub fn pop_text_dialog_and_get_text(editor: &super::EditorWindow) {
             return;
         }
 
-        // NOTE: We create the `Text` outside the `with_image_mut` closure because `TextInput::colour`
-        //       calls `with_image`, which will fail inside `with_image_mut`
-        let text = Text {
-            string: text_input.text(),
-            font_description: text_input.font_description(),
-            colour: text_input.colour(),
-        };
-        editor.imp().with_image_mut("text dialog response", |image| {
-            image.operation_stack.set_text(text);
-            image.operation_stack.finish_current_operation();
-        });
+        // NOTE: We create the `Text` before calling `on_ok` because `TextInput::colour` calls
+        //       `with_image`, which will fail inside the `with_image_mut` used by `on_ok`
+        on_ok(text_input.text());
     }));
 
     dialog.show();
 }
 
 mod underlying {
+    use std::cell::Cell;
+
     use gtk4::{
         glib::{self, Properties, WeakRef},
         prelude::*,
@@ -113,9 +215,25 @@ mod underlying {
 
         pub(super) font_button: OnceCell<gtk4::FontButton>,
         pub(super) input: OnceCell<gtk4::TextView>,
+        /// The colour of the text being edited, new text uses the secondary colour of the editor
+        pub(super) colour: Cell<Option<Colour>>,
+        pub(super) style_controls: OnceCell<StyleControls>,
+        /// The colour of the box behind the text being edited, new boxes use the primary colour of
+        /// the editor
+        pub(super) background_colour: Cell<Option<Colour>>,
         content: OnceCell<gtk4::Box>,
     }
 
+    /// The widgets which describe the [`TextStyle`](kcshot_data::TextStyle) of the text
+    #[derive(Debug)]
+    pub(super) struct StyleControls {
+        pub(super) background: gtk4::CheckButton,
+        pub(super) padding: gtk4::SpinButton,
+        pub(super) corner_radius: gtk4::SpinButton,
+        pub(super) outline_width: gtk4::SpinButton,
+        pub(super) shadow: gtk4::CheckButton,
+    }
+
     #[glib::object_subclass]
     impl ObjectSubclass for TextInput {
         const NAME: &'static str = "KCShotTextInput";
@@ -134,6 +252,9 @@ mod underlying {
             let hbox = gtk4::Box::new(gtk4::Orientation::Horizontal, 2);
             let font_button = place_format_buttons(content, &self.obj());
             self.font_button.set(font_button).unwrap();
+            self.style_controls
+                .set(make_style_controls(content))
+                .unwrap();
             self.input.set(make_text_view(&hbox)).unwrap();
             content.append(&hbox);
 
@@ -187,6 +308,64 @@ mod underlying {
         font_button
     }
 
+    fn make_style_controls(vbox: &gtk4::Box) -> StyleControls {
+        let hbox = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        hbox.set_margin_start(10);
+        hbox.set_margin_end(10);
+        hbox.set_margin_bottom(5);
+
+        let background = gtk4::CheckButton::with_label("Background");
+        background.set_tooltip_text(Some("Draw a box in the primary colour behind the text"));
+        hbox.append(&background);
+
+        let add_spinner = |label: &str, tooltip: &str, value: f64, max: f64| {
+            let label = gtk4::Label::new(Some(label));
+            let spinner = gtk4::SpinButton::with_range(0.0, max, 1.0);
+            spinner.set_value(value);
+            spinner.set_tooltip_text(Some(tooltip));
+            hbox.append(&label);
+            hbox.append(&spinner);
+
+            spinner
+        };
+        let padding = add_spinner(
+            "Padding",
+            "Space between the text and the edges of the box",
+            6.0,
+            100.0,
+        );
+        let corner_radius = add_spinner("Corners", "Radius of the corners of the box", 4.0, 100.0);
+        let outline_width = add_spinner(
+            "Outline",
+            "Width of the outline drawn around the letters",
+            0.0,
+            20.0,
+        );
+
+        // The padding and the corners only matter when there's a box
+        background
+            .bind_property("active", &padding, "sensitive")
+            .sync_create()
+            .build();
+        background
+            .bind_property("active", &corner_radius, "sensitive")
+            .sync_create()
+            .build();
+
+        let shadow = gtk4::CheckButton::with_label("Shadow");
+        hbox.append(&shadow);
+
+        vbox.append(&hbox);
+
+        StyleControls {
+            background,
+            padding,
+            corner_radius,
+            outline_width,
+            shadow,
+        }
+    }
+
     fn make_info_label() -> gtk4::Label {
         let label = gtk4::Label::new(None);
         label.set_markup(r#"You can use CommonMark Markdown or <a href="https://docs.gtk.org/Pango/pango_markup.html" title="Pango markup">Pango markup</a> to format your text."#);
@@ -248,12 +427,10 @@ mod underlying {
             glib::clone!(@weak text_input => move |_this, cairo, _w, _h| {
                 cairo.set_operator(cairo::Operator::Over);
 
-                let editor = text_input.editor().unwrap();
-
-                let secondary_colour = editor.secondary_colour();
-                if secondary_colour.alpha != 0 {
+                let colour = text_input.colour();
+                if colour.alpha != 0 {
                     cairo.rectangle(0.0, 0.0, SIZEF, SIZEF);
-                    cairo.set_source_colour(secondary_colour);
+                    cairo.set_source_colour(colour);
                     log_if_err!(cairo.fill());
                 } else {
                     // Instead of drawing nothing (what a fully transparent colour is) we draw a
@@ -292,8 +469,15 @@ mod underlying {
                 let editor = text_input.editor().unwrap();
                 let dialog = colourchooser::dialog(&editor);
 
-                dialog.connect_response(glib::clone!(@weak drawing_area => move |editor, colour| {
-                    editor.set_secondary_colour(colour);
+                dialog.connect_response(glib::clone!(
+                    @weak drawing_area,
+                    @weak text_input
+                => move |editor, colour| {
+                    if text_input.imp().colour.get().is_some() {
+                        text_input.imp().colour.set(Some(colour));
+                    } else {
+                        editor.set_secondary_colour(colour);
+                    }
                     drawing_area.queue_draw();
                 }));
 
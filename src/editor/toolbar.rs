@@ -1,4 +1,6 @@
-use gtk4::{glib, traits::WidgetExt};
+use gtk4::{glib, subclass::prelude::ObjectSubclassIsExt, traits::WidgetExt};
+
+use super::operations::{CapStyle, DashStyle, JoinStyle, StrokeStyle, Tool};
 
 glib::wrapper! {
     pub struct ToolbarWidget(ObjectSubclass<underlying::ToolbarWidget>)
@@ -20,10 +22,62 @@ impl ToolbarWidget {
 
         obj
     }
+
+    /// Shows the number the next bubble will get in the bubble options
+    pub fn show_next_bubble_number(&self, number: i32) {
+        if let Some(spinner) = self.imp().next_bubble_number.get() {
+            spinner.set_value(number as f64);
+        }
+    }
+
+    /// Shows the line width of the tool which was just picked
+    pub fn show_line_width(&self, line_width: f64) {
+        if let Some(spinner) = self.imp().line_width.get() {
+            spinner.set_value(line_width);
+        }
+    }
+
+    /// Shows `stroke` in the stroke options, which updates the editor the same way picking it by
+    /// hand does
+    pub fn show_stroke_style(&self, stroke: StrokeStyle) {
+        let imp = self.imp();
+        if let Some(dropdown) = imp.dash.get() {
+            dropdown.set_selected(underlying::position_of(&DashStyle::ALL, stroke.dash));
+        }
+        if let Some(dropdown) = imp.cap.get() {
+            dropdown.set_selected(underlying::position_of(&CapStyle::ALL, stroke.cap));
+        }
+        if let Some(dropdown) = imp.join.get() {
+            dropdown.set_selected(underlying::position_of(&JoinStyle::ALL, stroke.join));
+        }
+        if let Some(scale) = imp.opacity.get() {
+            scale.set_value(stroke.opacity * 100.0);
+        }
+    }
+
+    /// Moves the spinner holding the size `tool` draws with by `steps` of its page increments,
+    /// which updates the editor the same way picking the size by hand does
+    pub fn adjust_tool_size(&self, tool: Tool, steps: f64) {
+        let imp = self.imp();
+        let spinner = match tool {
+            Tool::AutoincrementBubble => imp.bubble_radius.get(),
+            Tool::Text => imp.text_size.get(),
+            tool if tool.has_line_width() || tool == Tool::Select => imp.line_width.get(),
+            _ => None,
+        };
+
+        if let Some(spinner) = spinner {
+            let (_, page) = spinner.increments();
+            spinner.set_value(spinner.value() + steps * page);
+        }
+    }
 }
 
 mod underlying {
-    use std::cell::Cell;
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
 
     use gtk4::{
         glib::{self, clone, ParamSpec, Properties, WeakRef},
@@ -31,12 +85,18 @@ mod underlying {
         subclass::prelude::*,
         Inhibit,
     };
-    use kcshot_data::colour::Colour;
+    use kcshot_data::{colour::Colour, settings::Settings};
     use once_cell::unsync::OnceCell;
 
     use crate::{
         editor::{
-            self, colourchooser, operations::Tool, underlying::EditorWindow as EditorWindowImp,
+            self, colourchooser, cropdialog,
+            operations::{
+                user_stamps, ArrowheadStyle, BundledStamp, CapStyle, CropConstraint, DashStyle,
+                FilterKind, InsetShape, JoinStyle, MeasureKind, NumberStyle, SpotlightEffect,
+                SpotlightShape, StampSource, Tool, BUILTIN_CROP_PRESETS,
+            },
+            underlying::EditorWindow as EditorWindowImp,
             utils::CairoExt,
         },
         kcshot::KCShot,
@@ -52,6 +112,14 @@ mod underlying {
         editing_started_with_cropping: Cell<bool>,
 
         buttons: OnceCell<Vec<gtk4::ToggleButton>>,
+        pub(super) next_bubble_number: OnceCell<gtk4::SpinButton>,
+        pub(super) line_width: OnceCell<gtk4::SpinButton>,
+        pub(super) text_size: OnceCell<gtk4::SpinButton>,
+        pub(super) bubble_radius: OnceCell<gtk4::SpinButton>,
+        pub(super) dash: OnceCell<gtk4::DropDown>,
+        pub(super) cap: OnceCell<gtk4::DropDown>,
+        pub(super) join: OnceCell<gtk4::DropDown>,
+        pub(super) opacity: OnceCell<gtk4::Scale>,
     }
 
     #[glib::object_subclass]
@@ -75,6 +143,9 @@ mod underlying {
                 editor.set_line_width(this.value());
             }));
             line_width_spinner.set_visible(false);
+            self.line_width
+                .set(line_width_spinner.clone())
+                .expect("construct should only be called once");
 
             let box_: gtk4::Box = self.obj().to_owned().upcast();
             let group_source_tool = if self.editing_started_with_cropping.get() {
@@ -90,21 +161,152 @@ mod underlying {
             primary_colour_button.set_tooltip_text(Some("Set primary colour"));
             let secondary_colour_button = Self::make_secondary_colour_button(editor.clone());
             secondary_colour_button.set_tooltip_text(Some("Set secondary colour"));
+            let highlight_colour_button = Self::make_highlight_colour_button(editor.clone());
+            highlight_colour_button.set_tooltip_text(Some("Set highlight colour"));
 
             #[rustfmt::skip]
             let mut buttons = vec![
                 make_tool_button(Tool::Pencil, &box_, &editor, Some(&group_source), Some(&line_width_spinner), None, Some(&secondary_colour_button)),
                 make_tool_button(Tool::Line, &box_, &editor, Some(&group_source), Some(&line_width_spinner), None, Some(&secondary_colour_button)),
                 make_tool_button(Tool::Arrow, &box_, &editor, Some(&group_source), Some(&line_width_spinner), None, Some(&secondary_colour_button)),
+                make_tool_button(Tool::DoubleArrow, &box_, &editor, Some(&group_source), Some(&line_width_spinner), None, Some(&secondary_colour_button)),
+                make_tool_button(Tool::CurvedArrow, &box_, &editor, Some(&group_source), Some(&line_width_spinner), None, Some(&secondary_colour_button)),
                 make_tool_button(Tool::Rectangle, &box_, &editor, Some(&group_source), Some(&line_width_spinner), Some(&primary_colour_button), Some(&secondary_colour_button)),
+                make_tool_button(Tool::RoundedRectangle, &box_, &editor, Some(&group_source), Some(&line_width_spinner), Some(&primary_colour_button), Some(&secondary_colour_button)),
                 make_tool_button(Tool::Highlight, &box_, &editor, Some(&group_source), None, None, None),
+                make_tool_button(Tool::Highlighter, &box_, &editor, Some(&group_source), Some(&line_width_spinner), None, None),
                 make_tool_button(Tool::Ellipse, &box_, &editor, Some(&group_source), Some(&line_width_spinner), Some(&primary_colour_button), Some(&secondary_colour_button)),
+                make_tool_button(Tool::Polyline, &box_, &editor, Some(&group_source), Some(&line_width_spinner), Some(&primary_colour_button), Some(&secondary_colour_button)),
                 make_tool_button(Tool::Pixelate, &box_, &editor, Some(&group_source), None, None, None),
                 make_tool_button(Tool::Blur, &box_, &editor, Some(&group_source), None, None, None),
+                make_tool_button(Tool::Redact, &box_, &editor, Some(&group_source), None, Some(&primary_colour_button), None),
+                make_tool_button(Tool::Spotlight, &box_, &editor, Some(&group_source), None, None, None),
+                make_tool_button(Tool::Filter, &box_, &editor, Some(&group_source), None, None, None),
+                make_tool_button(Tool::Magnify, &box_, &editor, Some(&group_source), Some(&line_width_spinner), None, Some(&secondary_colour_button)),
+                make_tool_button(Tool::Stamp, &box_, &editor, Some(&group_source), None, None, None),
                 make_tool_button(Tool::AutoincrementBubble, &box_, &editor, Some(&group_source), None, Some(&primary_colour_button), Some(&secondary_colour_button)),
                 make_tool_button(Tool::Text, &box_, &editor, Some(&group_source), None, None, None),
+                make_tool_button(Tool::Callout, &box_, &editor, Some(&group_source), Some(&line_width_spinner), Some(&primary_colour_button), Some(&secondary_colour_button)),
+                make_tool_button(Tool::Measure, &box_, &editor, Some(&group_source), None, None, Some(&secondary_colour_button)),
+                make_tool_button(Tool::Select, &box_, &editor, Some(&group_source), Some(&line_width_spinner), Some(&primary_colour_button), Some(&secondary_colour_button)),
             ];
 
+            let measure_options = Self::make_measure_options(editor.clone());
+            if let Some((measure_button, _)) =
+                buttons.iter().find(|(_, tool)| *tool == Tool::Measure)
+            {
+                measure_button.connect_toggled(clone!(@weak measure_options => move |this| {
+                    measure_options.set_visible(this.is_active());
+                }));
+            }
+
+            let (blur_scale, pixelate_scale) = Self::make_effect_scales(editor.clone());
+            for (tool, scale) in [(Tool::Blur, &blur_scale), (Tool::Pixelate, &pixelate_scale)] {
+                if let Some((button, _)) =
+                    buttons.iter().find(|(_, button_tool)| *button_tool == tool)
+                {
+                    button.connect_toggled(clone!(@weak scale => move |this| {
+                        scale.set_visible(this.is_active());
+                    }));
+                }
+            }
+
+            let spotlight_options = Self::make_spotlight_options(editor.clone());
+            if let Some((spotlight_button, _)) =
+                buttons.iter().find(|(_, tool)| *tool == Tool::Spotlight)
+            {
+                spotlight_button.connect_toggled(clone!(@weak spotlight_options => move |this| {
+                    spotlight_options.set_visible(this.is_active());
+                }));
+            }
+
+            for tool in [Tool::Highlight, Tool::Highlighter] {
+                if let Some((button, _)) =
+                    buttons.iter().find(|(_, button_tool)| *button_tool == tool)
+                {
+                    button.connect_toggled(clone!(@weak highlight_colour_button => move |this| {
+                        highlight_colour_button.set_visible(this.is_active());
+                    }));
+                }
+            }
+
+            let arrowhead_dropdown = Self::make_arrowhead_dropdown(editor.clone());
+            let corner_radius_spinner = Self::make_corner_radius_spinner(editor.clone());
+            let text_size_spinner = Self::make_text_size_spinner(editor.clone());
+            self.text_size
+                .set(text_size_spinner.clone())
+                .expect("construct should only be called once");
+            for (tool, options) in [
+                (Tool::Arrow, arrowhead_dropdown.upcast_ref::<gtk4::Widget>()),
+                (Tool::DoubleArrow, arrowhead_dropdown.upcast_ref()),
+                (Tool::CurvedArrow, arrowhead_dropdown.upcast_ref()),
+                (Tool::RoundedRectangle, corner_radius_spinner.upcast_ref()),
+                (Tool::Text, text_size_spinner.upcast_ref()),
+                (Tool::Callout, text_size_spinner.upcast_ref()),
+            ] {
+                if let Some((button, _)) =
+                    buttons.iter().find(|(_, button_tool)| *button_tool == tool)
+                {
+                    button.connect_toggled(clone!(@weak options => move |this| {
+                        options.set_visible(this.is_active());
+                    }));
+                }
+            }
+
+            let stroke_options = self.make_stroke_options(editor.clone());
+            for tool in [
+                Tool::Line,
+                Tool::Arrow,
+                Tool::DoubleArrow,
+                Tool::CurvedArrow,
+                Tool::Rectangle,
+                Tool::RoundedRectangle,
+                Tool::Ellipse,
+                Tool::Polyline,
+                Tool::Pencil,
+            ] {
+                if let Some((button, _)) =
+                    buttons.iter().find(|(_, button_tool)| *button_tool == tool)
+                {
+                    button.connect_toggled(clone!(@weak stroke_options => move |this| {
+                        stroke_options.set_visible(this.is_active());
+                    }));
+                }
+            }
+
+            let filter_options = Self::make_filter_options(editor.clone());
+            let magnify_options = Self::make_magnify_options(editor.clone());
+            let stamp_options = Self::make_stamp_options(editor.clone());
+            for (tool, options) in [
+                (Tool::Filter, &filter_options),
+                (Tool::Magnify, &magnify_options),
+                (Tool::Stamp, &stamp_options),
+            ] {
+                if let Some((button, _)) =
+                    buttons.iter().find(|(_, button_tool)| *button_tool == tool)
+                {
+                    button.connect_toggled(clone!(@weak options => move |this| {
+                        options.set_visible(this.is_active());
+                    }));
+                }
+            }
+
+            let bubble_options = self.make_bubble_options(editor.clone());
+            let toolbar = self.obj().to_owned();
+            if let Some((bubble_button, _)) = buttons
+                .iter()
+                .find(|(_, tool)| *tool == Tool::AutoincrementBubble)
+            {
+                bubble_button.connect_toggled(
+                    clone!(@weak editor, @weak bubble_options, @weak toolbar => move |this| {
+                        bubble_options.set_visible(this.is_active());
+                        if let Some(number) = editor.next_bubble_number() {
+                            toolbar.show_next_bubble_number(number);
+                        }
+                    }),
+                );
+            }
+
             if self.editing_started_with_cropping.get() {
                 buttons[0].0.set_active(true);
             }
@@ -115,7 +317,21 @@ mod underlying {
 
             box_.append(&primary_colour_button);
             box_.append(&secondary_colour_button);
+            box_.append(&highlight_colour_button);
             box_.append(&line_width_spinner);
+            box_.append(&measure_options);
+            box_.append(&arrowhead_dropdown);
+            box_.append(&corner_radius_spinner);
+            box_.append(&text_size_spinner);
+            box_.append(&stroke_options);
+            box_.append(&blur_scale);
+            box_.append(&pixelate_scale);
+            box_.append(&spotlight_options);
+            box_.append(&filter_options);
+            box_.append(&magnify_options);
+            box_.append(&stamp_options);
+            box_.append(&bubble_options);
+            box_.append(&Self::make_crop_preset_dropdown(editor.clone()));
 
             buttons.insert(0, (group_source, group_source_tool));
 
@@ -202,7 +418,11 @@ mod underlying {
 
             }));
 
-            Self::make_button::<true>(drawing_area, editor)
+            Self::make_button(
+                drawing_area,
+                editor,
+                editor::EditorWindow::set_primary_colour,
+            )
         }
 
         fn make_secondary_colour_button(editor: editor::EditorWindow) -> gtk4::Button {
@@ -232,12 +452,594 @@ mod underlying {
 
             }));
 
-            Self::make_button::<false>(drawing_area, editor)
+            Self::make_button(
+                drawing_area,
+                editor,
+                editor::EditorWindow::set_secondary_colour,
+            )
         }
 
-        fn make_button<const IS_PRIMARY: bool>(
+        fn make_highlight_colour_button(editor: editor::EditorWindow) -> gtk4::Button {
+            let drawing_area = gtk4::DrawingArea::new();
+            drawing_area.set_accessible_role(gtk4::AccessibleRole::Img);
+            drawing_area.set_size_request(20, 20);
+            drawing_area.set_draw_func(clone!(@weak editor =>  move |_this, cairo, _w, _h| {
+                cairo.set_operator(cairo::Operator::Over);
+
+                // Highlights are usually translucent, so they're shown over a black bar on white,
+                // like they would be over text
+                cairo.set_source_rgb(1.0, 1.0, 1.0);
+                cairo.rectangle(0.0, 0.0, 20.0, 20.0);
+                log_if_err!(cairo.fill());
+                cairo.set_source_colour(Colour::BLACK);
+                cairo.rectangle(3.0, 8.0, 14.0, 4.0);
+                log_if_err!(cairo.fill());
+
+                cairo.set_source_colour(editor.highlight_colour());
+                cairo.rectangle(0.0, 0.0, 20.0, 20.0);
+                log_if_err!(cairo.fill());
+
+                cairo.set_source_colour(Colour::BLACK);
+                cairo.rectangle(0.0, 0.0, 20.0, 20.0);
+                cairo.set_line_width(1.0);
+                log_if_err!(cairo.stroke());
+            }));
+
+            Self::make_button(
+                drawing_area,
+                editor,
+                editor::EditorWindow::set_highlight_colour,
+            )
+        }
+
+        fn make_crop_preset_dropdown(editor: editor::EditorWindow) -> gtk4::DropDown {
+            const ADD_CUSTOM_SIZE: &str = "Add size…";
+
+            let settings = Settings::open();
+            let presets = BUILTIN_CROP_PRESETS
+                .iter()
+                .map(|&preset| preset.to_owned())
+                .chain(settings.custom_crop_sizes())
+                .filter_map(|preset| CropConstraint::from_preset(&preset))
+                .collect::<Vec<_>>();
+
+            let labels = presets
+                .iter()
+                .map(ToString::to_string)
+                .chain(std::iter::once(ADD_CUSTOM_SIZE.to_owned()))
+                .collect::<Vec<_>>();
+            let model =
+                gtk4::StringList::new(&labels.iter().map(String::as_str).collect::<Vec<_>>());
+
+            let dropdown = gtk4::DropDown::new(Some(model.clone()), gtk4::Expression::NONE);
+            dropdown.set_tooltip_text(Some(
+                "Shape of the crop region (hold Shift while dragging it out for a square)",
+            ));
+
+            let current = CropConstraint::from_preset(&settings.crop_preset()).unwrap_or_default();
+            let selected = presets
+                .iter()
+                .position(|preset| *preset == current)
+                .unwrap_or(0);
+            dropdown.set_selected(selected as u32);
+
+            let presets = Rc::new(RefCell::new(presets));
+            let last_selected = Rc::new(Cell::new(selected as u32));
+            dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                let selected = this.selected();
+                if let Some(&preset) = presets.borrow().get(selected as usize) {
+                    last_selected.set(selected);
+                    editor.set_crop_constraint(preset);
+                    return;
+                }
+
+                // The last item is used for adding new sizes
+                cropdialog::pop_crop_size_dialog(&editor, clone!(
+                    @weak this,
+                    @weak model,
+                    @strong presets,
+                    @strong last_selected
+                => move |size| {
+                    let Some((width, height)) = size else {
+                        this.set_selected(last_selected.get());
+                        return;
+                    };
+
+                    let preset = CropConstraint::FixedSize { width, height };
+                    let settings = Settings::open();
+                    let mut custom_sizes = settings.custom_crop_sizes();
+                    custom_sizes.push(preset.to_preset());
+                    let custom_sizes = custom_sizes.iter().map(String::as_str).collect::<Vec<_>>();
+                    if let Err(why) = settings.try_set_custom_crop_sizes(&custom_sizes) {
+                        tracing::warn!("Failed to update `custom-crop-sizes` setting value: {why}");
+                    }
+
+                    let position = {
+                        let mut presets = presets.borrow_mut();
+                        presets.push(preset);
+                        presets.len() as u32 - 1
+                    };
+                    model.splice(position, 0, &[&preset.to_string()]);
+                    // This goes through the handler above, which applies the new preset
+                    this.set_selected(position);
+                }));
+            }));
+
+            dropdown
+        }
+
+        /// Makes the widgets which control what the measure tool measures and whether measurements
+        /// are kept
+        fn make_measure_options(editor: editor::EditorWindow) -> gtk4::Box {
+            let kind_dropdown = gtk4::DropDown::from_strings(&["Distance", "Size"]);
+            kind_dropdown.set_selected(u32::from(
+                editor.tool_options().measure_kind == MeasureKind::Size,
+            ));
+            kind_dropdown.set_tooltip_text(Some("What is measured"));
+            kind_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                let kind = if this.selected() == 1 {
+                    MeasureKind::Size
+                } else {
+                    MeasureKind::Distance
+                };
+                editor.set_measure_kind(kind);
+            }));
+
+            let keep_button = gtk4::CheckButton::with_label("Keep");
+            keep_button.set_tooltip_text(Some(
+                "Add measurements to the screenshot, instead of only showing the last one",
+            ));
+            keep_button.set_active(Settings::open().keep_measurements());
+            keep_button.connect_toggled(clone!(@weak editor => move |this| {
+                editor.set_keep_measurements(this.is_active());
+            }));
+
+            let options = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .visible(false)
+                .build();
+            options.append(&kind_dropdown);
+            options.append(&keep_button);
+
+            options
+        }
+
+        /// Makes the sliders with which the blur radius and the size of pixelation blocks are
+        /// picked
+        fn make_effect_scales(editor: editor::EditorWindow) -> (gtk4::Scale, gtk4::Scale) {
+            let settings = Settings::open();
+            let make_scale = |min, max, value, tooltip| {
+                let scale = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, min, max, 1.0);
+                scale.set_value(value);
+                scale.set_digits(0);
+                scale.set_draw_value(true);
+                scale.set_width_request(120);
+                scale.set_tooltip_text(Some(tooltip));
+                scale.set_visible(false);
+                scale
+            };
+
+            let blur_scale = make_scale(1.0, 50.0, settings.blur_radius(), "Blur radius");
+            blur_scale.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_blur_radius(this.value() as f32);
+            }));
+
+            let pixelate_scale = make_scale(
+                2.0,
+                64.0,
+                settings.pixelate_size() as f64,
+                "Size of the pixelation blocks",
+            );
+            pixelate_scale.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_pixelate_size(this.value() as u32);
+            }));
+
+            (blur_scale, pixelate_scale)
+        }
+
+        fn make_spotlight_options(editor: editor::EditorWindow) -> gtk4::Box {
+            let style = editor.tool_options().spotlight_style;
+
+            let shape_dropdown = gtk4::DropDown::from_strings(&["Rectangle", "Ellipse"]);
+            shape_dropdown.set_selected(u32::from(style.shape == SpotlightShape::Ellipse));
+            shape_dropdown.set_tooltip_text(Some("The shape of the region in focus"));
+            shape_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                let shape = if this.selected() == 1 {
+                    SpotlightShape::Ellipse
+                } else {
+                    SpotlightShape::Rectangle
+                };
+                editor.set_spotlight_shape(shape);
+            }));
+
+            let effect_dropdown = gtk4::DropDown::from_strings(&["Darken", "Blur"]);
+            effect_dropdown.set_selected(u32::from(style.effect == SpotlightEffect::Blur));
+            effect_dropdown.set_tooltip_text(Some("What is done to everything else"));
+            effect_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                let effect = if this.selected() == 1 {
+                    SpotlightEffect::Blur
+                } else {
+                    SpotlightEffect::Darken
+                };
+                editor.set_spotlight_effect(effect);
+            }));
+
+            let options = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .visible(false)
+                .build();
+            options.append(&shape_dropdown);
+            options.append(&effect_dropdown);
+
+            options
+        }
+
+        fn make_arrowhead_dropdown(editor: editor::EditorWindow) -> gtk4::DropDown {
+            let labels = ArrowheadStyle::ALL.map(ArrowheadStyle::label);
+            let dropdown = gtk4::DropDown::from_strings(&labels);
+            dropdown.set_selected(position_of(
+                &ArrowheadStyle::ALL,
+                editor.tool_options().arrowhead,
+            ));
+            dropdown.set_tooltip_text(Some("The style of the arrowheads"));
+            dropdown.set_visible(false);
+            dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                if let Some(style) = ArrowheadStyle::ALL.get(this.selected() as usize) {
+                    editor.set_arrowhead_style(*style);
+                }
+            }));
+
+            dropdown
+        }
+
+        fn make_corner_radius_spinner(editor: editor::EditorWindow) -> gtk4::SpinButton {
+            let spinner = gtk4::SpinButton::with_range(1.0, 200.0, 1.0);
+            spinner.set_value(editor.corner_radius());
+            spinner.set_tooltip_text(Some("Corner radius"));
+            spinner.set_visible(false);
+            spinner.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_corner_radius(this.value());
+            }));
+
+            spinner
+        }
+
+        fn make_text_size_spinner(editor: editor::EditorWindow) -> gtk4::SpinButton {
+            let settings = Settings::open();
+            let adjustment = gtk4::Adjustment::new(settings.text_size(), 4.0, 400.0, 1.0, 4.0, 0.0);
+            let spinner = gtk4::SpinButton::new(Some(&adjustment), 1.0, 0);
+            spinner.set_tooltip_text(Some("Text size"));
+            spinner.set_visible(false);
+            spinner.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_text_size(this.value());
+            }));
+
+            spinner
+        }
+
+        fn make_stroke_options(&self, editor: editor::EditorWindow) -> gtk4::Box {
+            let stroke = editor.tool_options().stroke;
+
+            let labels = DashStyle::ALL.map(DashStyle::label);
+            let dash_dropdown = gtk4::DropDown::from_strings(&labels);
+            dash_dropdown.set_selected(position_of(&DashStyle::ALL, stroke.dash));
+            dash_dropdown.set_tooltip_text(Some("Line style"));
+            dash_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                if let Some(dash) = DashStyle::ALL.get(this.selected() as usize) {
+                    editor.edit_stroke_style("dash", |stroke| stroke.dash = *dash);
+                }
+            }));
+
+            let labels = CapStyle::ALL.map(CapStyle::label);
+            let cap_dropdown = gtk4::DropDown::from_strings(&labels);
+            cap_dropdown.set_selected(position_of(&CapStyle::ALL, stroke.cap));
+            cap_dropdown.set_tooltip_text(Some("How the ends of lines look"));
+            cap_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                if let Some(cap) = CapStyle::ALL.get(this.selected() as usize) {
+                    editor.edit_stroke_style("cap", |stroke| stroke.cap = *cap);
+                }
+            }));
+
+            let labels = JoinStyle::ALL.map(JoinStyle::label);
+            let join_dropdown = gtk4::DropDown::from_strings(&labels);
+            join_dropdown.set_selected(position_of(&JoinStyle::ALL, stroke.join));
+            join_dropdown.set_tooltip_text(Some("How the corners of lines look"));
+            join_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                if let Some(join) = JoinStyle::ALL.get(this.selected() as usize) {
+                    editor.edit_stroke_style("join", |stroke| stroke.join = *join);
+                }
+            }));
+
+            // The scale is in percents, which are easier to pick than fractions
+            let opacity_scale =
+                gtk4::Scale::with_range(gtk4::Orientation::Horizontal, 5.0, 100.0, 5.0);
+            opacity_scale.set_value(stroke.opacity * 100.0);
+            opacity_scale.set_digits(0);
+            opacity_scale.set_draw_value(true);
+            opacity_scale.set_width_request(100);
+            opacity_scale.set_tooltip_text(Some("Opacity"));
+            opacity_scale.connect_value_changed(clone!(@weak editor => move |this| {
+                let opacity = this.value() / 100.0;
+                editor.edit_stroke_style("opacity", |stroke| stroke.opacity = opacity);
+            }));
+
+            let options = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .visible(false)
+                .build();
+            options.append(&dash_dropdown);
+            options.append(&cap_dropdown);
+            options.append(&join_dropdown);
+            options.append(&opacity_scale);
+
+            let set = "construct should only be called once";
+            self.dash.set(dash_dropdown).expect(set);
+            self.cap.set(cap_dropdown).expect(set);
+            self.join.set(join_dropdown).expect(set);
+            self.opacity.set(opacity_scale).expect(set);
+
+            options
+        }
+
+        fn make_filter_options(editor: editor::EditorWindow) -> gtk4::Box {
+            let filter = editor.tool_options().filter;
+            let make_scale = |min, max, value, tooltip, kind| {
+                let scale = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, min, max, 1.0);
+                scale.set_value(value);
+                scale.set_digits(0);
+                scale.set_draw_value(true);
+                scale.set_width_request(100);
+                scale.set_tooltip_text(Some(tooltip));
+                scale.set_visible(filter.kind == kind);
+                scale
+            };
+
+            // The scales are in percents, which are easier to pick than fractions
+            let brightness_scale = make_scale(
+                -100.0,
+                100.0,
+                filter.brightness * 100.0,
+                "Brightness",
+                FilterKind::BrightnessContrast,
+            );
+            brightness_scale.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_filter_brightness(this.value() / 100.0);
+            }));
+            let contrast_scale = make_scale(
+                0.0,
+                300.0,
+                filter.contrast * 100.0,
+                "Contrast",
+                FilterKind::BrightnessContrast,
+            );
+            contrast_scale.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_filter_contrast(this.value() / 100.0);
+            }));
+            let sharpen_scale = make_scale(
+                10.0,
+                500.0,
+                filter.sharpen_amount * 100.0,
+                "Sharpening strength",
+                FilterKind::Sharpen,
+            );
+            sharpen_scale.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_sharpen_amount(this.value() / 100.0);
+            }));
+
+            let labels = FilterKind::ALL.map(FilterKind::label);
+            let kind_dropdown = gtk4::DropDown::from_strings(&labels);
+            kind_dropdown.set_selected(position_of(&FilterKind::ALL, filter.kind));
+            kind_dropdown.set_tooltip_text(Some("The filter applied to the region"));
+            kind_dropdown.connect_selected_notify(
+                clone!(@weak editor, @weak brightness_scale, @weak contrast_scale, @weak sharpen_scale => move |this| {
+                    let kind = FilterKind::ALL
+                        .get(this.selected() as usize)
+                        .copied()
+                        .unwrap_or_default();
+                    editor.set_filter_kind(kind);
+
+                    brightness_scale.set_visible(kind == FilterKind::BrightnessContrast);
+                    contrast_scale.set_visible(kind == FilterKind::BrightnessContrast);
+                    sharpen_scale.set_visible(kind == FilterKind::Sharpen);
+                }),
+            );
+
+            let options = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .visible(false)
+                .build();
+            options.append(&kind_dropdown);
+            options.append(&brightness_scale);
+            options.append(&contrast_scale);
+            options.append(&sharpen_scale);
+
+            options
+        }
+
+        fn make_magnify_options(editor: editor::EditorWindow) -> gtk4::Box {
+            let magnify = editor.tool_options().magnify;
+
+            let zoom_spinner = gtk4::SpinButton::with_range(1.5, 8.0, 0.5);
+            zoom_spinner.set_value(magnify.zoom);
+            zoom_spinner.set_digits(1);
+            zoom_spinner.set_tooltip_text(Some("How many times the region is magnified"));
+            zoom_spinner.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_magnify_zoom(this.value());
+            }));
+
+            let shape_dropdown = gtk4::DropDown::from_strings(&["Circle", "Box"]);
+            shape_dropdown.set_selected(u32::from(magnify.shape == InsetShape::Box));
+            shape_dropdown.set_tooltip_text(Some("The shape of the magnified inset"));
+            shape_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                let shape = if this.selected() == 1 {
+                    InsetShape::Box
+                } else {
+                    InsetShape::Circle
+                };
+                editor.set_inset_shape(shape);
+            }));
+
+            let options = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .visible(false)
+                .build();
+            options.append(&zoom_spinner);
+            options.append(&shape_dropdown);
+
+            options
+        }
+
+        fn make_stamp_options(editor: editor::EditorWindow) -> gtk4::Box {
+            // The stamps folder is only looked at when the editor is opened, so images added to
+            // it show up in the next one
+            let stamps = BundledStamp::ALL
+                .into_iter()
+                .map(StampSource::Bundled)
+                .chain(user_stamps())
+                .collect::<Vec<_>>();
+            let names = stamps.iter().map(StampSource::label).collect::<Vec<_>>();
+            let labels = names.iter().map(String::as_str).collect::<Vec<_>>();
+            let stamps = Rc::new(stamps);
+
+            let dropdown = gtk4::DropDown::from_strings(&labels);
+            dropdown.set_tooltip_text(Some("The image to place"));
+
+            let emoji_entry = gtk4::Entry::new();
+            emoji_entry.set_placeholder_text(Some("Emoji"));
+            emoji_entry.set_tooltip_text(Some("Emoji or short text to place instead of the image"));
+            emoji_entry.set_show_emoji_icon(true);
+            emoji_entry.set_max_width_chars(6);
+            emoji_entry.connect_changed(
+                clone!(@weak editor, @weak dropdown, @strong stamps => move |this| {
+                    let text = this.text();
+                    let text = text.trim();
+                    if !text.is_empty() {
+                        editor.set_stamp(StampSource::Emoji(text.to_owned()));
+                    } else if let Some(stamp) = stamps.get(dropdown.selected() as usize) {
+                        editor.set_stamp(stamp.clone());
+                    }
+                }),
+            );
+
+            dropdown.connect_selected_notify(
+                clone!(@weak editor, @weak emoji_entry, @strong stamps => move |this| {
+                    // Clearing the emoji makes its handler pick the image up
+                    if !emoji_entry.text().is_empty() {
+                        emoji_entry.set_text("");
+                    } else if let Some(stamp) = stamps.get(this.selected() as usize) {
+                        editor.set_stamp(stamp.clone());
+                    }
+                }),
+            );
+
+            let options = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .visible(false)
+                .build();
+            options.append(&dropdown);
+            options.append(&emoji_entry);
+
+            options
+        }
+
+        fn make_bubble_options(&self, editor: editor::EditorWindow) -> gtk4::Box {
+            let settings = Settings::open();
+
+            let adjustment =
+                gtk4::Adjustment::new(settings.bubble_radius(), 8.0, 200.0, 1.0, 5.0, 0.0);
+            let radius_spinner = gtk4::SpinButton::new(Some(&adjustment), 1.0, 0);
+            radius_spinner.set_tooltip_text(Some("Bubble size"));
+            radius_spinner.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_bubble_radius(this.value());
+            }));
+
+            let labels = NumberStyle::ALL.map(NumberStyle::label);
+            let style_dropdown = gtk4::DropDown::from_strings(&labels);
+            style_dropdown.set_tooltip_text(Some("How bubbles are numbered"));
+            let current =
+                NumberStyle::from_name(&settings.bubble_number_style()).unwrap_or_default();
+            if let Some(position) = NumberStyle::ALL.iter().position(|style| *style == current) {
+                style_dropdown.set_selected(position as u32);
+            }
+            style_dropdown.connect_selected_notify(clone!(@weak editor => move |this| {
+                if let Some(style) = NumberStyle::ALL.get(this.selected() as usize) {
+                    editor.set_bubble_number_style(*style);
+                }
+            }));
+
+            let start_number = settings.bubble_start_number();
+            let adjustment =
+                gtk4::Adjustment::new(start_number as f64, 0.0, 9999.0, 1.0, 10.0, 0.0);
+            let start_spinner = gtk4::SpinButton::new(Some(&adjustment), 1.0, 0);
+            start_spinner.set_tooltip_text(Some("The number of the first bubble"));
+
+            let adjustment =
+                gtk4::Adjustment::new(start_number as f64, 0.0, 9999.0, 1.0, 10.0, 0.0);
+            let next_spinner = gtk4::SpinButton::new(Some(&adjustment), 1.0, 0);
+            next_spinner.set_tooltip_text(Some("The number of the next bubble"));
+            next_spinner.connect_value_changed(clone!(@weak editor => move |this| {
+                editor.set_next_bubble_number(this.value_as_int());
+            }));
+
+            start_spinner.connect_value_changed(
+                clone!(@weak editor, @weak next_spinner => move |this| {
+                    editor.set_bubble_start_number(this.value_as_int());
+                    if let Some(number) = editor.next_bubble_number() {
+                        next_spinner.set_value(number as f64);
+                    }
+                }),
+            );
+
+            let reset_button = gtk4::Button::with_label("Reset");
+            reset_button.set_tooltip_text(Some("Number the next bubble from the start again"));
+            reset_button.connect_clicked(clone!(@weak editor, @weak next_spinner => move |_| {
+                if let Some(number) = editor.reset_bubble_numbering() {
+                    next_spinner.set_value(number as f64);
+                }
+            }));
+
+            let auto_size_button = gtk4::CheckButton::with_label("Fit");
+            auto_size_button
+                .set_tooltip_text(Some("Grow bubbles so that their numbers fit inside them"));
+            auto_size_button.set_active(settings.bubble_auto_size());
+            auto_size_button.connect_toggled(clone!(@weak editor => move |this| {
+                editor.set_bubble_auto_size(this.is_active());
+            }));
+
+            self.next_bubble_number
+                .set(next_spinner.clone())
+                .expect("make_bubble_options should only be called once");
+            self.bubble_radius
+                .set(radius_spinner.clone())
+                .expect("make_bubble_options should only be called once");
+
+            let options = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(6)
+                .visible(false)
+                .build();
+            options.append(&radius_spinner);
+            options.append(&style_dropdown);
+            options.append(&gtk4::Label::new(Some("Start")));
+            options.append(&start_spinner);
+            options.append(&gtk4::Label::new(Some("Next")));
+            options.append(&next_spinner);
+            options.append(&reset_button);
+            options.append(&auto_size_button);
+
+            options
+        }
+
+        fn make_button(
             button_drawing_area: gtk4::DrawingArea,
             editor: editor::EditorWindow,
+            set_colour: fn(&editor::EditorWindow, Colour),
         ) -> gtk4::Button {
             let button = gtk4::Button::new();
             button.set_child(Some(&button_drawing_area));
@@ -248,11 +1050,7 @@ mod underlying {
 
                 dialog.connect_response(
                     clone!(@weak button_drawing_area => move |editor, colour| {
-                        if IS_PRIMARY {
-                            editor.set_primary_colour(colour);
-                        } else {
-                            editor.set_secondary_colour(colour);
-                        }
+                        set_colour(editor, colour);
                         button_drawing_area.queue_draw();
                     }),
                 );
@@ -264,6 +1062,11 @@ mod underlying {
         }
     }
 
+    /// Returns where `item` is in `all`, for showing it in a dropdown made out of `all`
+    pub(super) fn position_of<T: PartialEq>(all: &[T], item: T) -> u32 {
+        all.iter().position(|other| *other == item).unwrap_or(0) as u32
+    }
+
     fn make_tool_button(
         tool: Tool,
         toolbar: &gtk4::Box,
@@ -286,6 +1089,8 @@ mod underlying {
         };
         button.set_child(Some(&gtk4::Image::from_resource(tool.path())));
         button.set_tooltip_markup(Some(tool.tooltip()));
+        // Otherwise the last tool picked would grab Space, which draws shapes from their centre
+        button.set_focus_on_click(false);
 
         let spinner = spinner.cloned();
         let primary = primary.cloned();
@@ -1,24 +1,31 @@
-use std::cell::{Cell, RefCell};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
 
 use cairo::Context;
 use diesel::SqliteConnection;
 use gtk4::{
-    gdk::{self, BUTTON_PRIMARY, BUTTON_SECONDARY},
+    gdk::{self, BUTTON_MIDDLE, BUTTON_PRIMARY, BUTTON_SECONDARY},
     gio,
     glib::{self, clone, ParamSpec, Properties},
     prelude::*,
     subclass::prelude::*,
     Allocation,
 };
-use kcshot_data::geometry::{Point, Rectangle};
+use kcshot_data::geometry::Point;
 use once_cell::unsync::OnceCell;
 use tracing::error;
 
 use super::{toolbar, utils::ContextLogger, Colour};
 use crate::{
     editor::{
-        operations::{OperationStack, SelectionMode, Tool},
+        loupe,
+        operations::{EdgeMap, OperationStack, Tool},
+        project::Project,
         utils,
+        viewport::{Viewport, ZOOM_STEP},
     },
     historymodel::ModelNotifier,
     kcshot::KCShot,
@@ -26,6 +33,13 @@ use crate::{
     postcapture::run_postcapture_actions,
 };
 
+/// How far, in pixels, a single notch of the scroll wheel pans the screenshot
+const SCROLL_PAN_STEP: f64 = 32.0;
+/// How far, in pixels, the arrow keys move things around while Shift is held
+const LARGE_NUDGE_STEP: f64 = 10.0;
+/// How long the size of the current tool stays shown around the cursor after it was changed
+const TOOL_SIZE_PREVIEW_DURATION: Duration = Duration::from_millis(800);
+
 #[derive(Debug)]
 pub(super) struct Image {
     surface: cairo::ImageSurface,
@@ -34,7 +48,9 @@ pub(super) struct Image {
 
 impl Image {
     fn get_colour_at(&self, x: f64, y: f64) -> Colour {
-        let (x, y) = (x as usize, y as usize);
+        // The point can be outside of the screenshot when zoomed out
+        let x = x.clamp(0.0, (self.surface.width() - 1) as f64) as usize;
+        let y = y.clamp(0.0, (self.surface.height() - 1) as f64) as usize;
 
         let stride = self.surface.stride() as usize;
         // NOTE: We multiply by 4 here because CAIRO_FORMAT_RGB24 pixels are 4 bytes in size
@@ -76,6 +92,14 @@ pub struct EditorWindow {
 
     pub(super) image: RefCell<Option<Image>>,
     overlay: OnceCell<gtk4::Overlay>,
+    pub(super) toolbar: OnceCell<toolbar::ToolbarWidget>,
+    viewport: Cell<Viewport>,
+    /// Where the pointer was last seen, in window coordinates, this is used as the point around
+    /// which keyboard zooming happens
+    pointer: Cell<Option<Point>>,
+    /// The size of the current tool, in pixels on the screenshot, while it's shown around the
+    /// cursor, along with how many times it was changed, so that only the last change hides it
+    tool_size_preview: Cell<(Option<f64>, u32)>,
 
     /// This field is part of the "pick a colour from the screen" mechanism, we send the colour under
     /// the mouse cursor to the colour chooser dialog currently open
@@ -87,6 +111,10 @@ impl std::fmt::Debug for EditorWindow {
         f.debug_struct("EditorWindow")
             .field("image", &self.image)
             .field("overlay", &self.overlay)
+            .field("toolbar", &self.toolbar)
+            .field("viewport", &self.viewport)
+            .field("pointer", &self.pointer)
+            .field("tool_size_preview", &self.tool_size_preview)
             .field(
                 "editing_started_with_cropping",
                 &self.editing_started_with_cropping,
@@ -100,6 +128,10 @@ impl EditorWindow {
     fn do_draw(image: &Image, cairo: &Context, is_in_draw_event: bool) {
         cairo.set_operator(cairo::Operator::Source);
         log_if_err!(cairo.set_source_surface(&image.surface, 0f64, 0f64));
+        // When zoomed in, we want to see the individual pixels, not a blurry mess
+        if cairo.matrix().xx() > 1.0 {
+            cairo.source().set_filter(cairo::Filter::Nearest);
+        }
         log_if_err!(cairo.paint());
         cairo.set_operator(cairo::Operator::Over);
 
@@ -115,6 +147,19 @@ impl EditorWindow {
         image: &Image,
         point: Option<Point>,
     ) {
+        let crop_region = image.operation_stack.crop_region(point);
+        let rectangle = crop_region.unwrap_or(image.operation_stack.screen_dimensions);
+
+        // This has to be made before drawing, as drawing the operations destroys the original
+        // screenshot
+        let project = Project::new(
+            &image.surface,
+            crop_region,
+            image.operation_stack.annotations(),
+        )
+        .map_err(|why| error!("Failed to make a project out of the screenshot: {why}"))
+        .ok();
+
         let cairo = match Context::new(&image.surface) {
             Ok(cairo) => cairo,
             Err(err) => {
@@ -124,16 +169,13 @@ impl EditorWindow {
         };
         EditorWindow::do_draw(image, &cairo, false);
 
-        let rectangle = image
-            .operation_stack
-            .crop_region(point)
-            .unwrap_or(image.operation_stack.screen_dimensions);
-
         window.close();
 
         match utils::pixbuf_for(&image.surface, rectangle) {
             // Process all post capture actions
-            Some(mut pixbuf) => run_postcapture_actions(model_notifier, conn, &mut pixbuf),
+            Some(mut pixbuf) => {
+                run_postcapture_actions(model_notifier, conn, &mut pixbuf, project.as_ref());
+            }
             None => {
                 error!(
                     "Failed to create a pixbuf from the surface: {:?} with crop region {:#?}",
@@ -144,6 +186,70 @@ impl EditorWindow {
         };
     }
 
+    fn is_picking_a_colour(&self) -> bool {
+        let colour_tx = self.colour_tx.take();
+        let is_picking_a_colour = colour_tx.is_some();
+        self.colour_tx.set(colour_tx);
+
+        is_picking_a_colour
+    }
+
+    /// Maps a point in window coordinates to a point on the screenshot
+    fn to_image(&self, x: f64, y: f64) -> Point {
+        self.viewport.get().to_image(Point { x, y })
+    }
+
+    fn zoom_by(&self, factor: f64, anchor: Point) {
+        let mut viewport = self.viewport.get();
+        viewport.zoom_by(factor, anchor);
+        self.viewport.set(viewport);
+    }
+
+    /// Returns the point zooming should happen around, which is the pointer when it's over the
+    /// window and the top-left corner otherwise
+    fn zoom_anchor(&self) -> Point {
+        self.pointer.get().unwrap_or(Point { x: 0.0, y: 0.0 })
+    }
+
+    fn pan(&self, dx: f64, dy: f64) {
+        let mut viewport = self.viewport.get();
+        viewport.pan(dx, dy);
+        self.viewport.set(viewport);
+    }
+
+    /// Shows a circle as wide as `size` around the cursor for a moment, so that the size picked
+    /// for the current tool can be judged against the screenshot
+    pub(super) fn preview_tool_size(&self, size: f64) {
+        let (_, changes) = self.tool_size_preview.get();
+        let changes = changes.wrapping_add(1);
+        self.tool_size_preview.set((Some(size), changes));
+        self.obj().queue_draw();
+
+        let obj = self.obj();
+        glib::timeout_add_local_once(
+            TOOL_SIZE_PREVIEW_DURATION,
+            clone!(@weak obj => move || {
+                let imp = obj.imp();
+                if imp.tool_size_preview.get().1 == changes {
+                    imp.tool_size_preview.set((None, changes));
+                    obj.queue_draw();
+                }
+            }),
+        );
+    }
+
+    pub(super) fn set_image(
+        &self,
+        surface: cairo::ImageSurface,
+        mut operation_stack: OperationStack,
+    ) {
+        operation_stack.set_edge_map(EdgeMap::new(&surface));
+        self.image.replace(Some(Image {
+            surface,
+            operation_stack,
+        }));
+    }
+
     pub(super) fn with_image<F, T>(&self, ctx: &str, func: F) -> Option<T>
     where
         F: FnOnce(&Image) -> T,
@@ -213,19 +319,6 @@ impl ObjectImpl for EditorWindow {
         self.parent_constructed();
         let obj = self.obj();
 
-        let image = kcshot_screenshot::take_screenshot(KCShot::the().tokio_rt())
-            .expect("Couldn't take a screenshot");
-        let windows = kcshot_screenshot::get_windows().unwrap_or_else(|why| {
-            tracing::info!("Got while trying to retrieve windows: {why}");
-            vec![]
-        });
-        let screen_dimensions = Rectangle {
-            x: 0.0,
-            y: 0.0,
-            w: image.width() as f64,
-            h: image.height() as f64,
-        };
-
         let overlay = gtk4::Overlay::new();
         obj.set_child(Some(&overlay));
         let drawing_area = gtk4::DrawingArea::builder().can_focus(true).build();
@@ -235,17 +328,16 @@ impl ObjectImpl for EditorWindow {
         let toolbar = toolbar::ToolbarWidget::new(&obj, self.editing_started_with_cropping.get());
         overlay.add_overlay(&toolbar);
 
-        overlay.connect_get_child_position(move |_this, widget| {
-            let Rectangle {
-                w: screen_width,
-                h: screen_height,
-                ..
-            } = screen_dimensions;
+        overlay.connect_get_child_position(move |this, widget| {
+            let (screen_width, screen_height) = (this.width() as f64, this.height() as f64);
+            // The toolbar holds more than just the tool buttons, so we let it be as wide as it
+            // needs to be
+            let (_, width, _, _) = widget.measure(gtk4::Orientation::Horizontal, -1);
 
             Some(Allocation::new(
-                (screen_width / 2.0 - widget.width() as f64 / 2.0) as i32,
+                (screen_width / 2.0 - width as f64 / 2.0) as i32,
                 (screen_height / 5.0) as i32,
-                11 * 32,
+                width,
                 32,
             ))
         });
@@ -253,9 +345,54 @@ impl ObjectImpl for EditorWindow {
         self.overlay
             .set(overlay)
             .expect("construct should not be called more than once");
+        self.toolbar
+            .set(toolbar.clone())
+            .expect("construct should not be called more than once");
+
+        drawing_area.set_draw_func(clone!(@weak obj => move |_widget, cairo, w, h| {
+            let viewport = obj.imp().viewport.get();
+            obj.imp().with_image("draw event", |image| {
+                if viewport.is_zoomed() {
+                    // Parts of the window might not be covered by the screenshot anymore
+                    cairo.set_source_rgb(0.0, 0.0, 0.0);
+                    log_if_err!(cairo.paint());
+                }
 
-        drawing_area.set_draw_func(clone!(@weak obj => move |_widget, cairo, _w, _h| {
-            obj.imp().with_image("draw event", |image| EditorWindow::do_draw(image, cairo, true));
+                log_if_err!(cairo.save());
+                viewport.apply(cairo);
+                EditorWindow::do_draw(image, cairo, true);
+                log_if_err!(cairo.restore());
+
+                log_if_err!(viewport.draw_pixel_grid(
+                    cairo,
+                    (w as f64, h as f64),
+                    (image.surface.width() as f64, image.surface.height() as f64),
+                ));
+
+                let should_draw_loupe = obj.imp().is_picking_a_colour()
+                    || image.operation_stack.current_tool().is_cropping_tool();
+                if let Some(cursor) = obj.imp().pointer.get().filter(|_| should_draw_loupe) {
+                    let pixel = viewport.to_image(cursor);
+                    let target = loupe::Target {
+                        pixel,
+                        colour: image.get_colour_at(pixel.x, pixel.y),
+                        selection: image.operation_stack.current_crop_region(),
+                    };
+                    log_if_err!(loupe::draw(
+                        cairo,
+                        &image.surface,
+                        cursor,
+                        (w as f64, h as f64),
+                        &target
+                    ));
+                }
+
+                let (tool_size, _) = obj.imp().tool_size_preview.get();
+                if let (Some(cursor), Some(size)) = (obj.imp().pointer.get(), tool_size) {
+                    let diameter = viewport.to_window_distance(size);
+                    log_if_err!(loupe::draw_size_preview(cairo, cursor, diameter));
+                }
+            });
         }));
 
         let click_event_handler = gtk4::GestureClick::new();
@@ -266,6 +403,7 @@ impl ObjectImpl for EditorWindow {
                 if let Some(colour_tx) = obj.imp().colour_tx.take() {
                     // if colour_tx is non-None it means there is a colour dialog open, and the user
                     // is trying to pick a colour at the moment!
+                    let Point { x, y } = obj.imp().to_image(x, y);
                     obj.imp().with_image("colour picker", |image| {
                         let colour = image.get_colour_at(x, y);
                             if let Err(why) = colour_tx.send(colour) {
@@ -278,9 +416,18 @@ impl ObjectImpl for EditorWindow {
                         "There should be no colour_tx on the EditorWindow when we're not picking a colour"
                     );
 
-                    obj.imp().with_image_mut("primary button pressed", |image| {
-                        image.operation_stack.start_operation_at(Point { x, y });
+                    let point = obj.imp().to_image(x, y);
+                    let next_bubble_number = obj.imp().with_image_mut("primary button pressed", |image| {
+                        image.operation_stack.start_operation_at(point);
+                        let placed_bubble = image.operation_stack.current_tool() == Tool::AutoincrementBubble;
+                        placed_bubble.then_some(image.operation_stack.bubbles.next_number)
                     });
+
+                    // The toolbar has to be told outside of `with_image_mut`, as it updates the
+                    // stack when the number it shows changes
+                    if let (Some(toolbar), Some(Some(number))) = (obj.imp().toolbar.get(), next_bubble_number) {
+                        toolbar.show_next_bubble_number(number);
+                    }
                 }
             } else if this.current_button() == BUTTON_SECONDARY {
                 obj.close();
@@ -290,23 +437,54 @@ impl ObjectImpl for EditorWindow {
         let motion_event_handler = gtk4::EventControllerMotion::new();
         motion_event_handler.connect_motion(
             clone!(@weak obj, @weak drawing_area => move |_, x, y| {
+                obj.imp().pointer.set(Some(Point { x, y }));
+                let Point { x, y } = obj.imp().to_image(x, y);
                 obj.imp().with_image_mut("motion event", |image| {
                     image.operation_stack.set_current_window(x, y);
                     drawing_area.queue_draw();
                 });
             }),
         );
+        motion_event_handler.connect_leave(clone!(@weak obj, @weak drawing_area => move |_| {
+            obj.imp().pointer.set(None);
+            drawing_area.queue_draw();
+        }));
         drawing_area.add_controller(motion_event_handler);
 
         click_event_handler.connect_released(
-            clone!(@weak obj, @weak drawing_area => move |_this, _n_clicks, x, y| {
+            clone!(@weak obj, @weak drawing_area => move |this, n_clicks, x, y| {
+                if this.current_button() == BUTTON_MIDDLE {
+                    // The middle button is used for panning
+                    return;
+                }
+
+                let point = obj.imp().to_image(x, y);
+                let mut should_adjust_crop = false;
+                let mut should_edit_text = false;
+                let mut selected_stroke_style = None;
                 let should_queue_draw = obj.imp().with_image_mut("mouse button released event", |image| {
                     // NOTE: image.operation_stack.finish_current_operation MUST be called in all
                     //       branches of this if-chain, in order for tools to take part in the undo
-                    //       stack! For the Text tool, this happens in pop_text_dialog_and_get_text.
-                    if image.operation_stack.current_tool() == Tool::Text {
+                    //       stack! For the Text and Callout tools, this happens in
+                    //       pop_text_dialog_and_get_text, while polylines are finished once they're
+                    //       double-clicked or closed.
+                    if image.operation_stack.is_dragging_selection() {
+                        image.operation_stack.finish_current_operation();
+                        true
+                    } else if image.operation_stack.current_tool() == Tool::Select {
+                        image.operation_stack.finish_current_operation();
+                        should_adjust_crop =
+                            n_clicks == 2 && image.operation_stack.is_initial_crop_selected();
+                        should_edit_text =
+                            n_clicks == 2 && image.operation_stack.selected_text().is_some();
+                        selected_stroke_style = image.operation_stack.selected_stroke_style();
+                        true
+                    } else if matches!(image.operation_stack.current_tool(), Tool::Text | Tool::Callout) {
                         super::textdialog::pop_text_dialog_and_get_text(&obj);
                         true
+                    } else if image.operation_stack.current_tool() == Tool::Polyline {
+                        image.operation_stack.release_polyline_point(n_clicks == 2);
+                        true
                     } else if !image.operation_stack.current_tool().is_saving_tool() {
                         image.operation_stack.finish_current_operation();
                         true
@@ -318,7 +496,7 @@ impl ObjectImpl for EditorWindow {
                             conn,
                             obj.upcast_ref(),
                             image,
-                            Some(Point { x, y })
+                            Some(point)
                         ));
                         false
                     }
@@ -327,6 +505,18 @@ impl ObjectImpl for EditorWindow {
                 if should_queue_draw.unwrap_or(true) {
                     drawing_area.queue_draw();
                 }
+
+                // These need to happen outside of `with_image_mut`, as the dialogs read what they
+                // edit through `with_image`, and the toolbar changes the stack when its controls do
+                if let Some(stroke) = selected_stroke_style {
+                    obj.show_stroke_style(stroke);
+                }
+                if should_adjust_crop {
+                    super::cropdialog::pop_crop_dialog(&obj);
+                }
+                if should_edit_text {
+                    super::textdialog::pop_text_edit_dialog(&obj);
+                }
             }),
         );
 
@@ -335,6 +525,7 @@ impl ObjectImpl for EditorWindow {
         let drag_controller = gtk4::GestureDrag::new();
         drag_controller.connect_drag_update(
             clone!(@weak obj, @weak drawing_area =>  move |_this, x, y| {
+                let (x, y) = obj.imp().viewport.get().to_image_distance(x, y);
                 obj.imp().with_image_mut("drag update event", |image| {
                     image.operation_stack.update_current_operation_end_coordinate(x, y);
                     if image.operation_stack.current_tool().is_cropping_tool() {
@@ -346,6 +537,7 @@ impl ObjectImpl for EditorWindow {
         );
         drag_controller.connect_drag_end(
             clone!(@weak obj, @weak drawing_area, @weak toolbar => move |_, x, y| {
+                let (x, y) = obj.imp().viewport.get().to_image_distance(x, y);
                 obj.imp().with_image_mut("drag end event", |image| {
                     image.operation_stack.update_current_operation_end_coordinate(x, y);
                     if image.operation_stack.current_tool() == Tool::Crop {
@@ -359,13 +551,110 @@ impl ObjectImpl for EditorWindow {
         );
         drawing_area.add_controller(drag_controller);
 
+        // Tablets report how hard their stylus is pressed through a separate gesture, which runs
+        // alongside the click and drag ones that actually draw
+        let stylus_controller = gtk4::GestureStylus::new();
+        let record_pressure = clone!(@weak obj => move |this: &gtk4::GestureStylus, _x: f64, _y: f64| {
+            let pressure = this.axis(gdk::AxisUse::Pressure);
+            obj.imp().with_image_mut("stylus event", |image| {
+                image.operation_stack.set_stylus_pressure(pressure);
+            });
+        });
+        stylus_controller.connect_down(record_pressure.clone());
+        stylus_controller.connect_motion(record_pressure);
+        stylus_controller.connect_up(clone!(@weak obj => move |_, _, _| {
+            obj.imp().with_image_mut("stylus up event", |image| {
+                image.operation_stack.set_stylus_pressure(None);
+            });
+        }));
+        drawing_area.add_controller(stylus_controller);
+
+        let pan_controller = gtk4::GestureDrag::new();
+        pan_controller.set_button(BUTTON_MIDDLE);
+        let last_pan_offset = Rc::new(Cell::new((0.0, 0.0)));
+        pan_controller.connect_drag_begin(
+            clone!(@strong last_pan_offset => move |_, _, _| last_pan_offset.set((0.0, 0.0))),
+        );
+        pan_controller.connect_drag_update(
+            clone!(@weak obj, @weak drawing_area, @strong last_pan_offset => move |_this, x, y| {
+                let (last_x, last_y) = last_pan_offset.replace((x, y));
+                obj.imp().pan(x - last_x, y - last_y);
+                drawing_area.queue_draw();
+            }),
+        );
+        drawing_area.add_controller(pan_controller);
+
+        let scroll_controller =
+            gtk4::EventControllerScroll::new(gtk4::EventControllerScrollFlags::BOTH_AXES);
+        scroll_controller.connect_scroll(
+            clone!(@weak obj, @weak drawing_area => @default-return gtk4::Inhibit(false), move |this, dx, dy| {
+                let imp = obj.imp();
+                let state = this.current_event_state();
+                if state.contains(gdk::ModifierType::CONTROL_MASK) {
+                    let factor = if dy < 0.0 { ZOOM_STEP } else { 1.0 / ZOOM_STEP };
+                    imp.zoom_by(factor, imp.zoom_anchor());
+                } else if state.contains(gdk::ModifierType::ALT_MASK)
+                    && imp.with_image("scroll event", |image| image.operation_stack.is_drawing()) == Some(true)
+                {
+                    // Alt is held to stop snapping to edges while drawing, the size being drawn
+                    // with shouldn't change when scrolling then
+                    return gtk4::Inhibit(true);
+                } else if state.contains(gdk::ModifierType::ALT_MASK) || !imp.viewport.get().is_zoomed() {
+                    // Scrolling pans around zoomed in screenshots, so the tool size is then only
+                    // changed while Alt is held
+                    if dy == 0.0 {
+                        return gtk4::Inhibit(false);
+                    }
+                    obj.adjust_tool_size(if dy < 0.0 { 1.0 } else { -1.0 });
+                } else {
+                    imp.pan(-dx * SCROLL_PAN_STEP, -dy * SCROLL_PAN_STEP);
+                }
+
+                drawing_area.queue_draw();
+                gtk4::Inhibit(true)
+            }),
+        );
+        drawing_area.add_controller(scroll_controller);
+
         let key_event_controller = gtk4::EventControllerKey::new();
         key_event_controller.connect_key_pressed(
-            clone!(@weak obj, @weak drawing_area => @default-return gtk4::Inhibit(false), move |_, key, _, _| {
+            clone!(@weak obj, @weak drawing_area => @default-return gtk4::Inhibit(false), move |_, key, _, modifiers| {
+                let nudge_step = if modifiers.contains(gdk::ModifierType::SHIFT_MASK) {
+                    LARGE_NUDGE_STEP
+                } else {
+                    1.0
+                };
+                let nudge = match key {
+                    gdk::Key::Left => Some((-nudge_step, 0.0)),
+                    gdk::Key::Right => Some((nudge_step, 0.0)),
+                    gdk::Key::Up => Some((0.0, -nudge_step)),
+                    gdk::Key::Down => Some((0.0, nudge_step)),
+                    _ => None,
+                };
+                if let Some((dx, dy)) = nudge {
+                    obj.imp().with_image_mut("nudge", |image| {
+                        image.operation_stack.nudge(dx, dy);
+                        drawing_area.queue_draw();
+                    });
+                    return gtk4::Inhibit(true);
+                }
+
+                let size_steps = match key {
+                    gdk::Key::bracketleft => Some(-1.0),
+                    gdk::Key::bracketright => Some(1.0),
+                    _ => None,
+                };
+                if let Some(steps) = size_steps {
+                    obj.adjust_tool_size(steps);
+                    return gtk4::Inhibit(true);
+                }
+
                 obj.imp().with_image_mut("key pressed event", |image| {
                     if key == gdk::Key::Control_L || key == gdk::Key::Control_R {
                         image.operation_stack.set_ignore_windows(true);
                         drawing_area.queue_draw();
+                    } else if key == gdk::Key::space {
+                        image.operation_stack.from_centre = true;
                     } else if key == gdk::Key::Return {
                         if !obj.imp().editing_started_with_cropping.get() {
                             // Saving a screenshot using `Return` only makes sense in "crop-first"
@@ -381,7 +670,27 @@ impl ObjectImpl for EditorWindow {
                             None
                         ));
                     } else if key == gdk::Key::Shift_L || key == gdk::Key::Shift_R {
-                        image.operation_stack.selection_mode = SelectionMode::WindowsWithoutDecorations;
+                        // Windows are only picked by clicking, so Shift picks their other rectangle
+                        // then, while it constrains the regions and shapes which are dragged out
+                        image.operation_stack.set_selection_mode_inverted(true);
+                        image.operation_stack.constrain = true;
+                    } else if key == gdk::Key::Alt_L || key == gdk::Key::Alt_R {
+                        image.operation_stack.snap_to_edges = false;
+                    } else if key == gdk::Key::Delete || key == gdk::Key::BackSpace {
+                        image.operation_stack.delete_selection();
+                        drawing_area.queue_draw();
+                    } else if key == gdk::Key::Page_Up {
+                        image.operation_stack.restack_selection(1);
+                        drawing_area.queue_draw();
+                    } else if key == gdk::Key::Page_Down {
+                        image.operation_stack.restack_selection(-1);
+                        drawing_area.queue_draw();
+                    } else if key == gdk::Key::Home {
+                        image.operation_stack.restack_selection(isize::MAX);
+                        drawing_area.queue_draw();
+                    } else if key == gdk::Key::End {
+                        image.operation_stack.restack_selection(isize::MIN);
+                        drawing_area.queue_draw();
                     }
                 });
                 gtk4::Inhibit(false)
@@ -393,10 +702,15 @@ impl ObjectImpl for EditorWindow {
                     if key == gdk::Key::Control_L || key == gdk::Key::Control_R {
                         image.operation_stack.set_ignore_windows(false);
                         drawing_area.queue_draw();
+                    } else if key == gdk::Key::space {
+                        image.operation_stack.from_centre = false;
                     } else if key == gdk::Key::Escape {
                         obj.close();
                     } else if key == gdk::Key::Shift_L || key == gdk::Key::Shift_R {
-                        image.operation_stack.selection_mode = SelectionMode::WindowsWithDecorations;
+                        image.operation_stack.set_selection_mode_inverted(false);
+                        image.operation_stack.constrain = false;
+                    } else if key == gdk::Key::Alt_L || key == gdk::Key::Alt_R {
+                        image.operation_stack.snap_to_edges = true;
                     }
                 });
             }),
@@ -412,6 +726,35 @@ impl ObjectImpl for EditorWindow {
         }));
         obj.add_action(&undo_action);
 
+        let zoom_in_action = gio::SimpleAction::new("zoom-in", None);
+        zoom_in_action.connect_activate(clone!(@weak obj, @weak drawing_area => move |_, _| {
+            obj.imp().zoom_by(ZOOM_STEP, obj.imp().zoom_anchor());
+            drawing_area.queue_draw();
+        }));
+        obj.add_action(&zoom_in_action);
+
+        let zoom_out_action = gio::SimpleAction::new("zoom-out", None);
+        zoom_out_action.connect_activate(clone!(@weak obj, @weak drawing_area => move |_, _| {
+            obj.imp().zoom_by(1.0 / ZOOM_STEP, obj.imp().zoom_anchor());
+            drawing_area.queue_draw();
+        }));
+        obj.add_action(&zoom_out_action);
+
+        let zoom_reset_action = gio::SimpleAction::new("zoom-reset", None);
+        zoom_reset_action.connect_activate(clone!(@weak obj, @weak drawing_area => move |_, _| {
+            let mut viewport = obj.imp().viewport.get();
+            viewport.reset();
+            obj.imp().viewport.set(viewport);
+            drawing_area.queue_draw();
+        }));
+        obj.add_action(&zoom_reset_action);
+
+        let adjust_crop_action = gio::SimpleAction::new("adjust-crop", None);
+        adjust_crop_action.connect_activate(clone!(@weak obj => move |_, _| {
+            super::cropdialog::pop_crop_dialog(&obj);
+        }));
+        obj.add_action(&adjust_crop_action);
+
         let redo_action = gio::SimpleAction::new("redo", None);
         redo_action.connect_activate(clone!(@weak obj, @weak drawing_area => move |_, _| {
             obj.imp().with_image_mut("win.redo activated", |image| {
@@ -425,15 +768,10 @@ impl ObjectImpl for EditorWindow {
         // in the same place QWERTY does.
         KCShot::the().set_accels_for_action("win.undo", &["<Ctrl>Z"]);
         KCShot::the().set_accels_for_action("win.redo", &["<Ctrl>Y"]);
-
-        self.image.replace(Some(Image {
-            surface: image,
-            operation_stack: OperationStack::new(
-                windows,
-                screen_dimensions,
-                self.editing_started_with_cropping.get(),
-            ),
-        }));
+        KCShot::the().set_accels_for_action("win.adjust-crop", &["<Ctrl>R"]);
+        KCShot::the().set_accels_for_action("win.zoom-in", &["<Ctrl>plus", "<Ctrl>equal"]);
+        KCShot::the().set_accels_for_action("win.zoom-out", &["<Ctrl>minus"]);
+        KCShot::the().set_accels_for_action("win.zoom-reset", &["<Ctrl>0"]);
     }
 
     fn dispose(&self) {
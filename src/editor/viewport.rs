@@ -0,0 +1,246 @@
+use cairo::Context;
+use kcshot_data::{colour::Colour, geometry::Point};
+
+use super::utils::CairoExt;
+
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 32.0;
+/// How much a single zoom step (one notch of the scroll wheel, or one keypress) zooms in or out
+pub const ZOOM_STEP: f64 = 1.25;
+/// The zoom level starting from which every pixel of the screenshot is outlined
+const PIXEL_GRID_MIN_ZOOM: f64 = 8.0;
+const PIXEL_GRID_COLOUR: Colour = Colour {
+    red: 127,
+    green: 127,
+    blue: 127,
+    alpha: 96,
+};
+
+/// Describes which part of the screenshot is visible in the editor, and how big it is
+///
+/// The screenshot is drawn scaled by `zoom`, with its top-left corner at `offset` in window
+/// coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    zoom: f64,
+    offset: Point,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            offset: Point { x: 0.0, y: 0.0 },
+        }
+    }
+}
+
+impl Viewport {
+    /// Returns whether the screenshot is drawn any differently than at 1:1 in the top-left corner
+    pub fn is_zoomed(&self) -> bool {
+        self.zoom != 1.0 || self.offset.x != 0.0 || self.offset.y != 0.0
+    }
+
+    /// Maps a point in window coordinates (as received from events) to a point on the screenshot
+    pub fn to_image(&self, Point { x, y }: Point) -> Point {
+        Point {
+            x: (x - self.offset.x) / self.zoom,
+            y: (y - self.offset.y) / self.zoom,
+        }
+    }
+
+    /// Maps a distance in window coordinates (like the ones reported by drag events) to a distance
+    /// on the screenshot
+    pub fn to_image_distance(&self, dx: f64, dy: f64) -> (f64, f64) {
+        (dx / self.zoom, dy / self.zoom)
+    }
+
+    /// Maps a distance on the screenshot to one in window coordinates, the inverse of
+    /// [`Self::to_image_distance`] for a single length
+    pub fn to_window_distance(&self, distance: f64) -> f64 {
+        distance * self.zoom
+    }
+
+    /// Multiplies the zoom level by `factor`, while keeping the point of the screenshot under
+    /// `anchor` (in window coordinates) in place
+    ///
+    /// Zoom levels within half a step of 1:1 are snapped to it, as multiplying by [`ZOOM_STEP`] and
+    /// dividing by it again rarely lands exactly on 1, and the screenshot is then lined up with the
+    /// pixels of the window again.
+    pub fn zoom_by(&mut self, factor: f64, anchor: Point) {
+        let fixed = self.to_image(anchor);
+
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let snaps_to_actual_size = self.zoom.ln().abs() < ZOOM_STEP.ln() / 2.0;
+        if snaps_to_actual_size {
+            self.zoom = 1.0;
+        }
+
+        self.offset = Point {
+            x: anchor.x - fixed.x * self.zoom,
+            y: anchor.y - fixed.y * self.zoom,
+        };
+        if snaps_to_actual_size {
+            self.offset = Point {
+                x: self.offset.x.round(),
+                y: self.offset.y.round(),
+            };
+        }
+    }
+
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.offset.x += dx;
+        self.offset.y += dy;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Makes everything drawn afterwards on `cairo` go through the viewport's transform
+    pub fn apply(&self, cairo: &Context) {
+        cairo.translate(self.offset.x, self.offset.y);
+        cairo.scale(self.zoom, self.zoom);
+    }
+
+    /// Outlines the pixels of an image of `image_width`x`image_height` when zoomed in far enough
+    /// for them to be told apart, this should be called without the viewport's transform applied
+    pub fn draw_pixel_grid(
+        &self,
+        cairo: &Context,
+        (width, height): (f64, f64),
+        (image_width, image_height): (f64, f64),
+    ) -> Result<(), cairo::Error> {
+        if self.zoom < PIXEL_GRID_MIN_ZOOM {
+            return Ok(());
+        }
+
+        let top_left = self.to_image(Point { x: 0.0, y: 0.0 });
+        let bottom_right = self.to_image(Point {
+            x: width,
+            y: height,
+        });
+        let (first_column, last_column) = (
+            top_left.x.floor().max(0.0),
+            bottom_right.x.ceil().min(image_width),
+        );
+        let (first_row, last_row) = (
+            top_left.y.floor().max(0.0),
+            bottom_right.y.ceil().min(image_height),
+        );
+
+        cairo.save()?;
+        cairo.set_source_colour(PIXEL_GRID_COLOUR);
+        cairo.set_line_width(1.0);
+
+        let (top, bottom) = (
+            self.offset.y + first_row * self.zoom,
+            self.offset.y + last_row * self.zoom,
+        );
+        let mut column = first_column;
+        while column <= last_column {
+            // The half-pixel offset keeps the lines sharp
+            let x = (self.offset.x + column * self.zoom).round() + 0.5;
+            cairo.move_to(x, top);
+            cairo.line_to(x, bottom);
+            column += 1.0;
+        }
+
+        let (left, right) = (
+            self.offset.x + first_column * self.zoom,
+            self.offset.x + last_column * self.zoom,
+        );
+        let mut row = first_row;
+        while row <= last_row {
+            let y = (self.offset.y + row * self.zoom).round() + 0.5;
+            cairo.move_to(left, y);
+            cairo.line_to(right, y);
+            row += 1.0;
+        }
+
+        cairo.stroke()?;
+        cairo.restore()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Point, Viewport, MAX_ZOOM, MIN_ZOOM, ZOOM_STEP};
+
+    fn assert_close(a: Point, b: Point) {
+        assert!(
+            (a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn transforms() {
+        let mut viewport = Viewport::default();
+        let point = Point { x: 30.0, y: 40.0 };
+        assert!(!viewport.is_zoomed());
+        assert_close(viewport.to_image(point), point);
+
+        viewport.pan(10.0, -20.0);
+        assert!(viewport.is_zoomed());
+        assert_close(viewport.to_image(point), Point { x: 20.0, y: 60.0 });
+        assert_eq!(viewport.to_image_distance(5.0, 5.0), (5.0, 5.0));
+
+        viewport.zoom_by(2.0, Point { x: 10.0, y: -20.0 });
+        assert_close(viewport.to_image(point), Point { x: 10.0, y: 30.0 });
+        assert_eq!(viewport.to_image_distance(5.0, -8.0), (2.5, -4.0));
+
+        viewport.reset();
+        assert!(!viewport.is_zoomed());
+    }
+
+    #[test]
+    fn zoom_keeps_anchor_in_place() {
+        let mut viewport = Viewport::default();
+        let anchor = Point { x: 120.0, y: 75.0 };
+
+        let before = viewport.to_image(anchor);
+        viewport.zoom_by(ZOOM_STEP, anchor);
+        assert_close(viewport.to_image(anchor), before);
+
+        let anchor = Point { x: 13.0, y: 250.0 };
+        let before = viewport.to_image(anchor);
+        viewport.zoom_by(ZOOM_STEP * ZOOM_STEP, anchor);
+        assert_close(viewport.to_image(anchor), before);
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut viewport = Viewport::default();
+        let anchor = Point { x: 0.0, y: 0.0 };
+
+        viewport.zoom_by(1000.0, anchor);
+        assert_eq!(viewport.to_image_distance(MAX_ZOOM, 0.0), (1.0, 0.0));
+
+        viewport.zoom_by(0.0001, anchor);
+        assert_eq!(viewport.to_image_distance(MIN_ZOOM, 0.0), (1.0, 0.0));
+    }
+
+    #[test]
+    fn zoom_snaps_to_actual_size() {
+        let mut viewport = Viewport::default();
+        let anchor = Point { x: 0.0, y: 0.0 };
+
+        for _ in 0..7 {
+            viewport.zoom_by(ZOOM_STEP, anchor);
+        }
+        for _ in 0..7 {
+            viewport.zoom_by(ZOOM_STEP.recip(), anchor);
+        }
+        assert!(!viewport.is_zoomed());
+
+        // The offset is rounded as well, so that pixels line up with the window
+        viewport.zoom_by(ZOOM_STEP, Point { x: 10.0, y: 10.0 });
+        viewport.zoom_by(ZOOM_STEP.recip(), Point { x: 33.0, y: 71.0 });
+        assert_eq!(viewport.to_image_distance(3.0, 4.0), (3.0, 4.0));
+        let offset = viewport.to_image(anchor);
+        assert_eq!((offset.x, offset.y), (offset.x.round(), offset.y.round()));
+    }
+}
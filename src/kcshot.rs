@@ -325,6 +325,20 @@ Application Options:
                 settings.set_saved_screenshots_path(default_folder.to_str().unwrap());
             }
 
+            if settings.stamps_path().is_empty() {
+                let default_folder = if cfg!(feature = "xdg-paths") {
+                    xdg::BaseDirectories::with_prefix("kcshot")
+                        .unwrap()
+                        .get_data_home()
+                        .join("Stamps")
+                } else {
+                    std::env::current_dir().unwrap().join("Stamps")
+                };
+
+                tracing::info!("'stamps-path' was empty, set it to {default_folder:?}");
+                settings.set_stamps_path(default_folder.to_str().unwrap());
+            }
+
             gtk4::Window::set_default_icon_name("kcshot");
         }
     }
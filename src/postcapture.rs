@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt::Write as _};
+use std::{collections::HashMap, fmt::Write as _, path::Path};
 
 use diesel::SqliteConnection;
 use gtk4::{
@@ -10,6 +10,7 @@ use kcshot_data::settings::Settings;
 
 use crate::{
     db,
+    editor::project::{Project, VectorFormat},
     historymodel::{ModelNotifier, RowData},
 };
 
@@ -25,11 +26,14 @@ pub trait PostCaptureAction {
     fn description(&self) -> String;
 
     /// Gets called when executing the post capture action.
+    ///
+    /// `project` is the editable version of the screenshot, it is missing if we failed to make it.
     fn handle(
         &self,
         model_notifier: &ModelNotifier,
         conn: &mut SqliteConnection,
         pixbuf: &mut Pixbuf,
+        project: Option<&Project>,
     );
 }
 
@@ -54,29 +58,23 @@ impl PostCaptureAction for SaveToDisk {
         model_notifier: &ModelNotifier,
         conn: &mut SqliteConnection,
         pixbuf: &mut Pixbuf,
+        project: Option<&Project>,
     ) {
-        let now = glib::DateTime::now_local()
-            .unwrap()
-            .format_iso8601()
-            .unwrap()
-            .to_string();
-
+        let now = now();
+        let path = screenshot_path(&now, "png");
         let settings = Settings::open();
-        let mut path = settings.saved_screenshots_path();
-        if !path.ends_with('/') {
-            path.push('/');
-        }
-
-        if let Err(why) = std::fs::create_dir_all(&path) {
-            tracing::error!("Failed to create directory='{path}': {why}");
-        }
-
-        write!(path, "screenshot_{now}.png").expect("Writing to a string shouldn't fail");
 
         if let Err(why) = pixbuf.savev(&path, "png", &[]) {
             tracing::error!("Failed to save screenshot to file: {why}");
         }
 
+        if let Some(project) = project.filter(|_| settings.save_editable_projects()) {
+            let project_path = Project::path_for(Path::new(&path));
+            if let Err(why) = project.save(&project_path) {
+                tracing::error!("Failed to save project to {project_path:?}: {why}");
+            }
+        }
+
         if let Err(why) = db::add_screenshot_to_history(conn, Some(path.clone()), now.clone(), None)
         {
             tracing::error!("Failed to add screenshot to history: {why}");
@@ -88,6 +86,45 @@ impl PostCaptureAction for SaveToDisk {
     }
 }
 
+/// This struct represents the action of exporting the screenshot along with its annotations to a
+/// vector image.
+pub struct ExportVector(VectorFormat);
+
+impl PostCaptureAction for ExportVector {
+    fn id(&self) -> String {
+        format!("export-{}", self.0.extension())
+    }
+
+    fn name(&self) -> String {
+        format!("Export as {}", self.0.extension().to_uppercase())
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Saves the screenshot to a {} file, in which annotations stay sharp when scaled",
+            self.0.extension().to_uppercase()
+        )
+    }
+
+    fn handle(
+        &self,
+        _model_notifier: &ModelNotifier,
+        _conn: &mut SqliteConnection,
+        _pixbuf: &mut Pixbuf,
+        project: Option<&Project>,
+    ) {
+        let Some(project) = project else {
+            tracing::error!("There is no project to export from, bailing...");
+            return;
+        };
+
+        let path = screenshot_path(&now(), self.0.extension());
+        if let Err(why) = project.export(Path::new(&path), self.0) {
+            tracing::error!("Failed to export screenshot to {path}: {why}");
+        }
+    }
+}
+
 /// This struct represents the action of copying the picture to the users clipboard.
 pub struct CopyToClipboard;
 
@@ -109,6 +146,7 @@ impl PostCaptureAction for CopyToClipboard {
         _model_notifier: &ModelNotifier,
         _conn: &mut SqliteConnection,
         pixbuf: &mut Pixbuf,
+        _project: Option<&Project>,
     ) {
         let Some(display) = gdk::Display::default() else {
             tracing::error!("Failed to fetch gdk::Display, bailing...");
@@ -120,14 +158,41 @@ impl PostCaptureAction for CopyToClipboard {
     }
 }
 
+fn now() -> String {
+    glib::DateTime::now_local()
+        .unwrap()
+        .format_iso8601()
+        .unwrap()
+        .to_string()
+}
+
+/// Returns the path a screenshot taken at `now` should be saved at, making sure its directory
+/// exists
+fn screenshot_path(now: &str, extension: &str) -> String {
+    let settings = Settings::open();
+    let mut path = settings.saved_screenshots_path();
+    if !path.ends_with('/') {
+        path.push('/');
+    }
+
+    if let Err(why) = std::fs::create_dir_all(&path) {
+        tracing::error!("Failed to create directory='{path}': {why}");
+    }
+
+    write!(path, "screenshot_{now}.{extension}").expect("Writing to a string shouldn't fail");
+
+    path
+}
+
 /// Executes the post capture actions in the order they are defined in the settings.
 pub fn run_postcapture_actions(
     model_notifier: &ModelNotifier,
     conn: &mut SqliteConnection,
     pixbuf: &mut Pixbuf,
+    project: Option<&Project>,
 ) {
     for action in get_actions_from_settings() {
-        action.handle(model_notifier, conn, pixbuf);
+        action.handle(model_notifier, conn, pixbuf, project);
     }
 }
 
@@ -154,6 +219,11 @@ fn get_actions_from_settings() -> Vec<&'static dyn PostCaptureAction> {
 }
 
 /// Vector of all available post capture actions.
-fn get_postcapture_actions() -> Vec<&'static dyn PostCaptureAction> {
-    vec![&SaveToDisk, &CopyToClipboard]
+pub fn get_postcapture_actions() -> Vec<&'static dyn PostCaptureAction> {
+    vec![
+        &SaveToDisk,
+        &CopyToClipboard,
+        &ExportVector(VectorFormat::Svg),
+        &ExportVector(VectorFormat::Pdf),
+    ]
 }